@@ -0,0 +1,292 @@
+// Generates search2048.h, the C declaration of the library's ABI, from
+// src/lib.rs: every `pub extern "C" fn`, `#[repr(C)]` struct, fieldless
+// enum, `pub const` and callback type. The header is written to
+// OUT_DIR/search2048.h, where packaging scripts pick it up. It does not
+// depend on the enabled features: feature-gated functions are wrapped in
+// `#ifdef SEARCH2048_FEATURE_<NAME>`.
+//
+// This is a line-based reader for the subset of Rust the ABI surface uses,
+// not a general parser: items must start at column 0, function signatures
+// end at the line holding `{`, and struct fields sit one per line. An item
+// it cannot read fails the build with its line, so the header never drifts
+// from the library.
+//
+// The build also sets `cfg(has_c_compiler)` when `cc` runs, so the test that
+// compiles the header is skipped, visibly, where there is none.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=PATH");
+    println!("cargo:rustc-check-cfg=cfg(has_c_compiler)");
+    if let Err(e) = write_header() {
+        panic!("search2048.h not generated: {e}");
+    }
+    if Command::new("cc").arg("--version").output().is_ok_and(|o| o.status.success()) {
+        println!("cargo:rustc-cfg=has_c_compiler");
+    }
+}
+
+fn write_header() -> Result<(), String> {
+    let src = fs::read_to_string("src/lib.rs").map_err(|e| format!("reading src/lib.rs: {e}"))?;
+    let header = generate(&src)?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR").map_err(|e| format!("OUT_DIR: {e}"))?);
+    let path = out_dir.join("search2048.h");
+    fs::write(&path, header).map_err(|e| format!("writing {}: {e}", path.display()))
+}
+
+#[derive(Default)]
+struct Header {
+    defines: Vec<String>,
+    enums: Vec<String>,
+    opaque: Vec<String>,
+    structs: Vec<String>,
+    typedefs: Vec<String>,
+    functions: Vec<String>,
+}
+
+fn generate(src: &str) -> Result<String, String> {
+    let lines: Vec<&str> = src.lines().collect();
+    let mut h = Header::default();
+    let mut docs: Vec<String> = Vec::new();
+    let mut repr: Option<String> = None;
+    let mut feature: Option<String> = None;
+    let mut i = 0;
+    // Index of the line closing the item that starts at `from`
+    let item_end = |from: usize, end: &dyn Fn(&str) -> bool| {
+        (from..lines.len()).find(|&k| end(lines[k])).ok_or_else(|| format!("src/lib.rs:{}: item never ends", from + 1))
+    };
+    let unreadable = |at: usize, what: &str| format!("src/lib.rs:{}: cannot read this {what}", at + 1);
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(doc) = line.strip_prefix("///") {
+            docs.push(doc.strip_prefix(' ').unwrap_or(doc).to_string());
+            i += 1;
+            continue;
+        }
+        if let Some(r) = line.strip_prefix("#[repr(") {
+            repr = Some(r.trim_end_matches(")]").to_string());
+            i += 1;
+            continue;
+        }
+        if let Some(f) = line.strip_prefix("#[cfg(feature = \"") {
+            feature = Some(f.trim_end_matches("\")]").to_string());
+            i += 1;
+            continue;
+        }
+        if line.starts_with("#[") {
+            i += 1;
+            continue;
+        }
+        if line.starts_with("pub extern \"C\" fn ") {
+            let start = i;
+            i = item_end(start, &|l| l.trim_end().ends_with('{'))?;
+            let sig = lines[start..=i].iter().map(|l| l.trim()).collect::<Vec<_>>().join(" ");
+            let decl = comment(&docs) + &function(&sig).ok_or_else(|| unreadable(start, "function"))? + ";";
+            h.functions.push(match &feature {
+                Some(f) => format!("#ifdef {}\n{decl}\n#endif", feature_macro(f)),
+                None => decl,
+            });
+        } else if let Some(rest) = line.strip_prefix("pub struct ") {
+            let name = ident(rest);
+            if repr.as_deref().is_some_and(|r| r.starts_with('C')) {
+                let mut body = Vec::new();
+                let end = item_end(i, &|l| l == "}")?;
+                for (k, l) in lines.iter().enumerate().take(end).skip(i + 1) {
+                    if let Some(field) = l.trim().strip_prefix("pub ") {
+                        body.push(field_decl(field).ok_or_else(|| unreadable(k, "struct field"))?);
+                    }
+                }
+                i = end;
+                h.structs.push(format!("{}typedef struct {name} {{\n{}}} {name};", comment(&docs), body.concat()));
+            } else {
+                h.opaque.push(format!("typedef struct {name} {name};"));
+            }
+        } else if let Some(rest) = line.strip_prefix("pub enum ") {
+            let name = ident(rest);
+            let mut body = String::new();
+            let end = item_end(i, &|l| l == "}")?;
+            for l in &lines[i + 1..end] {
+                let (variant, note) = split_comment(l.trim());
+                if let Some((v, val)) = variant.trim_end_matches(',').split_once('=') {
+                    body += &format!("    {name}_{} = {},{}\n", v.trim(), val.trim(), note);
+                }
+            }
+            i = end;
+            h.enums.push(format!("{}enum {name} {{\n{body}}};", comment(&docs)));
+        } else if let Some(rest) = line.strip_prefix("pub const ") {
+            let (decl, note) = split_comment(rest);
+            let (name, value) = decl.split_once(':').zip(decl.split_once('=')).map(|(n, v)| (n.0, v.1))
+                .ok_or_else(|| unreadable(i, "const"))?;
+            let value = c_expr(value.trim().trim_end_matches(';'));
+            h.defines.push(format!("{}#define {} {value}{note}", comment(&docs), name.trim()));
+        } else if let Some(rest) = line.strip_prefix("pub type ") {
+            let (name, ty) = rest.split_once('=').ok_or_else(|| unreadable(i, "type alias"))?;
+            let sig = ty.trim().trim_end_matches(';');
+            if let Some(f) = sig.strip_prefix("extern \"C\" fn") {
+                h.typedefs.push(comment(&docs) + &fn_pointer(name.trim(), f).ok_or_else(|| unreadable(i, "callback type"))?);
+            }
+        }
+        docs.clear();
+        repr = None;
+        feature = None;
+        i += 1;
+    }
+
+    let mut out = String::from(
+        "/* search2048.h: C ABI of the search2048 library.\n \
+         * Generated by build.rs from src/lib.rs; do not edit. */\n\n\
+         #ifndef SEARCH2048_H\n#define SEARCH2048_H\n\n\
+         #include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n\n\
+         #ifdef __cplusplus\nextern \"C\" {\n#endif\n\n",
+    );
+    for section in [&h.defines, &h.enums, &h.opaque, &h.structs, &h.typedefs, &h.functions] {
+        if section.is_empty() { continue; }
+        let sep = if std::ptr::eq(section, &h.defines) { "\n" } else { "\n\n" };
+        out += &section.join(sep);
+        out += "\n\n";
+    }
+    out += "#ifdef __cplusplus\n}\n#endif\n\n#endif /* SEARCH2048_H */\n";
+    Ok(out)
+}
+
+/// Feature-gated functions are declared under this macro, so one header
+/// serves every build; define it when linking a build with the feature.
+fn feature_macro(name: &str) -> String {
+    format!("SEARCH2048_FEATURE_{}", name.to_uppercase().replace('-', "_"))
+}
+
+fn ident(s: &str) -> String {
+    s.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect()
+}
+
+/// Doc lines as a C comment block
+fn comment(docs: &[String]) -> String {
+    docs.iter().map(|d| format!("// {d}").trim_end().to_string() + "\n").collect()
+}
+
+/// Split a trailing `// note` off a line, returning the note as C
+fn split_comment(line: &str) -> (&str, String) {
+    match line.split_once("//") {
+        Some((code, note)) => (code.trim_end(), format!("  // {}", note.trim())),
+        None => (line, String::new()),
+    }
+}
+
+/// `name: Type,  // note` inside a struct
+fn field_decl(field: &str) -> Option<String> {
+    let (decl, note) = split_comment(field);
+    let (name, ty) = decl.trim_end_matches(',').split_once(':')?;
+    let (base, dims) = array_type(ty.trim())?;
+    Some(format!("    {} {}{};{}\n", c_type(&base), name.trim(), dims, note))
+}
+
+/// Peel `[T; N]` layers into a base type and C dimension suffixes
+fn array_type(ty: &str) -> Option<(String, String)> {
+    match ty.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(inner) => {
+            let (elem, len) = inner.rsplit_once(';')?;
+            let (base, dims) = array_type(elem.trim())?;
+            Some((base, format!("[{}]{dims}", c_expr(len.trim()))))
+        }
+        None => Some((ty.to_string(), String::new())),
+    }
+}
+
+/// Rust constant expression to C: casts dropped, `uN::MAX` and
+/// `iN::MIN`/`iN::MAX` spelled out
+fn c_expr(expr: &str) -> String {
+    let expr = expr.replace(" as usize", "");
+    let limit = expr.split_once("::").filter(|(_, l)| *l == "MAX" || *l == "MIN");
+    match limit {
+        Some((ty, "MIN")) if ty.starts_with('u') => "0".to_string(),
+        Some((ty, l)) if ty.starts_with('u') => format!("UINT{}_{l}", &ty[1..]),
+        Some((ty, l)) if ty.starts_with('i') => format!("INT{}_{l}", &ty[1..]),
+        _ if expr.contains(' ') => format!("({expr})"),
+        _ => expr,
+    }
+}
+
+fn c_type(ty: &str) -> String {
+    let ty = ty.trim();
+    if let Some(t) = ty.strip_prefix("*const ") { return format!("const {}*", c_type(t)); }
+    if let Some(t) = ty.strip_prefix("*mut ") { return format!("{}*", c_type(t)); }
+    // Nullable function pointers are plain function pointers in C
+    if let Some(t) = ty.strip_prefix("Option<").and_then(|t| t.strip_suffix('>')) { return c_type(t); }
+    match ty {
+        "u8" => "uint8_t", "u16" => "uint16_t", "u32" => "uint32_t", "u64" => "uint64_t",
+        "i8" => "int8_t", "i16" => "int16_t", "i32" => "int32_t", "i64" => "int64_t",
+        "f32" => "float", "f64" => "double", "bool" => "bool",
+        "usize" => "size_t", "isize" => "ptrdiff_t",
+        "c_char" => "char", "c_void" => "void",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Split a parameter list at top-level commas
+fn params(list: &str) -> Vec<String> {
+    let (mut out, mut cur, mut depth) = (Vec::new(), String::new(), 0);
+    for ch in list.chars() {
+        match ch {
+            '(' | '<' | '[' => depth += 1,
+            ')' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push(std::mem::take(&mut cur));
+                continue;
+            }
+            _ => {}
+        }
+        cur.push(ch);
+    }
+    out.push(cur);
+    out.into_iter().map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect()
+}
+
+fn c_params(list: &str) -> Option<String> {
+    let ps = params(list)
+        .iter()
+        .map(|p| {
+            let (name, ty) = p.split_once(':')?;
+            Some(format!("{} {}", c_type(ty), name.trim()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(if ps.is_empty() { "void".to_string() } else { ps.join(", ") })
+}
+
+/// Split `(params) -> ret` into the parameter list and C return type
+fn signature_parts(sig: &str) -> Option<(&str, String)> {
+    let open = sig.find('(')?;
+    let mut depth = 0;
+    let close = sig[open..]
+        .char_indices()
+        .find(|&(_, ch)| {
+            match ch {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })
+        .map(|(k, _)| open + k)?;
+    let ret = sig[close + 1..].trim().trim_end_matches('{').trim();
+    let ret = ret.strip_prefix("->").map_or("void".to_string(), c_type);
+    Some((&sig[open + 1..close], ret))
+}
+
+fn function(sig: &str) -> Option<String> {
+    let rest = sig.strip_prefix("pub extern \"C\" fn ")?;
+    let name = ident(rest);
+    let (list, ret) = signature_parts(rest)?;
+    Some(format!("{ret} {name}({})", c_params(list)?))
+}
+
+fn fn_pointer(name: &str, sig: &str) -> Option<String> {
+    let (list, ret) = signature_parts(sig)?;
+    Some(format!("typedef {ret} (*{name})({});", c_params(list)?))
+}
@@ -1,138 +1,854 @@
-/// Fast expectimax search engine for 2048 with bitboard and row lookup tables.
-/// Board packed as u64 (4 bits per cell, log2 values).
-/// Precomputed 65536-entry tables make moves and evaluation O(1) per row.
-/// Based on nneonneo/xificurk architecture; achieves 10M+ states/sec.
+//! Fast expectimax search engine for 2048 with bitboard and row lookup tables.
+//! Board packed as u64 (4 bits per cell, log2 values).
+//! Precomputed 65536-entry tables make moves and evaluation O(1) per row.
+//! Based on nneonneo/xificurk architecture; achieves 10M+ states/sec.
 
-use std::collections::HashMap;
-use std::cell::RefCell;
-use std::sync::Once;
+// The C ABI trusts its callers (ctypes) to pass valid buffers.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 type BB = u64;  // 16 nybbles: row0=bits[0:15], row1=[16:31], row2=[32:47], row3=[48:63]
 
-// ── Lookup tables (filled once at startup) ──
-static mut TBL_LEFT:  [u16; 65536] = [0; 65536];
-static mut TBL_RIGHT: [u16; 65536] = [0; 65536];
-static mut TBL_SCORE: [f64; 65536] = [0.0; 65536];  // merge score for left-move
-static mut TBL_HEUR:  [f64; 65536] = [0.0; 65536];  // heuristic score per row
-static INIT: Once = Once::new();
+// ── Lookup tables ──
+// Row tables are built whole for one board shape and weight set and never
+// changed afterwards: the setters drop the config's set, the next search
+// builds a fresh one, and every search reads the set it snapshotted.
+
+/// Row lookup tables for one board shape and set of weights
+struct Tables {
+    left: Box<[u16; 65536]>,
+    right: Box<[u16; 65536]>,
+    score: Box<[f64; 65536]>,          // merge score for left-move
+    heur: Box<[f64; 65536]>,           // heuristic score per row
+    heur_surv: Box<[f64; 65536]>,      // same, survival profile
+    heur_fix: Box<[i64; 65536]>,       // heur in 1/FIXED_SCALE units, for fixed-point mode
+    heur_surv_fix: Box<[i64; 65536]>,  // same, survival profile
+}
+
+/// Per-cell snake weights, 8 orientations
+static TBL_SNAKE: LazyLock<[[f64; 16]; 8]> = LazyLock::new(|| {
+    let mut snake = [[0.0; 16]; 8];
+    for (o, weights) in snake.iter_mut().enumerate() {
+        for k in 0..16 {
+            weights[snake_cell(o, k)] = SNAKE_BASE.powi(15 - k as i32);
+        }
+    }
+    snake
+});
+static WALLS: AtomicU64 = AtomicU64::new(0);  // wall nybbles outside the board shape (0 = full 4×4)
+
+/// Heuristic weights baked into the row table (see `set_eval_weights`).
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct EvalWeights {
+    pub lost: f64,    // per-line baseline so every playable board scores > 0
+    pub empty: f64,   // per empty cell
+    pub merges: f64,  // per group of mergeable equal tiles
+    pub mono: f64,    // monotonicity penalty (Δrank⁴)
+    pub sum: f64,     // tile-mass penalty (rank³·⁵)
+    // Board-level terms (0 = off)
+    pub corner: f64,         // scales the max-tile corner bonus/penalty
+    pub corner_soften: f64,  // 0–1 cut to the off-corner penalty when one move restores it
+    pub corner_min_tile: f64,  // corner term applies only once the max tile reaches this value
+    pub trapped: f64,        // penalty per rank² of tiles walled in by other values
+    pub scatter: f64,        // penalty per rank² of high duplicates with no adjacent twin
+    pub snake: f64,          // scales the best-orientation snake-path sum
+    pub chain: f64,          // bonus per rank along the descending chain from a cornered max tile
+    pub grip: f64,           // bonus per rank of the second-largest tile beside a cornered max tile
+    pub anchor: f64,         // bonus per rank of a full, strictly descending snake anchor row
+    pub snake_power: f64,    // snake cells count tile value^this (1 = linear in value)
+    pub pattern: f64,        // penalty per rank² of cells breaking the target pattern
+    pub scatter_reach: f64,  // a duplicate with a twin this many steps away or closer is not scattered
+    pub scatter_falloff: f64,  // scattered tiles weigh (distance to twin / 6)^this (0 = all alike)
+    pub corner_tile_growth: f64,  // corner term × (1 + this × max rank / 11), i.e. 1 + this at 2048
+    pub corner_fill_growth: f64,  // corner term × (1 + this × share of cells occupied)
+    pub breathing: f64,      // bonus per empty cell at the tail of the best snake path
+}
 
 // Heuristic weights (nneonneo/xificurk CMA-ES optimized — EXACT values)
-const W_LOST:   f64 = 200000.0;
-const W_EMPTY:  f64 = 270.0;
-const W_MERGES: f64 = 700.0;
-const W_MONO:   f64 = 47.0;
-const W_SUM:    f64 = 11.0;
+const DEFAULT_WEIGHTS: EvalWeights = EvalWeights {
+    lost: 200000.0,
+    empty: 270.0,
+    merges: 700.0,
+    mono: 47.0,
+    sum: 11.0,
+    corner: 0.0,
+    corner_soften: 0.0,
+    corner_min_tile: 0.0,
+    trapped: 0.0,
+    scatter: 0.0,
+    snake: 0.0,
+    chain: 0.0,
+    grip: 0.0,
+    anchor: 0.0,
+    snake_power: 1.0,
+    pattern: 0.0,
+    scatter_reach: 1.0,
+    scatter_falloff: 0.0,
+    corner_tile_growth: 0.0,
+    corner_fill_growth: 0.0,
+    breathing: 0.0,
+};
+
+impl EvalWeights {
+    /// Every weight in declaration order; destructures exhaustively so a new
+    /// field cannot be missed
+    fn values(&self) -> [f64; 21] {
+        let EvalWeights {
+            lost, empty, merges, mono, sum, corner, corner_soften, corner_min_tile,
+            trapped, scatter, snake, chain, grip, anchor, snake_power, pattern,
+            scatter_reach, scatter_falloff, corner_tile_growth, corner_fill_growth, breathing,
+        } = *self;
+        [lost, empty, merges, mono, sum, corner, corner_soften, corner_min_tile,
+         trapped, scatter, snake, chain, grip, anchor, snake_power, pattern,
+         scatter_reach, scatter_falloff, corner_tile_growth, corner_fill_growth, breathing]
+    }
+}
+
+// Endgame profile: buys space and merges over structure once the board is
+// nearly full (see `set_endgame_threshold`)
+const SURVIVAL_WEIGHTS: EvalWeights = EvalWeights {
+    empty: 1000.0,
+    merges: 1400.0,
+    ..DEFAULT_WEIGHTS
+};
+
 const MONO_POW: i32 = 4;
+const WALL_RANK: u8 = 15;          // nybble of a cell outside a smaller board shape
 const SUM_POW:  f64 = 3.5;
+const FIXED_SCALE: f64 = 1024.0;   // fixed-point values are whole multiples of 1/this
+const FIXED_ONE: i64 = 1 << 16;    // 1.0 for the Q16 spawn odds and blend of fixed-point mode
 const CPROB_THRESH: f64 = 0.0001;  // prune branches below this probability
+const MAX_DEPTH_LIMIT: u32 = 32;   // hard ceiling for set_max_depth
+const CASCADE_MIN_CHAIN: u32 = 4;  // tiles a cascade must roll up to count
+const CASCADE_MARGIN: f64 = 0.01;  // relative score band a cascade move may win from
+const SCATTER_MIN_RANK: u8 = 6;    // duplicates from 64 up count as scattered
+const SCATTER_MAX_DIST: f64 = 6.0; // farthest apart two cells can be (corner to corner)
+const SNAKE_BASE: f64 = 1.5;       // weight ratio between consecutive snake cells
+const BREATHING_CELLS: usize = 4;  // tail cells of the snake path the breathing term watches
+const BFS_FRONTIER_LIMIT: usize = 1 << 16;  // boards kept per ply by moves_to_target
+const THREAT_CELLS: usize = 2;     // cells flagged by threatened_cells
+const THREAT_DEPTH: u32 = 2;       // move-node depth used to value each forced spawn
+const SOFTMAX_REL_TEMP: f64 = 0.01;  // softmax temperature as a fraction of |best score|
+const BF_EXPLORE: f64 = 0.01;      // best-first UCB bonus as a fraction of |node value|
+const OPENING_MAX_RANK: u8 = 7;    // classify_position: max tile ≤ 128 is the opening
+const ENDGAME_MIN_RANK: u8 = 11;   // … and ≥ 2048 the endgame
+const DANGER_EMPTY: u32 = 1;       // … in danger at this many empty cells or fewer
+const SNAKE_ALIGNED_CELLS: usize = 4;  // … snake-aligned if this much of the path descends
 
-// Transposition table: bitboard → (depth, score)
-thread_local! {
-    static TT: RefCell<HashMap<BB, (u32, f64)>> = RefCell::new(HashMap::with_capacity(1 << 20));
+// Corner term base values (× max rank², scaled by EvalWeights::corner)
+const CORNER_BONUS:     f64 = 500.0;
+const EDGE_PENALTY:     f64 = 1000.0;
+const INTERIOR_PENALTY: f64 = 3000.0;
+const CORNER_GROWTH_RANK: f64 = 11.0;  // max rank at which corner_tile_growth adds its full value (2048)
+
+// Cell order of the row-wise snake from the top-left corner; the other seven
+// orientations are its mirror images and transposes
+const SNAKE_PATH: [usize; 16] = [0, 1, 2, 3, 7, 6, 5, 4, 8, 9, 10, 11, 15, 14, 13, 12];
+
+// Evaluation term bits for `set_eval_term_mask` (the `lost` baseline is
+// always on)
+pub const TERM_EMPTY:   u32 = 1 << 0;
+pub const TERM_MERGES:  u32 = 1 << 1;
+pub const TERM_MONO:    u32 = 1 << 2;
+pub const TERM_SUM:     u32 = 1 << 3;
+pub const TERM_CORNER:  u32 = 1 << 4;
+pub const TERM_TRAPPED: u32 = 1 << 5;
+pub const TERM_SCATTER: u32 = 1 << 6;
+pub const TERM_SNAKE:   u32 = 1 << 7;
+pub const TERM_CHAIN:   u32 = 1 << 8;
+pub const TERM_GRIP:    u32 = 1 << 9;
+pub const TERM_ANCHOR:  u32 = 1 << 10;
+pub const TERM_PATTERN: u32 = 1 << 11;
+pub const TERM_BREATHING: u32 = 1 << 12;
+pub const TERM_ALL:     u32 = 0x1FFF;
+pub const NUM_TERMS:    u32 = 13;  // entries written by evaluate_components
+
+// ── Spawn distribution ──
+
+const MAX_SPAWN_OUTCOMES: usize = 4;
+
+/// Tiles a spawn can produce (as ranks) with their probabilities
+#[derive(Clone, Copy)]
+struct SpawnDist {
+    ranks: [u8; MAX_SPAWN_OUTCOMES],
+    probs: [f64; MAX_SPAWN_OUTCOMES],
+    n: usize,
 }
 
-fn init_tables() {
-    INIT.call_once(|| {
-        for rv in 0u32..65536 {
-            let t = [
-                (rv & 0xF) as u8,
-                ((rv >> 4) & 0xF) as u8,
-                ((rv >> 8) & 0xF) as u8,
-                ((rv >> 12) & 0xF) as u8,
-            ];
+impl SpawnDist {
+    /// The standard game: a 2 or a 4 with probabilities `[p2, p4]`, both
+    /// given so that neither is rounded by computing it from the other
+    const fn two_four([p2, p4]: [f64; 2]) -> SpawnDist {
+        SpawnDist { ranks: [1, 2, 0, 0], probs: [p2, p4, 0.0, 0.0], n: 2 }
+    }
 
-            // ── Row heuristic (nneonneo formula) ──
-            let mut empty = 0.0f64;
-            let mut merges = 0.0f64;
-            let mut sum_val = 0.0f64;
-            let mut mono_l = 0.0f64;
-            let mut mono_r = 0.0f64;
-            let mut prev: u8 = 0;
-            let mut counter = 0i32;
-
-            for i in 0..4 {
-                if t[i] == 0 {
-                    empty += 1.0;
-                } else {
-                    sum_val += (t[i] as f64).powf(SUM_POW);
-                    if prev == t[i] {
-                        counter += 1;
-                    } else if counter > 0 {
-                        merges += 1.0 + counter as f64;
-                        counter = 0;
-                    }
-                    prev = t[i];
-                }
-                if i > 0 {
-                    let a = (t[i - 1] as f64).powi(MONO_POW);
-                    let b = (t[i] as f64).powi(MONO_POW);
-                    if t[i - 1] > t[i] { mono_l += a - b; }
-                    else if t[i] > t[i - 1] { mono_r += b - a; }
-                }
+    fn outcomes(&self) -> impl Iterator<Item = (u8, f64)> + '_ {
+        self.ranks[..self.n].iter().copied().zip(self.probs[..self.n].iter().copied())
+    }
+
+    /// Rank for a uniform draw `x` in [0, 1)
+    fn sample(&self, x: f64) -> u8 {
+        let mut acc = 0.0;
+        for (rank, p) in self.outcomes() {
+            acc += p;
+            if x < acc { return rank; }
+        }
+        self.ranks[self.n - 1]
+    }
+
+    /// Odds as the search weighs them under risk weighting `risk`: each
+    /// p becomes p^(1/risk), renormalized, so risk 1 keeps the true odds
+    /// and higher values give the rarer tiles more say
+    fn risk_weighted(&self, risk: f64) -> SpawnDist {
+        if risk == 1.0 { return *self; }
+        let mut out = *self;
+        for p in &mut out.probs[..self.n] { *p = p.powf(1.0 / risk); }
+        let total: f64 = out.probs[..self.n].iter().sum();
+        for p in &mut out.probs[..self.n] { *p /= total; }
+        out
+    }
+}
+
+// ── Runtime configuration ──
+// Changed through the C ABI setters; every top-level search snapshots it into
+// the thread-local CFG so the hot path never touches the lock.
+
+#[derive(Clone)]
+struct Config {
+    weights: EvalWeights,
+    spawn: SpawnDist,       // spawned tiles and their odds (default 90% 2, 10% 4)
+    spawn_risk: f64,        // chance nodes weigh spawn odds p as p^(1/risk), renormalized (1 = true odds)
+    max_chance_cells: u32,  // empty cells expanded per chance node (16 = all)
+    chance_selector: Option<ChanceSelector>,  // replaces the built-in chance cell choice
+    win_target: u16,        // tile value that counts as a win
+    chance_blend: f64,      // 0 = pure expectation, 1 = worst-case spawn
+    aggression: f64,        // root blend: 0 = lookahead value only, 1 = merge score only
+    move_objective: Option<MoveObjective>,  // replaces the built-in root move score
+    score_norm: u32,        // SCORES_* rescaling of ranked output scores
+    chance_cutoff: bool,    // stop root chance nodes once they cannot beat the best move
+    fixed_point: bool,      // leaf values and chance averages in scaled integers
+    corner_safe_eps: f64,   // relative band in which a corner-keeping move is preferred (0 = off)
+    unstick_empty: u32,     // below this many empties a stuck root plays for mobility (0 = off)
+    mirror_empty: u32,      // above this many empties root moves average over the 8 symmetries (0 = off)
+    survival_guard: bool,   // never play into a game-ending spawn when some move avoids it
+    explore_temp: f64,      // softmax temperature for sampling the played move, × |best| (0 = argmax)
+    variance_penalty: f64,  // root scores lose this × the std dev of their spawn outcomes (0 = off)
+    reverse_penalty: f64,   // relative score cut for undoing the caller's last move (0 = off)
+    chain_relaxed: bool,    // chain term accepts any descent, not just exact halving
+    merge_accumulate: bool, // move values include the move's merge score, at every depth
+    cascade_bias: bool,     // prefer moves that keep a ready merge cascade
+    ntuple: Option<Arc<NTuple>>,  // replaces the handcrafted leaf evaluation
+    survival_weights: EvalWeights,
+    endgame_empty: u32,     // use survival weights below this many empties (0 = off)
+    survival_from_move: u32,  // context searches from this move on use survival weights (0 = off)
+    force_survival: bool,   // per-search: survival weights everywhere (set from move context)
+    max_depth: u32,         // deepest search allowed, requested or adaptive
+    preferred_corner: u8,   // anchor for the corner term (CORNER_ANY = all four)
+    tt_array_bits: u32,     // log2 slots of the array TT (0 = HashMap TT)
+    tt_depth_band: u32,     // TT entries are reused within bands of this many depths (1 = exact)
+    search_seed: Option<u64>,  // seeds randomized search decisions (None = deterministic)
+    decision_log: u32,      // searches kept in the decision log (0 = off)
+    term_mask: u32,         // TERM_* bits left enabled in both weight profiles
+    snake_pin: Option<u8>,  // snake orientation fixed by pin_orientation (None = best of 8)
+    snake_blend: f64,       // relative gap under which the top two orientations blend (0 = hard max)
+    target_pattern: Option<[u8; 16]>,  // required rank per cell, PATTERN_ANY_RANK = any (None = no pattern)
+    board_shape: [u8; 2],   // rows × columns in play, top-left aligned (4×4 = full board)
+    tables: Option<Arc<Tables>>,  // row tables for the settings above (None = not built yet)
+}
+
+const DEFAULT_CONFIG: Config = Config {
+    weights: DEFAULT_WEIGHTS,
+    spawn: SpawnDist::two_four([0.9, 0.1]),
+    spawn_risk: 1.0,
+    max_chance_cells: 16,
+    chance_selector: None,
+    win_target: 2048,
+    chance_blend: 0.0,
+    aggression: 0.0,
+    move_objective: None,
+    score_norm: SCORES_RAW,
+    chance_cutoff: false,
+    fixed_point: false,
+    corner_safe_eps: 0.0,
+    unstick_empty: 0,
+    mirror_empty: 0,
+    survival_guard: false,
+    explore_temp: 0.0,
+    variance_penalty: 0.0,
+    reverse_penalty: 0.0,
+    chain_relaxed: false,
+    merge_accumulate: false,
+    cascade_bias: false,
+    ntuple: None,
+    survival_weights: SURVIVAL_WEIGHTS,
+    endgame_empty: 0,
+    survival_from_move: 0,
+    force_survival: false,
+    max_depth: 16,
+    preferred_corner: CORNER_ANY,
+    tt_array_bits: 0,
+    tt_depth_band: 1,
+    search_seed: None,
+    decision_log: 0,
+    term_mask: TERM_ALL,
+    snake_pin: None,
+    snake_blend: 0.0,
+    target_pattern: None,
+    board_shape: [4, 4],
+    tables: None,
+};
+
+static CONFIG: Mutex<Config> = Mutex::new(DEFAULT_CONFIG);
+
+impl Config {
+    /// Stable digest of every setting (the per-search `force_survival` flag
+    /// and the tables derived from the rest aside). Destructures
+    /// exhaustively so a new field cannot be missed.
+    fn fingerprint(&self) -> u64 {
+        let Config {
+            weights, spawn, spawn_risk, max_chance_cells, chance_selector, win_target, chance_blend, aggression,
+            move_objective, score_norm, chance_cutoff, fixed_point, corner_safe_eps, unstick_empty, mirror_empty,
+            survival_guard, explore_temp, variance_penalty, reverse_penalty, chain_relaxed,
+            merge_accumulate, cascade_bias, ntuple, survival_weights, endgame_empty,
+            survival_from_move, force_survival: _, max_depth, preferred_corner, tt_array_bits,
+            tt_depth_band, search_seed, decision_log, term_mask, snake_pin, snake_blend,
+            target_pattern, board_shape, tables: _,
+        } = self;
+        let mut h = Fnv::new();
+        for w in [weights, survival_weights] {
+            for v in w.values() { h.u64(v.to_bits()); }
+        }
+        h.u64(spawn.n as u64);
+        for (rank, p) in spawn.outcomes() {
+            h.u64(rank as u64);
+            h.u64(p.to_bits());
+        }
+        for v in [*max_chance_cells, *win_target as u32, *score_norm, *chance_cutoff as u32, *fixed_point as u32,
+                  *chain_relaxed as u32, *merge_accumulate as u32, *cascade_bias as u32,
+                  *survival_guard as u32, *unstick_empty, *mirror_empty, *endgame_empty, *survival_from_move, *max_depth, *preferred_corner as u32,
+                  *tt_array_bits, *tt_depth_band, *decision_log, *term_mask] {
+            h.u64(v as u64);
+        }
+        h.u64(chance_blend.to_bits());
+        h.u64(spawn_risk.to_bits());
+        h.u64(aggression.to_bits());
+        h.u64(corner_safe_eps.to_bits());
+        h.u64(explore_temp.to_bits());
+        h.u64(snake_blend.to_bits());
+        h.u64(variance_penalty.to_bits());
+        h.u64(reverse_penalty.to_bits());
+        h.u64(ntuple.as_ref().map_or(0, |n| n.digest));
+        h.u64(search_seed.is_some() as u64);
+        h.u64(search_seed.unwrap_or(0));
+        h.u64(snake_pin.map_or(u64::MAX, |o| o as u64));
+        h.u64(chance_selector.map_or(0, |f| f as usize as u64));
+        h.u64(move_objective.map_or(0, |f| f as usize as u64));
+        h.u64(target_pattern.is_some() as u64);
+        h.bytes(&target_pattern.unwrap_or([0; 16]));
+        h.bytes(board_shape);
+        h.0
+    }
+}
+
+/// 64-bit FNV-1a: tiny and stable across builds, unlike std's hasher
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xCBF29CE484222325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100000001B3);
+        }
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.bytes(&v.to_le_bytes());
+    }
+}
+
+fn config() -> MutexGuard<'static, Config> {
+    CONFIG.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Counters for the most recent top-level search on the calling thread
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SearchStats {
+    pub move_nodes: u64,
+    pub chance_nodes: u64,
+    pub evals: u64,
+    pub tt_hits: u64,
+    pub elapsed_us: u64,
+    pub depth: u32,  // effective depth after adaptive deepening
+    pub tt_probes: u64,      // chance-node TT lookups
+    pub tt_collisions: u64,  // … that found the slot held by another position (array TT only)
+}
+
+/// `SearchStats` plus where the expectimax search spent its time, in
+/// nanoseconds (`profiling` builds only, see `get_search_timings`)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SearchTimings {
+    pub stats: SearchStats,
+    pub total_ns: u64,  // the whole search, as `stats.elapsed_us`
+    pub moves_ns: u64,  // making the moves of move nodes
+    pub eval_ns: u64,   // leaf evaluations
+    pub tt_ns: u64,     // transposition-table lookups and stores
+    pub cells_ns: u64,  // choosing which cells chance nodes expand
+}
+
+/// Search phases `timed` can charge
+#[derive(Clone, Copy)]
+enum Phase {
+    Moves,
+    Eval,
+    Tt,
+    Cells,
+}
+
+/// Run `f`, charging its time to `phase` in `profiling` builds; otherwise
+/// no clock is read
+#[inline(always)]
+fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "profiling")]
+    {
+        let start = Instant::now();
+        let r = f();
+        let ns = start.elapsed().as_nanos() as u64;
+        TIMINGS.with(|t| {
+            let mut t = t.borrow_mut();
+            *match phase {
+                Phase::Moves => &mut t.moves_ns,
+                Phase::Eval => &mut t.eval_ns,
+                Phase::Tt => &mut t.tt_ns,
+                Phase::Cells => &mut t.cells_ns,
+            } += ns;
+        });
+        r
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        let _ = phase;
+        f()
+    }
+}
+
+// ── Transposition table ──
+// Default: exact HashMap keyed on the bitboard, evicted past 4M entries down
+// to the principal variation of the last search. Optional: direct-mapped
+// array of 32-byte entries (two per cache line), always-replace, selected
+// with `set_tt_array`.
+
+const TT_MAP_LIMIT: usize = 1 << 22;
+
+#[repr(C, align(32))]
+#[derive(Clone, Copy, Default)]
+struct TtEntry {
+    key: u64,
+    score: f64,
+    depth: u32,  // 0 = empty slot (chance nodes are only stored at depth ≥ 1)
+    _pad: u32,
+}
+
+enum TransTable {
+    Map { map: HashMap<BB, (u32, f64)>, pv: Vec<BB> },  // pv: keys kept by `evict`
+    Array { entries: Vec<TtEntry>, bits: u32 },
+}
+
+impl TransTable {
+    fn new(array_bits: u32) -> TransTable {
+        if array_bits == 0 {
+            TransTable::Map { map: HashMap::with_capacity(1 << 20), pv: Vec::new() }
+        } else {
+            TransTable::Array { entries: vec![TtEntry::default(); 1 << array_bits], bits: array_bits }
+        }
+    }
+
+    fn array_bits(&self) -> u32 {
+        match self {
+            TransTable::Map { .. } => 0,
+            TransTable::Array { bits, .. } => *bits,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            TransTable::Map { map, .. } => map.len(),
+            TransTable::Array { entries, .. } => entries.iter().filter(|e| e.depth > 0).count(),
+        }
+    }
+
+    #[inline]
+    fn slot(board: BB, bits: u32) -> usize {
+        (board.wrapping_mul(0x9E3779B97F4A7C15) >> (64 - bits)) as usize
+    }
+
+    fn get(&self, board: BB) -> Option<(u32, f64)> {
+        self.probe(board).0
+    }
+
+    /// `get`, plus whether the lookup collided: the slot held a different
+    /// position, which the exact HashMap never does
+    fn probe(&self, board: BB) -> (Option<(u32, f64)>, bool) {
+        match self {
+            TransTable::Map { map, .. } => (map.get(&board).copied(), false),
+            TransTable::Array { entries, bits } => {
+                let e = &entries[Self::slot(board, *bits)];
+                if e.depth == 0 { return (None, false); }
+                if e.key != board { return (None, true); }
+                (Some((e.depth, e.score)), false)
             }
-            if counter > 0 { merges += 1.0 + counter as f64; }
-
-            let heur = W_LOST
-                + W_EMPTY * empty
-                + W_MERGES * merges
-                - W_MONO * mono_l.min(mono_r)
-                - W_SUM * sum_val;
-
-            unsafe { TBL_HEUR[rv as usize] = heur; }
-
-            // ── Left move ──
-            let mut line = [0u8; 4];
-            let mut w = 0usize;
-            for i in 0..4 { if t[i] != 0 { line[w] = t[i]; w += 1; } }
-
-            let mut out = [0u8; 4];
-            let mut score = 0.0f64;
-            let mut i = 0usize;
-            let mut o = 0usize;
-            while i < 4 && line[i] != 0 {
-                if i + 1 < 4 && line[i] == line[i + 1] {
-                    let nr = line[i] + 1;
-                    out[o] = if nr <= 15 { nr } else { 15 };
-                    score += (1u64 << (nr as u32)) as f64;
-                    i += 2;
-                } else {
-                    out[o] = line[i];
-                    i += 1;
-                }
-                o += 1;
-            }
-            let left = (out[0] as u16) | ((out[1] as u16) << 4)
-                     | ((out[2] as u16) << 8) | ((out[3] as u16) << 12);
-
-            // ── Right move (reverse, left-compress, reverse) ──
-            let rt = [t[3], t[2], t[1], t[0]];
-            let mut rline = [0u8; 4];
-            w = 0;
-            for i in 0..4 { if rt[i] != 0 { rline[w] = rt[i]; w += 1; } }
-
-            let mut rout = [0u8; 4];
-            i = 0; o = 0;
-            while i < 4 && rline[i] != 0 {
-                if i + 1 < 4 && rline[i] == rline[i + 1] {
-                    let nr = rline[i] + 1;
-                    rout[o] = if nr <= 15 { nr } else { 15 };
-                    i += 2;
-                } else {
-                    rout[o] = rline[i];
-                    i += 1;
+        }
+    }
+
+    fn insert(&mut self, board: BB, depth: u32, score: f64) {
+        match self {
+            TransTable::Map { map, .. } => {
+                map.insert(board, (depth, score));
+                if map.len() > TT_MAP_LIMIT {
+                    self.evict();
+                    log(LOG_DEBUG, || {
+                        format!("transposition table full ({TT_MAP_LIMIT} entries), evicted to {}", self.len())
+                    });
                 }
-                o += 1;
             }
-            let right = (rout[3] as u16) | ((rout[2] as u16) << 4)
-                       | ((rout[1] as u16) << 8) | ((rout[0] as u16) << 12);
+            TransTable::Array { entries, bits } => {
+                entries[Self::slot(board, *bits)] = TtEntry { key: board, score, depth, _pad: 0 };
+            }
+        }
+    }
 
-            unsafe {
-                TBL_LEFT[rv as usize] = left;
-                TBL_RIGHT[rv as usize] = right;
-                TBL_SCORE[rv as usize] = score;
+    fn clear(&mut self) {
+        match self {
+            TransTable::Map { map, pv } => {
+                map.clear();
+                pv.clear();
             }
+            TransTable::Array { entries, .. } => entries.fill(TtEntry::default()),
         }
-    });
+    }
+
+    /// Record the principal variation that `evict` keeps (HashMap TT only:
+    /// array slots are overwritten by whatever hashes there)
+    fn set_pv(&mut self, keys: Vec<BB>) {
+        if let TransTable::Map { pv, .. } = self { *pv = keys; }
+    }
+
+    /// Drop every entry except those on the recorded principal variation
+    fn evict(&mut self) {
+        match self {
+            TransTable::Map { map, pv } => {
+                let kept: Vec<(BB, (u32, f64))> = pv.iter().filter_map(|&k| map.get(&k).map(|&v| (k, v))).collect();
+                map.clear();
+                map.extend(kept);
+            }
+            TransTable::Array { .. } => self.clear(),
+        }
+    }
+}
+
+thread_local! {
+    static TT: RefCell<TransTable> = RefCell::new(TransTable::new(0));
+    static CFG: RefCell<Config> = const { RefCell::new(DEFAULT_CONFIG) };
+    static LAST_MARGIN: Cell<f64> = const { Cell::new(0.0) };  // best − second of the last search
+    static STATS: RefCell<SearchStats> = RefCell::new(SearchStats::default());
+    static ABORT: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };  // set by cancel_search
+    static SEARCH_RNG: RefCell<Option<Rng>> = const { RefCell::new(None) };  // Some only with a search seed
+    static MOVE_NUMBER: Cell<Option<u32>> = const { Cell::new(None) };  // caller's move count, context searches only
+    static LAST_MOVE: Cell<Option<Direction>> = const { Cell::new(None) };  // caller's previous move, look-back searches only
+    static KEEP_TT: Cell<bool> = const { Cell::new(false) };  // SearchContext searches carry their TT over
+    static ADVERSARIAL: Cell<bool> = const { Cell::new(false) };  // minimax searches: chance nodes take the worst spawn
+    #[cfg(feature = "profiling")]
+    static TIMINGS: RefCell<SearchTimings> = RefCell::new(SearchTimings::default());  // phase times of the last search
+    #[cfg(test)]
+    static ABORT_AT: Cell<Option<(u32, u64)>> = const { Cell::new(None) };  // tests: abort searches of this depth at this many move nodes
+}
+
+/// Has this thread's search been cancelled? Aborted searches unwind at once
+/// with meaningless scores, which the caller discards.
+#[inline]
+fn aborted() -> bool {
+    #[cfg(test)]
+    if let Some((depth, nodes)) = ABORT_AT.with(|a| a.get()) {
+        if STATS.with(|s| s.borrow().depth >= depth && s.borrow().move_nodes >= nodes) { return true; }
+    }
+    ABORT.with(|a| a.borrow().as_ref().is_some_and(|f| f.load(Ordering::Relaxed)))
+}
+
+#[inline(always)]
+fn stat(f: impl FnOnce(&mut SearchStats)) {
+    STATS.with(|s| f(&mut s.borrow_mut()));
+}
+
+#[inline]
+fn unpack_row(rv: u32) -> [u8; 4] {
+    [
+        (rv & 0xF) as u8,
+        ((rv >> 4) & 0xF) as u8,
+        ((rv >> 8) & 0xF) as u8,
+        ((rv >> 12) & 0xF) as u8,
+    ]
+}
+
+/// Raw, unweighted features of one line (row or column)
+#[derive(Clone, Copy, Default)]
+struct RowFeatures {
+    empty: f64,
+    merges: f64,
+    mono: f64,  // the smaller of the two directional monotonicity penalties
+    sum: f64,
+}
+
+/// Row heuristic features (nneonneo formula)
+fn row_features(rv: u32) -> RowFeatures {
+    let t = unpack_row(rv);
+    let len = line_len(&t);
+    let mut empty = 0.0f64;
+    let mut merges = 0.0f64;
+    let mut sum_val = 0.0f64;
+    let mut mono_l = 0.0f64;
+    let mut mono_r = 0.0f64;
+    let mut prev: u8 = 0;
+    let mut counter = 0i32;
+
+    for i in 0..len {
+        if t[i] == 0 {
+            empty += 1.0;
+        } else {
+            sum_val += (t[i] as f64).powf(SUM_POW);
+            if prev == t[i] {
+                counter += 1;
+            } else if counter > 0 {
+                merges += 1.0 + counter as f64;
+                counter = 0;
+            }
+            prev = t[i];
+        }
+        if i > 0 {
+            let a = (t[i - 1] as f64).powi(MONO_POW);
+            let b = (t[i] as f64).powi(MONO_POW);
+            if t[i - 1] > t[i] { mono_l += a - b; }
+            else if t[i] > t[i - 1] { mono_r += b - a; }
+        }
+    }
+    if counter > 0 { merges += 1.0 + counter as f64; }
+
+    RowFeatures { empty, merges, mono: mono_l.min(mono_r), sum: sum_val }
+}
+
+#[inline]
+fn row_heur(f: &RowFeatures, w: &EvalWeights) -> f64 {
+    w.lost
+        + w.empty * f.empty
+        + w.merges * f.merges
+        - w.mono * f.mono
+        - w.sum * f.sum
+}
+
+/// Fill a row heuristic table for the given weights
+fn build_heur_table(table: &mut [f64; 65536], w: &EvalWeights) {
+    for rv in 0u32..65536 {
+        table[rv as usize] = row_heur(&row_features(rv), w);
+    }
+}
+
+/// Fill a fixed-point row table: `build_heur_table`'s values rounded to
+/// 1/`FIXED_SCALE`, with the tile mass taken from `exact_mass`
+fn build_fixed_table(table: &mut [i64; 65536], w: &EvalWeights) {
+    for rv in 0u32..65536 {
+        let f = RowFeatures { sum: exact_mass(rv), ..row_features(rv) };
+        table[rv as usize] = (row_heur(&f, w) * FIXED_SCALE).round() as i64;
+    }
+}
+
+/// `RowFeatures::sum` from correctly rounded operations only: rank^3.5
+/// (`SUM_POW`) as rank³·√rank, where `powf` may differ in the last bit
+/// from one libm to another
+fn exact_mass(rv: u32) -> f64 {
+    let t = unpack_row(rv);
+    t[..line_len(&t)]
+        .iter()
+        .filter(|&&r| r != 0)
+        .map(|&r| {
+            let r = r as f64;
+            r * r * r * r.sqrt()
+        })
+        .sum()
+}
+
+/// Weights with every term outside `mask` zeroed
+fn masked(w: &EvalWeights, mask: u32) -> EvalWeights {
+    let on = |bit: u32, v: f64| if mask & bit != 0 { v } else { 0.0 };
+    EvalWeights {
+        empty: on(TERM_EMPTY, w.empty),
+        merges: on(TERM_MERGES, w.merges),
+        mono: on(TERM_MONO, w.mono),
+        sum: on(TERM_SUM, w.sum),
+        corner: on(TERM_CORNER, w.corner),
+        trapped: on(TERM_TRAPPED, w.trapped),
+        scatter: on(TERM_SCATTER, w.scatter),
+        snake: on(TERM_SNAKE, w.snake),
+        chain: on(TERM_CHAIN, w.chain),
+        grip: on(TERM_GRIP, w.grip),
+        anchor: on(TERM_ANCHOR, w.anchor),
+        pattern: on(TERM_PATTERN, w.pattern),
+        breathing: on(TERM_BREATHING, w.breathing),
+        ..*w
+    }
+}
+
+/// A zeroed 65536-entry table, allocated straight on the heap
+fn zeroed_table<T: Copy + Default + std::fmt::Debug>() -> Box<[T; 65536]> {
+    vec![T::default(); 65536].try_into().unwrap()
+}
+
+impl Tables {
+    /// Move tables for the current board shape, and both row heuristic
+    /// tables with their fixed-point copies from the config's masked weights
+    fn new(cfg: &Config) -> Tables {
+        let mut t = Tables {
+            left: zeroed_table(),
+            right: zeroed_table(),
+            score: zeroed_table(),
+            heur: zeroed_table(),
+            heur_surv: zeroed_table(),
+            heur_fix: zeroed_table(),
+            heur_surv_fix: zeroed_table(),
+        };
+        let (w, surv) = (masked(&cfg.weights, cfg.term_mask), masked(&cfg.survival_weights, cfg.term_mask));
+        build_move_tables(&mut t);
+        build_heur_table(&mut t.heur, &w);
+        build_heur_table(&mut t.heur_surv, &surv);
+        build_fixed_table(&mut t.heur_fix, &w);
+        build_fixed_table(&mut t.heur_surv_fix, &surv);
+        t
+    }
+}
+
+/// Run `f` on the tables of this thread's config snapshot, loading the
+/// snapshot first if this thread has none yet
+#[inline]
+fn with_tables<R>(f: impl FnOnce(&Tables) -> R) -> R {
+    CFG.with(|c| {
+        if c.borrow().tables.is_none() { load_config(); }
+        f(c.borrow().tables.as_deref().unwrap())
+    })
+}
+
+/// Background searches and parallel batches under way. They read the board
+/// shape live from threads of their own, so `set_board_shape` refuses while
+/// any is running. Changed only under the config lock, which the setter
+/// holds while it reshapes.
+static SHAPE_READERS: AtomicU32 = AtomicU32::new(0);
+
+/// Registration in `SHAPE_READERS`, withdrawn on drop
+struct ShapeReader;
+
+impl ShapeReader {
+    /// Register; taking the config lock guard proves no reshape is under way
+    fn new(_cfg: &MutexGuard<Config>) -> ShapeReader {
+        SHAPE_READERS.fetch_add(1, Ordering::SeqCst);
+        ShapeReader
+    }
+}
+
+impl Drop for ShapeReader {
+    fn drop(&mut self) {
+        SHAPE_READERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// False (recording `Busy`) while another thread may be reading the board
+/// shape; the caller holds the config lock for the check and the reshape
+fn shape_idle(_cfg: &MutexGuard<Config>) -> bool {
+    let idle = SHAPE_READERS.load(Ordering::SeqCst) == 0;
+    if !idle { set_error(ErrorCode::Busy); }
+    idle
+}
+
+/// Cells of a line that are on the board: all four, or on a smaller shape
+/// those before the first wall (walls sit at the far end of every line)
+#[inline]
+fn line_len(t: &[u8; 4]) -> usize {
+    if walls() == 0 { return 4; }
+    t.iter().position(|&v| v == WALL_RANK).unwrap_or(4)
+}
+
+/// Fill the left/right move and merge-score tables for the current shape.
+/// On a smaller shape tiles slide only within `line_len`, walls stay put,
+/// and no merge may produce the wall rank.
+fn build_move_tables(tables: &mut Tables) {
+    let walled = walls() != 0;
+    let merges = |a: u8, b: u8| a == b && !(walled && a + 1 >= WALL_RANK);
+    for rv in 0u32..65536 {
+        let t = unpack_row(rv);
+        let len = line_len(&t);
+
+        // ── Left move ──
+        let mut line = [0u8; 4];
+        let mut w = 0usize;
+        for &v in &t[..len] { if v != 0 { line[w] = v; w += 1; } }
+
+        let mut out = t;
+        out[..len].fill(0);
+        let mut score = 0.0f64;
+        let mut i = 0usize;
+        let mut o = 0usize;
+        while i < len && line[i] != 0 {
+            if i + 1 < len && merges(line[i], line[i + 1]) {
+                let nr = line[i] + 1;
+                out[o] = if nr <= 15 { nr } else { 15 };
+                score += (1u64 << (nr as u32)) as f64;
+                i += 2;
+            } else {
+                out[o] = line[i];
+                i += 1;
+            }
+            o += 1;
+        }
+        let left = (out[0] as u16) | ((out[1] as u16) << 4)
+                 | ((out[2] as u16) << 8) | ((out[3] as u16) << 12);
+
+        // ── Right move (reverse, left-compress, reverse) ──
+        let mut rline = [0u8; 4];
+        w = 0;
+        for &v in t[..len].iter().rev() { if v != 0 { rline[w] = v; w += 1; } }
+
+        let mut rout = [0u8; 4];
+        i = 0; o = 0;
+        while i < len && rline[i] != 0 {
+            if i + 1 < len && merges(rline[i], rline[i + 1]) {
+                let nr = rline[i] + 1;
+                rout[o] = if nr <= 15 { nr } else { 15 };
+                i += 2;
+            } else {
+                rout[o] = rline[i];
+                i += 1;
+            }
+            o += 1;
+        }
+        let mut right = t;
+        for k in 0..len { right[k] = rout[len - 1 - k]; }
+        let right = (right[0] as u16) | ((right[1] as u16) << 4)
+                  | ((right[2] as u16) << 8) | ((right[3] as u16) << 12);
+
+        tables.left[rv as usize] = left;
+        tables.right[rv as usize] = right;
+        tables.score[rv as usize] = score;
+    }
 }
 
 // ── Board primitives ──
@@ -147,6 +863,27 @@ fn cell(b: BB, r: usize, c: usize) -> u8 {
     ((b >> ((r << 4) | (c << 2))) & 0xF) as u8
 }
 
+/// Wall nybbles of the current board shape (0 on the full 4×4)
+#[inline(always)]
+fn walls() -> u64 {
+    WALLS.load(Ordering::Relaxed)
+}
+
+/// `b` with the cells outside the board shape set to walls
+#[inline]
+fn with_walls(b: BB) -> BB {
+    let w = walls();
+    (b & !w) | w
+}
+
+/// Bit 4i set for each empty cell i (the low bit of its nybble)
+#[inline]
+fn empty_nybbles(b: BB) -> u64 {
+    let x = b | (b >> 1);
+    let x = x | (x >> 2);
+    !x & 0x1111_1111_1111_1111
+}
+
 fn transpose(x: BB) -> BB {
     // nneonneo bit-parallel transpose: two rounds of 2×2 block swaps
     let a1 = x & 0xF0F00F0FF0F00F0Fu64;
@@ -159,181 +896,7128 @@ fn transpose(x: BB) -> BB {
     b1 | (b2 << 24) | (b3 >> 24)
 }
 
+/// Board under symmetry `o` (0–7, as the snake orientations: bit 0 mirrors
+/// columns, bit 1 mirrors rows, bit 2 then transposes)
+fn orient(b: BB, o: usize) -> BB {
+    let row = |x: BB, r: u32| (x >> (r * 16)) & 0xFFFF;
+    let mut x = b;
+    if o & 1 != 0 { x = (0..4).fold(0, |acc, r| acc | (reverse_row(row(x, r) as u16) as u64) << (r * 16)); }
+    if o & 2 != 0 { x = (0..4).fold(0, |acc, r| acc | row(x, r) << ((3 - r) * 16)); }
+    if o & 4 != 0 { x = transpose(x); }
+    x
+}
+
 #[inline]
 fn reverse_row(r: u16) -> u16 {
     ((r & 0xF) << 12) | (((r >> 4) & 0xF) << 8)
     | (((r >> 8) & 0xF) << 4) | ((r >> 12) & 0xF)
 }
 
+/// Read 16 raw tile values (row-major) into a bitboard of log2 ranks
+fn read_board(board_ptr: *const u16) -> BB {
+    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
+    let mut board: BB = 0;
+    for (i, &val) in flat.iter().enumerate() {
+        board |= tile_rank(val) << (i * 4);
+    }
+    with_walls(board)
+}
+
+/// Read 16 signed exponents (row-major) into a bitboard; ≤ 0 is empty
+fn read_board_exp(board_ptr: *const i8) -> BB {
+    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
+    let mut board: BB = 0;
+    for (i, &e) in flat.iter().enumerate() {
+        board |= (e.clamp(0, 15) as u64) << (i * 4);
+    }
+    with_walls(board)
+}
+
+/// Nybble stored for a raw tile value (0 for empty)
+fn tile_rank(val: u16) -> u64 {
+    let rank = if val == 0 { 0u64 } else { (val as f64).log2() as u64 };
+    rank & 0xF
+}
+
+/// Read 16 log2 ranks (row-major) into a bitboard
+fn read_board_log2(board_ptr: *const u8) -> BB {
+    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
+    let mut board: BB = 0;
+    for (i, &rank) in flat.iter().enumerate() {
+        board |= (rank.min(15) as u64) << (i * 4);
+    }
+    with_walls(board)
+}
+
+/// Expand a bitboard back into 16 raw tile values; walls read as empty
+fn board_tiles(b: BB) -> [u16; 16] {
+    let b = b & !walls();
+    let mut out = [0u16; 16];
+    for (i, v) in out.iter_mut().enumerate() {
+        let rank = (b >> (i * 4)) & 0xF;
+        *v = if rank == 0 { 0 } else { 1u16 << rank };
+    }
+    out
+}
+
+/// Write a bitboard out as 16 raw tile values
+fn write_board(b: BB, out_ptr: *mut u16) {
+    let out = unsafe { std::slice::from_raw_parts_mut(out_ptr, 16) };
+    out.copy_from_slice(&board_tiles(b));
+}
+
 // ── Moves via table lookup ──
 
-fn move_left(b: BB) -> (BB, f64) {
+fn move_left(b: BB, t: &Tables) -> (BB, f64) {
     let mut r = 0u64;
     let mut s = 0.0;
     for i in 0..4 {
         let rv = get_row(b, i);
-        unsafe {
-            r |= (TBL_LEFT[rv as usize] as u64) << (i << 4);
-            s += TBL_SCORE[rv as usize];
-        }
+        r |= (t.left[rv as usize] as u64) << (i << 4);
+        s += t.score[rv as usize];
     }
     (r, s)
 }
 
-fn move_right(b: BB) -> (BB, f64) {
+fn move_right(b: BB, t: &Tables) -> (BB, f64) {
     let mut r = 0u64;
     let mut s = 0.0;
     for i in 0..4 {
         let rv = get_row(b, i);
-        unsafe {
-            r |= (TBL_RIGHT[rv as usize] as u64) << (i << 4);
-            s += TBL_SCORE[reverse_row(rv) as usize];
-        }
+        r |= (t.right[rv as usize] as u64) << (i << 4);
+        // A line scores the same merges from either end; the reversed
+        // row would put a smaller shape's walls first
+        s += t.score[rv as usize];
     }
     (r, s)
 }
 
-fn do_move(b: BB, dir: u8) -> (BB, f64, bool) {
-    let (nb, sc) = match dir {
-        0 => { let t = transpose(b); let (m, s) = move_left(t); (transpose(m), s) }
-        1 => { let t = transpose(b); let (m, s) = move_right(t); (transpose(m), s) }
-        2 => move_left(b),
-        3 => move_right(b),
-        _ => (b, 0.0),
-    };
+/// Move directions; the discriminants are the C ABI encoding.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Up = 0,
+    Down = 1,
+    Left = 2,
+    Right = 3,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    fn from_u8(d: u8) -> Option<Direction> {
+        Direction::ALL.get(d as usize).copied()
+    }
+
+    /// The move that slides tiles back the other way
+    fn reverse(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    /// The same move on the board under symmetry `o` (see `orient`)
+    fn oriented(self, o: usize) -> Direction {
+        let mut d = self;
+        if o & 1 != 0 && matches!(d, Direction::Left | Direction::Right) { d = d.reverse(); }
+        if o & 2 != 0 && matches!(d, Direction::Up | Direction::Down) { d = d.reverse(); }
+        if o & 4 != 0 {
+            d = match d {
+                Direction::Up => Direction::Left,
+                Direction::Left => Direction::Up,
+                Direction::Down => Direction::Right,
+                Direction::Right => Direction::Down,
+            };
+        }
+        d
+    }
+
+    fn name(self) -> &'static std::ffi::CStr {
+        match self {
+            Direction::Up => c"up",
+            Direction::Down => c"down",
+            Direction::Left => c"left",
+            Direction::Right => c"right",
+        }
+    }
+}
+
+fn do_move(b: BB, dir: Direction) -> (BB, f64, bool) {
+    let (nb, sc) = with_tables(|tbl| match dir {
+        Direction::Up => { let t = transpose(b); let (m, s) = move_left(t, tbl); (transpose(m), s) }
+        Direction::Down => { let t = transpose(b); let (m, s) = move_right(t, tbl); (transpose(m), s) }
+        Direction::Left => move_left(b, tbl),
+        Direction::Right => move_right(b, tbl),
+    });
+    debug_assert!(nb != b || sc == 0.0, "unmoved board scored {sc}");
     (nb, sc, nb != b)
 }
 
+/// Number of legal moves, stopping each direction at its first row that
+/// slides; no result boards or scores are built
+fn count_legal(b: BB) -> u32 {
+    let t = transpose(b);
+    let slides = |x: BB, tbl: &[u16; 65536]| (0..4).any(|i| {
+        let r = get_row(x, i);
+        tbl[r as usize] != r
+    });
+    with_tables(|tbl| {
+        let (left, right) = (&*tbl.left, &*tbl.right);
+        [slides(t, left), slides(t, right), slides(b, left), slides(b, right)]
+            .into_iter()
+            .filter(|&m| m)
+            .count() as u32
+    })
+}
+
 // ── Evaluation ──
 
 fn evaluate(b: BB) -> f64 {
+    stat(|s| s.evals += 1);
+    CFG.with(|c| {
+        let c = c.borrow();
+        if c.fixed_point { return evaluate_fixed(b, &c) as f64 / FIXED_SCALE; }
+        if let Some(net) = &c.ntuple { return net.evaluate(b); }
+        let tables = c.tables.as_deref().unwrap();
+        if use_survival(b, &c) {
+            heur_score(b, &tables.heur_surv) + board_terms(b, &c.survival_weights, &c)
+        } else {
+            heur_score(b, &tables.heur) + board_terms(b, &c.weights, &c)
+        }
+    })
+}
+
+fn heur_score(b: BB, table: &[f64; 65536]) -> f64 {
     // Pure nneonneo heuristic: sum of row + column table scores (8 lookups)
     let t = transpose(b);
     let mut score = 0.0;
     for i in 0..4 {
-        unsafe {
-            score += TBL_HEUR[get_row(b, i) as usize];
-            score += TBL_HEUR[get_row(t, i) as usize];
-        }
+        score += table[get_row(b, i) as usize];
+        score += table[get_row(t, i) as usize];
     }
     score
 }
 
-// ── Expectimax search (nneonneo architecture) ──
-// Depth counts MOVE nodes only. Probability pruning for chance nodes.
+/// Leaf value in fixed-point mode, in 1/`FIXED_SCALE` units: row terms from
+/// the fixed-point tables; board-level terms and n-tuple networks are still
+/// computed in f64 and rounded
+fn evaluate_fixed(b: BB, c: &Config) -> i64 {
+    let quantize = |v: f64| (v * FIXED_SCALE).round() as i64;
+    if let Some(net) = &c.ntuple { return quantize(net.evaluate(b)); }
+    let survival = use_survival(b, c);
+    let tables = c.tables.as_deref().unwrap();
+    let table = if survival { &tables.heur_surv_fix } else { &tables.heur_fix };
+    let t = transpose(b);
+    let rows: i64 = (0..4).map(|i| table[get_row(b, i) as usize] + table[get_row(t, i) as usize]).sum();
+    rows + quantize(board_terms(b, if survival { &c.survival_weights } else { &c.weights }, c))
+}
 
-/// Count distinct non-zero tile ranks on the board
-fn count_distinct(board: BB) -> u32 {
-    let mut seen = 0u16;  // bitmask of ranks seen
-    for i in 0..16 {
-        let rank = ((board >> (i * 4)) & 0xF) as u16;
-        if rank != 0 { seen |= 1 << rank; }
+/// Features of the 4 rows and 4 columns, in the order heur_score sums them
+fn line_features(b: BB) -> [RowFeatures; 8] {
+    let t = transpose(b);
+    let mut lines = [RowFeatures::default(); 8];
+    for i in 0..4 {
+        lines[2 * i] = row_features(get_row(b, i) as u32);
+        lines[2 * i + 1] = row_features(get_row(t, i) as u32);
     }
-    seen.count_ones()
+    lines
 }
 
-/// Chance node: enumerate tile spawns, call move node
-fn score_chance_node(board: BB, depth: u32, cprob: f64) -> f64 {
-    if cprob < CPROB_THRESH || depth == 0 {
-        return evaluate(board);
+/// Signed contribution of each term to the leaf score, indexed like the
+/// `TERM_*` bits. Adding `8 * w.lost` gives the handcrafted evaluation.
+fn eval_components(b: BB, w: &EvalWeights, c: &Config) -> [f64; NUM_TERMS as usize] {
+    let mut out = board_term_values(b, w, c);
+    for f in &line_features(b) {
+        out[0] += w.empty * f.empty;
+        out[1] += w.merges * f.merges;
+        out[2] -= w.mono * f.mono;
+        out[3] -= w.sum * f.sum;
     }
+    out
+}
 
-    // TT check
-    let cached = TT.with(|tt| {
-        if let Some(&(d, s)) = tt.borrow().get(&board) {
-            if d >= depth { return Some(s); }
-        }
-        None
-    });
-    if let Some(s) = cached { return s; }
+/// Weight profile `evaluate` applies to this board
+fn active_weights(b: BB, c: &Config) -> &EvalWeights {
+    if use_survival(b, c) { &c.survival_weights } else { &c.weights }
+}
 
-    let mut num_open = 0u32;
-    for i in 0..16 {
-        if (board >> (i * 4)) & 0xF == 0 { num_open += 1; }
-    }
-    if num_open == 0 { return evaluate(board); }
+fn use_survival(b: BB, c: &Config) -> bool {
+    c.force_survival || (c.endgame_empty > 0 && count_empty(b) < c.endgame_empty)
+}
 
-    let prob_per_cell = cprob / num_open as f64;
-    let mut total = 0.0;
+/// Weight scaling each evaluation term, indexed like the `TERM_*` bits
+fn term_weights(w: &EvalWeights) -> [f64; NUM_TERMS as usize] {
+    [w.empty, w.merges, w.mono, w.sum, w.corner, w.trapped, w.scatter, w.snake, w.chain, w.grip,
+     w.anchor, w.pattern, w.breathing]
+}
+
+/// `TERM_*` bits of the board-level terms; the rest are line terms, summed
+/// from the row tables
+const BOARD_TERMS: u32 = TERM_ALL & !(TERM_EMPTY | TERM_MERGES | TERM_MONO | TERM_SUM);
+
+/// Does any board-level term have a weight?
+fn has_board_terms(w: &EvalWeights) -> bool {
+    term_weights(w).iter().enumerate().any(|(t, &v)| BOARD_TERMS & (1 << t) != 0 && v != 0.0)
+}
 
-    for i in 0..16u32 {
-        if (board >> (i * 4)) & 0xF != 0 { continue; }
-        let shift = i * 4;
-        let nb2 = board | (1u64 << shift);
-        total += 0.9 * score_move_node(nb2, depth, prob_per_cell * 0.9);
-        let nb4 = board | (2u64 << shift);
-        total += 0.1 * score_move_node(nb4, depth, prob_per_cell * 0.1);
+/// Board-level term `bit`, signed and scaled by its weight `wt`
+fn board_term(b: BB, bit: u32, wt: f64, w: &EvalWeights, c: &Config) -> f64 {
+    match bit {
+        TERM_CORNER if (1u32 << max_rank(b)) as f64 >= w.corner_min_tile => {
+            wt * corner_growth(b, w) * corner_term(b, w.corner_soften, anchor_corners(c.preferred_corner))
+        }
+        TERM_CORNER => 0.0,
+        TERM_TRAPPED => -(wt * trapped_term(b)),
+        TERM_SCATTER => -(wt * scatter_term(b, w.scatter_reach, w.scatter_falloff)),
+        TERM_SNAKE => wt * snake_term(b, w.snake_power, c),
+        TERM_CHAIN => wt * chain_term(b, c.chain_relaxed),
+        TERM_GRIP => wt * grip_term(b),
+        TERM_ANCHOR => wt * anchor_term(b, c.snake_pin, w.snake_power),
+        TERM_PATTERN => -(wt * pattern_term(b, c.target_pattern.as_ref())),
+        TERM_BREATHING => wt * breathing_term(b, c.snake_pin, w.snake_power),
+        _ => unreachable!("board term {bit:#x} has no evaluation"),
     }
-    let result = total / num_open as f64;
+}
 
-    // Cache
-    TT.with(|tt| {
-        let mut t = tt.borrow_mut();
-        t.insert(board, (depth, result));
-        if t.len() > (1 << 22) { t.clear(); }
+/// Opt-in terms that need the whole board rather than single rows, signed
+/// and weighted, indexed like the `TERM_*` bits. Line terms and terms with
+/// a zero weight are 0.
+fn board_term_values(b: BB, w: &EvalWeights, c: &Config) -> [f64; NUM_TERMS as usize] {
+    let weights = term_weights(w);
+    std::array::from_fn(|t| {
+        let (bit, wt) = (1 << t, weights[t]);
+        if BOARD_TERMS & bit == 0 || wt == 0.0 { return 0.0; }
+        board_term(b, bit, wt, w, c)
+    })
+}
+
+/// Sum of `board_term_values`, without building the array: this runs at
+/// every leaf, and under the default weights no board-level term is on
+fn board_terms(b: BB, w: &EvalWeights, c: &Config) -> f64 {
+    if !has_board_terms(w) { return 0.0; }
+    let weights = term_weights(w);
+    (0..NUM_TERMS)
+        .filter(|&t| BOARD_TERMS & (1 << t) != 0 && weights[t as usize] != 0.0)
+        .map(|t| board_term(b, 1 << t, weights[t as usize], w, c))
+        .sum()
+}
+
+/// Tile values, raised to `power`, weighted by position along a snake path
+/// (geometric, `SNAKE_BASE` per step from the head corner), best of the 8
+/// orientations unless one is pinned. With a snake blend, a runner-up within
+/// `snake_blend` × best is averaged in with weight ½ at a tie, falling
+/// linearly to 0 at the edge of the band, so the term moves continuously as
+/// the leading orientation changes.
+fn snake_term(b: BB, power: f64, c: &Config) -> f64 {
+    if c.snake_pin.is_some() || c.snake_blend <= 0.0 { return best_snake(b, c.snake_pin, power).1; }
+    let (mut best, mut second) = (0.0f64, 0.0f64);
+    for v in snake_sums(b, power) {
+        if v > best {
+            second = best;
+            best = v;
+        } else if v > second {
+            second = v;
+        }
+    }
+    let (gap, band) = (best - second, c.snake_blend * best);
+    if gap >= band { best } else { best - 0.5 * (1.0 - gap / band) * gap }
+}
+
+/// Cell (row-major) at step `k` of the snake path in orientation `o`
+fn snake_cell(o: usize, k: usize) -> usize {
+    let i = SNAKE_PATH[k];
+    let (mut r, mut c) = (i / 4, i % 4);
+    if o & 1 != 0 { c = 3 - c; }
+    if o & 2 != 0 { r = 3 - r; }
+    if o & 4 != 0 { (r, c) = (c, r); }
+    r * 4 + c
+}
+
+/// Best snake orientation (index into TBL_SNAKE) and its sum with tile
+/// values raised to `power`; a pinned orientation is the only candidate
+fn best_snake(b: BB, pin: Option<u8>, power: f64) -> (usize, f64) {
+    if let Some(o) = pin {
+        let o = o as usize;
+        let snake = &TBL_SNAKE[o];
+        return (o, snake_tiles(b, power).iter().zip(snake).map(|(&t, &wt)| t * wt).sum());
+    }
+    snake_sums(b, power)
+        .into_iter()
+        .enumerate()
+        .fold((0, 0.0), |best, (o, v)| if v > best.1 { (o, v) } else { best })
+}
+
+/// Snake-path sum of each of the 8 orientations
+fn snake_sums(b: BB, power: f64) -> [f64; 8] {
+    let tiles = snake_tiles(b, power);
+    TBL_SNAKE.map(|w| tiles.iter().zip(&w).map(|(&t, &wt)| t * wt).sum())
+}
+
+/// Empty cells among the last `BREATHING_CELLS` of the snake path in the
+/// best (or pinned) orientation at snake power `power`: the low-weight end
+/// where new tiles should land while the head is built up. A blocked tail
+/// means spawns go up against the big tiles instead.
+fn breathing_term(board: BB, pin: Option<u8>, power: f64) -> f64 {
+    let (o, _) = best_snake(board, pin, power);
+    (16 - BREATHING_CELLS..16).filter(|&k| (board >> (snake_cell(o, k) * 4)) & 0xF == 0).count() as f64
+}
+
+/// Per-cell magnitude the snake term weighs: tile value^`power`, 0 for an
+/// empty cell whatever the power
+fn snake_tiles(b: BB, power: f64) -> [f64; 16] {
+    board_tiles(b).map(|t| match t {
+        0 => 0.0,
+        _ if power == 1.0 => t as f64,
+        _ => (t as f64).powf(power),
+    })
+}
+
+/// Sum of rank² over high tiles (≥ `SCATTER_MIN_RANK`) that share their
+/// value with another tile yet have none within `reach` steps (Manhattan;
+/// reach 1 means not touching): duplicates that will need work to bring
+/// together. With `falloff` > 0 each such tile is scaled by (distance to its
+/// nearest twin / `SCATTER_MAX_DIST`)^falloff, so near misses cost less than
+/// opposite corners. Tiles are bucketed by value as a cell bitmask, so each
+/// tile checks only its own bucket.
+fn scatter_term(b: BB, reach: f64, falloff: f64) -> f64 {
+    let mut buckets = [0u16; 16];
+    for i in 0..16 {
+        let v = ((b >> (i * 4)) & 0xF) as usize;
+        if v as u8 >= SCATTER_MIN_RANK { buckets[v] |= 1 << i; }
+    }
+    let mut total = 0.0;
+    for (v, &mask) in buckets.iter().enumerate() {
+        if mask.count_ones() < 2 { continue; }
+        let lone: f64 = (0..16)
+            .filter(|&i| mask & (1 << i) != 0)
+            .map(|i| nearest_twin(i, mask) as f64)
+            .filter(|&d| d > reach)
+            .map(|d| if falloff == 0.0 { 1.0 } else { (d / SCATTER_MAX_DIST).powf(falloff) })
+            .sum();
+        total += lone * (v as f64) * (v as f64);
+    }
+    total
+}
+
+/// Manhattan distance from cell `i` to the nearest other cell set in `mask`
+fn nearest_twin(i: usize, mask: u16) -> usize {
+    (0..16)
+        .filter(|&j| j != i && mask & (1 << j) != 0)
+        .map(|j| (i / 4).abs_diff(j / 4) + (i % 4).abs_diff(j % 4))
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
+/// Sum of rank² over trapped tiles: every neighbour occupied, none equal (so
+/// no merge is possible) and at least one larger, i.e. a mid tile wedged
+/// against bigger ones. The max tile itself is never counted.
+fn trapped_term(b: BB) -> f64 {
+    let mt = max_rank(b);
+    let mut total = 0.0;
+    for i in 0..16 {
+        let (r, c) = (i / 4, i % 4);
+        let v = cell(b, r, c);
+        if v == 0 || v == mt { continue; }
+        let mut larger = false;
+        let walled = neighbours(r, c).all(|(nr, nc)| {
+            let nv = cell(b, nr, nc);
+            larger |= nv > v;
+            nv != 0 && nv != v
+        });
+        if walled && larger { total += (v as f64) * (v as f64); }
+    }
+    total
+}
+
+/// Corners that count as anchors: the preferred one, or all four
+fn anchor_corners(preferred: u8) -> &'static [(usize, usize)] {
+    match preferred {
+        p @ 0..=3 => &CORNERS[p as usize..p as usize + 1],
+        _ => &CORNERS,
+    }
+}
+
+/// Max tile in an anchor corner earns a bonus; on an edge or in the interior
+/// it is penalised. With `soften` > 0 the penalty shrinks when some move puts
+/// the max tile straight back into an anchor (a forced but recoverable detour).
+fn corner_term(b: BB, soften: f64, anchors: &[(usize, usize)]) -> f64 {
+    let mt = max_rank(b);
+    let lv2 = (mt as f64) * (mt as f64);
+    if max_in_corner(b, mt, anchors) { return CORNER_BONUS * lv2; }
+
+    let on_edge = (0..16).any(|i| {
+        let (r, c) = (i / 4, i % 4);
+        (r == 0 || r == 3 || c == 0 || c == 3) && cell(b, r, c) == mt
+    });
+    let mut penalty = if on_edge { EDGE_PENALTY } else { INTERIOR_PENALTY } * lv2;
+    if soften > 0.0 && corner_recoverable(b, anchors) {
+        penalty *= 1.0 - soften.min(1.0);
+    }
+    -penalty
+}
+
+/// Factor on the corner term as the game advances: 1 plus the configured
+/// growth per unit of max rank (relative to `CORNER_GROWTH_RANK`) and per
+/// unit of board fullness; exactly 1 with both growths at 0
+fn corner_growth(b: BB, w: &EvalWeights) -> f64 {
+    let mut f = 1.0;
+    if w.corner_tile_growth != 0.0 {
+        f *= 1.0 + w.corner_tile_growth * max_rank(b) as f64 / CORNER_GROWTH_RANK;
+    }
+    if w.corner_fill_growth != 0.0 {
+        let cells = 16 - walls().count_ones() / 4;  // wall nybbles are all ones
+        let filled = cells - count_empty(b);
+        f *= 1.0 + w.corner_fill_growth * filled as f64 / cells as f64;
+    }
+    f
+}
+
+fn max_in_corner(b: BB, mt: u8, anchors: &[(usize, usize)]) -> bool {
+    anchors.iter().any(|&(r, c)| cell(b, r, c) == mt)
+}
+
+/// One-ply check: does any legal move leave the max tile in an anchor corner?
+fn corner_recoverable(b: BB, anchors: &[(usize, usize)]) -> bool {
+    Direction::ALL.into_iter().any(|d| {
+        let (nb, _, moved) = do_move(b, d);
+        moved && max_in_corner(nb, max_rank(nb), anchors)
+    })
+}
+
+// ── N-tuple network evaluation ──
+// Blob layout (little-endian):
+//   u32 tuple_count
+//   tuple_count × { u32 len (1–6), len × u8 cell index (0–15, row-major) }
+//   then, per tuple in order, 16^len × f32 weights indexed by the tuple's
+//   cell ranks packed 4 bits each, first cell in the lowest nybble.
+// Symmetric sampling is not applied; exporters should list every symmetric
+// copy of a tuple explicitly. Values should stay positive like the built-in
+// heuristic, since a dead board scores 0.
+
+const NTUPLE_MAX_LEN: usize = 6;
+
+struct NTuple {
+    tuples: Vec<Vec<u8>>,
+    weights: Vec<Vec<f32>>,
+    digest: u64,  // FNV-1a of the source blob, for config_fingerprint
+}
+
+impl NTuple {
+    fn parse(bytes: &[u8]) -> Option<NTuple> {
+        let mut pos = 0usize;
+        let read_u32 = |pos: &mut usize| -> Option<u32> {
+            let b = bytes.get(*pos..*pos + 4)?;
+            *pos += 4;
+            Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let count = read_u32(&mut pos)? as usize;
+        if count == 0 || count > 1024 { return None; }
+
+        let mut tuples = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = read_u32(&mut pos)? as usize;
+            if len == 0 || len > NTUPLE_MAX_LEN { return None; }
+            let cells = bytes.get(pos..pos + len)?.to_vec();
+            if cells.iter().any(|&c| c >= 16) { return None; }
+            pos += len;
+            tuples.push(cells);
+        }
+
+        let mut weights = Vec::with_capacity(count);
+        for cells in &tuples {
+            let n = 1usize << (4 * cells.len());
+            let raw = bytes.get(pos..pos + 4 * n)?;
+            pos += 4 * n;
+            weights.push(raw.chunks_exact(4)
+                .map(|w| f32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+                .collect());
+        }
+        if pos != bytes.len() { return None; }
+        let mut digest = Fnv::new();
+        digest.bytes(bytes);
+        Some(NTuple { tuples, weights, digest: digest.0 })
+    }
+
+    fn evaluate(&self, b: BB) -> f64 {
+        let mut sum = 0.0f64;
+        for (cells, w) in self.tuples.iter().zip(&self.weights) {
+            let mut idx = 0usize;
+            for (k, &c) in cells.iter().enumerate() {
+                idx |= (((b >> (c * 4)) & 0xF) as usize) << (4 * k);
+            }
+            sum += w[idx] as f64;
+        }
+        sum
+    }
+}
+
+// ── Structure detection ──
+
+/// Corner indices used by `set_preferred_corner`: 0 = top-left,
+/// 1 = top-right, 2 = bottom-left, 3 = bottom-right.
+const CORNERS: [(usize, usize); 4] = [(0, 0), (0, 3), (3, 0), (3, 3)];
+pub const CORNER_ANY: u8 = 0xFF;
+
+fn count_empty(board: BB) -> u32 {
+    (0..16).filter(|i| (board >> (i * 4)) & 0xF == 0).count() as u32
+}
+
+fn max_rank(board: BB) -> u8 {
+    let board = board & !walls();
+    (0..16).map(|i| ((board >> (i * 4)) & 0xF) as u8).max().unwrap_or(0)
+}
+
+/// In-bounds orthogonal neighbours of (r, c)
+fn neighbours(r: usize, c: usize) -> impl Iterator<Item = (usize, usize)> {
+    [(r.wrapping_sub(1), c), (r + 1, c), (r, c.wrapping_sub(1)), (r, c + 1)]
+        .into_iter()
+        .filter(|&(nr, nc)| nr < 4 && nc < 4)
+}
+
+/// Length of the longest halving chain from a cornered max tile that ends in
+/// an equal pair — the tiles a run of merges would roll into the max tile.
+/// 0 when the max tile is off-corner or no chain ends in a pair.
+fn cascade_length(board: BB) -> u32 {
+    let max = max_rank(board);
+    if max == 0 { return 0; }
+    CORNERS.iter()
+        .filter(|&&(r, c)| cell(board, r, c) == max)
+        .map(|&(r, c)| follow_cascade(board, r, c, 1 << (r * 4 + c), 1))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Sum of ranks along the best descending path from a cornered max tile
+/// (the max tile included). Strict: each step exactly halves, as a merge
+/// cascade needs. Relaxed: each step at least halves, so 1024→256→128
+/// still counts. 0 when the max tile is off-corner.
+fn chain_term(board: BB, relaxed: bool) -> f64 {
+    let max = max_rank(board);
+    if max == 0 { return 0.0; }
+    CORNERS.iter()
+        .filter(|&&(r, c)| cell(board, r, c) == max)
+        .map(|&(r, c)| follow_chain(board, r, c, 1 << (r * 4 + c), relaxed))
+        .max()
+        .unwrap_or(0) as f64
+}
+
+/// Rank of the second-largest tile (a second max tile counts) when it sits
+/// next to a cornered max tile, the first step of any snake from that
+/// corner; 0 otherwise.
+fn grip_term(board: BB) -> f64 {
+    let mut ranks: Vec<u8> = (0..16).map(|i| ((board >> (i * 4)) & 0xF) as u8).collect();
+    ranks.sort_unstable_by(|a, b| b.cmp(a));
+    let (max, second) = (ranks[0], ranks[1]);
+    if second == 0 { return 0.0; }
+    let gripped = CORNERS.iter().any(|&(r, c)| {
+        cell(board, r, c) == max && neighbours(r, c).any(|(nr, nc)| cell(board, nr, nc) == second)
+    });
+    if gripped { second as f64 } else { 0.0 }
+}
+
+/// Rank sum of the anchor row, the first four cells of the snake path in the
+/// best (or pinned) orientation at snake power `power`, when it is full and
+/// strictly descending from the head corner; 0 otherwise. A solid anchor row
+/// is the backbone the rest of the snake builds down to.
+fn anchor_term(board: BB, pin: Option<u8>, power: f64) -> f64 {
+    let (o, _) = best_snake(board, pin, power);
+    let ranks: [u64; 4] = std::array::from_fn(|k| (board >> (snake_cell(o, k) * 4)) & 0xF);
+    let perfect = ranks[3] > 0 && ranks.windows(2).all(|p| p[0] > p[1]);
+    if perfect { ranks.iter().sum::<u64>() as f64 } else { 0.0 }
+}
+
+/// Sum of rank² over the cells that break the target pattern: the required
+/// tile's rank where a cell holds anything else, the tile's rank where a
+/// cell should be empty. 0 with no pattern set.
+fn pattern_term(board: BB, pattern: Option<&[u8; 16]>) -> f64 {
+    let Some(pattern) = pattern else { return 0.0 };
+    let mut total = 0.0;
+    for (i, &want) in pattern.iter().enumerate() {
+        let have = ((board >> (i * 4)) & 0xF) as u8;
+        if want == PATTERN_ANY_RANK || want == have { continue; }
+        let r = if want == 0 { have } else { want } as f64;
+        total += r * r;
+    }
+    total
+}
+
+fn follow_chain(board: BB, r: usize, c: usize, visited: u16, relaxed: bool) -> u32 {
+    let v = cell(board, r, c);
+    let mut best = 0;
+    for (nr, nc) in neighbours(r, c) {
+        let bit = 1u16 << (nr * 4 + nc);
+        if visited & bit != 0 { continue; }
+        let nv = cell(board, nr, nc);
+        let next = if relaxed { nv != 0 && nv < v } else { nv != 0 && nv + 1 == v };
+        if next { best = best.max(follow_chain(board, nr, nc, visited | bit, relaxed)); }
+    }
+    v as u32 + best
+}
+
+fn follow_cascade(board: BB, r: usize, c: usize, visited: u16, len: u32) -> u32 {
+    let v = cell(board, r, c);
+    let mut best = 0;
+    for (nr, nc) in neighbours(r, c) {
+        let bit = 1u16 << (nr * 4 + nc);
+        if visited & bit != 0 { continue; }
+        let nv = cell(board, nr, nc);
+        if nv == 0 { continue; }
+        if nv == v {
+            best = best.max(len + 1);  // the pair that triggers the cascade
+        } else if nv + 1 == v {
+            best = best.max(follow_cascade(board, nr, nc, visited | bit, len + 1));
+        }
+    }
+    best
+}
+
+/// Share of rank change along rows and columns that runs against each
+/// line's main direction, ×2 so it spans 0 (every line monotone) to 1
+/// (every line zigzags evenly). Empty cells are skipped, not read as 0.
+fn disorder(b: BB) -> f64 {
+    let t = transpose(b);
+    let (mut against, mut total) = (0u32, 0u32);
+    for line in (0..4).flat_map(|i| [get_row(b, i), get_row(t, i)]) {
+        let tiles: Vec<u32> = (0..4).map(|k| ((line >> (k * 4)) & 0xF) as u32).filter(|&v| v != 0).collect();
+        let (mut up, mut down) = (0, 0);
+        for w in tiles.windows(2) {
+            if w[1] > w[0] { up += w[1] - w[0]; } else { down += w[0] - w[1]; }
+        }
+        against += up.min(down);
+        total += up + down;
+    }
+    if total == 0 { 0.0 } else { 2.0 * against as f64 / total as f64 }
+}
+
+// Position categories returned by `classify_position`: exactly one phase
+// bit, plus any of the structure bits
+pub const POS_OPENING:       u32 = 1 << 0;
+pub const POS_MIDGAME:       u32 = 1 << 1;
+pub const POS_ENDGAME:       u32 = 1 << 2;
+pub const POS_CORNER_HELD:   u32 = 1 << 3;
+pub const POS_SNAKE_ALIGNED: u32 = 1 << 4;
+pub const POS_IN_DANGER:     u32 = 1 << 5;
+
+/// Phase by max tile (`OPENING_MAX_RANK`, `ENDGAME_MIN_RANK`); corner held
+/// as the corner term sees it; snake-aligned when the first
+/// `SNAKE_ALIGNED_CELLS` cells of the snake term's orientation are filled and
+/// non-increasing; in danger with at most `DANGER_EMPTY` empty cells or at
+/// most one legal move.
+fn classify(b: BB, c: &Config) -> u32 {
+    let mt = max_rank(b);
+    let mut flags = match mt {
+        r if r <= OPENING_MAX_RANK => POS_OPENING,
+        r if r >= ENDGAME_MIN_RANK => POS_ENDGAME,
+        _ => POS_MIDGAME,
+    };
+    if mt > 0 && max_in_corner(b, mt, anchor_corners(c.preferred_corner)) { flags |= POS_CORNER_HELD; }
+    let o = best_snake(b, c.snake_pin, active_weights(b, c).snake_power).0;
+    let head: Vec<u64> = (0..SNAKE_ALIGNED_CELLS).map(|k| (b >> (snake_cell(o, k) * 4)) & 0xF).collect();
+    if head.iter().all(|&v| v != 0) && head.windows(2).all(|w| w[0] >= w[1]) {
+        flags |= POS_SNAKE_ALIGNED;
+    }
+    if count_empty(b) <= DANGER_EMPTY || count_legal(b) <= 1 { flags |= POS_IN_DANGER; }
+    flags
+}
+
+// ── Expectimax search (nneonneo architecture) ──
+// Depth counts MOVE nodes only, excluding the root move: depth 0 ranks each
+// root move by the static evaluation of the board it produces (no spawn),
+// depth 1 adds one spawn and one reply move, and so on.
+// Probability pruning for chance nodes.
+
+/// Count distinct non-zero tile ranks on the board
+fn count_distinct(board: BB) -> u32 {
+    let board = board & !walls();
+    let mut seen = 0u16;  // bitmask of ranks seen
+    for i in 0..16 {
+        let rank = ((board >> (i * 4)) & 0xF) as u16;
+        if rank != 0 { seen |= 1 << rank; }
+    }
+    seen.count_ones()
+}
+
+/// Number of occupied orthogonal neighbours of cell `i`
+fn occupied_neighbours(board: BB, i: usize) -> u32 {
+    let (r, c) = (i / 4, i % 4);
+    let mut n = 0;
+    if r > 0 && cell(board, r - 1, c) != 0 { n += 1; }
+    if r < 3 && cell(board, r + 1, c) != 0 { n += 1; }
+    if c > 0 && cell(board, r, c - 1) != 0 { n += 1; }
+    if c < 3 && cell(board, r, c + 1) != 0 { n += 1; }
+    n
+}
+
+/// Empty cells a chance node expands. When there are more than `max`, keep the
+/// ones with the most occupied neighbours (the most disruptive spawns). An
+/// installed chance selector chooses instead.
+fn chance_cells(board: BB, max: u32) -> ([u8; 16], usize) {
+    if let Some(cb) = CFG.with(|c| c.borrow().chance_selector) {
+        if let Some(chosen) = selected_cells(board, max, cb) { return chosen; }
+    }
+    let mut cells = [0u8; 16];
+    let mut n = 0;
+    for i in 0..16 {
+        if (board >> (i * 4)) & 0xF == 0 { cells[n] = i as u8; n += 1; }
+    }
+    let max = max as usize;
+    if n > max {
+        // Equally-crowded cells keep index order unless a search seed is set,
+        // in which case the random tie key decides between them
+        let mut keyed = [(std::cmp::Reverse(0u32), 0u64, 0u8); 16];
+        SEARCH_RNG.with(|r| {
+            let mut r = r.borrow_mut();
+            for (k, &i) in keyed.iter_mut().zip(&cells[..n]) {
+                let tie = r.as_mut().map_or(0, |rng| rng.next_u64());
+                *k = (std::cmp::Reverse(occupied_neighbours(board, i as usize)), tie, i);
+            }
+        });
+        keyed[..n].sort_unstable();
+        for (c, k) in cells.iter_mut().zip(&keyed[..max]) {
+            *c = k.2;
+        }
+        n = max;
+    }
+    (cells, n)
+}
+
+/// Ask the chance selector for up to `max` cells. Indices that are out of
+/// range, occupied or repeated are dropped; None (use the built-in choice)
+/// if nothing valid is left.
+fn selected_cells(board: BB, max: u32, cb: ChanceSelector) -> Option<([u8; 16], usize)> {
+    let tiles = board_tiles(board);
+    let mut out = [0u8; 16];
+    let returned = cb(tiles.as_ptr(), out.as_mut_ptr(), max).min(max) as usize;
+    let mut cells = [0u8; 16];
+    let mut n = 0;
+    let mut seen = 0u16;
+    for &i in &out[..returned] {
+        if i >= 16 || tiles[i as usize] != 0 || seen & (1 << i) != 0 { continue; }
+        seen |= 1 << i;
+        cells[n] = i;
+        n += 1;
+    }
+    (n > 0).then_some((cells, n))
+}
+
+/// Chance node: enumerate tile spawns, call move node
+fn score_chance_node(board: BB, depth: u32, cprob: f64) -> f64 {
+    chance_node(board, depth, cprob, None)
+}
+
+/// Chance node body. With `cut = Some((alpha, upper))`, where `upper`
+/// bounds every node value, expansion stops as soon as the cells still to
+/// come cannot lift the mean above `alpha`; the bound reached so far is
+/// returned (below `alpha`, never cached) instead of the exact value.
+fn chance_node(board: BB, depth: u32, cprob: f64, cut: Option<(f64, f64)>) -> f64 {
+    if cprob < CPROB_THRESH || depth == 0 {
+        return timed(Phase::Eval, || evaluate(board));
+    }
+
+    let (spawn, max_cells, blend, band, custom, fixed) = CFG.with(|c| {
+        let c = c.borrow();
+        (c.spawn, c.max_chance_cells, c.chance_blend, c.tt_depth_band, c.chance_selector.is_some(), c.fixed_point)
     });
 
+    // TT check: an entry searched at least as deep, or merely in the same
+    // depth band when banding is on
+    let cached = timed(Phase::Tt, || TT.with(|tt| {
+        let (entry, collided) = tt.borrow().probe(board);
+        stat(|s| {
+            s.tt_probes += 1;
+            s.tt_collisions += collided as u64;
+        });
+        entry.filter(|&(d, _)| d / band >= depth / band).map(|(_, s)| s)
+    }));
+    if let Some(s) = cached {
+        stat(|s| s.tt_hits += 1);
+        return s;
+    }
+    stat(|s| s.chance_nodes += 1);
+    if fixed {
+        let result = fixed_expectation(board, depth, cprob, &spawn, max_cells, blend);
+        timed(Phase::Tt, || TT.with(|tt| tt.borrow_mut().insert(board, depth, result)));
+        return result;
+    }
+
+    let mut worst = f64::INFINITY;
+    let open = empty_nybbles(board);
+    let mean = if open.count_ones() == 1 && !custom {
+        // One empty cell, common deep in the endgame: no cell list or top-K
+        // selection, just its spawn outcomes (same arithmetic as below)
+        let shift = open.trailing_zeros();
+        let mut total = 0.0;
+        for (rank, p) in spawn.outcomes() {
+            let v = score_move_node(board | ((rank as u64) << shift), depth, cprob * p);
+            total += p * v;
+            worst = worst.min(v);
+        }
+        if cut.is_some_and(|(alpha, _)| total < alpha) { return total; }
+        total
+    } else {
+        let (cells, num_open) = timed(Phase::Cells, || chance_cells(board, max_cells));
+        if num_open == 0 { return timed(Phase::Eval, || evaluate(board)); }
+
+        let prob_per_cell = cprob / num_open as f64;
+        let mut total = 0.0;
+        for (k, &i) in cells[..num_open].iter().enumerate() {
+            let shift = i as u32 * 4;
+            let mut cell_total = 0.0;
+            for (rank, p) in spawn.outcomes() {
+                let v = score_move_node(board | ((rank as u64) << shift), depth, prob_per_cell * p);
+                cell_total += p * v;
+                worst = worst.min(v);
+            }
+            total += cell_total;
+            if let Some((alpha, upper)) = cut {
+                let bound = (total + (num_open - k - 1) as f64 * upper) / num_open as f64;
+                if bound < alpha { return bound; }
+            }
+        }
+        total / num_open as f64
+    };
+    // Blend toward the worst spawn for variance-averse play (blend = 0 → pure expectation)
+    let result = if blend > 0.0 { (1.0 - blend) * mean + blend * worst } else { mean };
+
+    // Cache
+    timed(Phase::Tt, || TT.with(|tt| tt.borrow_mut().insert(board, depth, result)));
+
     result
 }
 
+/// Chance node value in fixed-point mode. Child values are whole multiples
+/// of 1/`FIXED_SCALE` (leaves are, merge scores are integers, and so is
+/// each average below), so they convert to integers exactly; the
+/// odds-weighted average and the worst-case blend are then taken in
+/// integers, with Q16 odds and blend, rounding down. No f64 rounding reaches
+/// the value, so it is the same on every platform.
+fn fixed_expectation(board: BB, depth: u32, cprob: f64, spawn: &SpawnDist, max_cells: u32, blend: f64) -> f64 {
+    let (cells, num_open) = timed(Phase::Cells, || chance_cells(board, max_cells));
+    if num_open == 0 { return timed(Phase::Eval, || evaluate(board)); }
+    let odds = q16_odds(spawn);
+    let prob_per_cell = cprob / num_open as f64;
+    let (mut total, mut worst) = (0i128, i64::MAX);
+    for &i in &cells[..num_open] {
+        for ((rank, p), &q) in spawn.outcomes().zip(&odds) {
+            let v = score_move_node(board | ((rank as u64) << (i as u32 * 4)), depth, prob_per_cell * p);
+            let v = (v * FIXED_SCALE) as i64;
+            total += q as i128 * v as i128;
+            worst = worst.min(v);
+        }
+    }
+    let mean = total.div_euclid(num_open as i128 * FIXED_ONE as i128) as i64;
+    let bq = (blend * FIXED_ONE as f64).round() as i64;
+    let result = if bq > 0 {
+        ((FIXED_ONE - bq) as i128 * mean as i128 + bq as i128 * worst as i128).div_euclid(FIXED_ONE as i128) as i64
+    } else {
+        mean
+    };
+    result as f64 / FIXED_SCALE
+}
+
+/// Spawn odds in Q16, each rounded to nearest and the last taking the
+/// remainder, so they sum to exactly `FIXED_ONE`
+fn q16_odds(spawn: &SpawnDist) -> [i64; MAX_SPAWN_OUTCOMES] {
+    let mut odds = [0i64; MAX_SPAWN_OUTCOMES];
+    for (q, &p) in odds[..spawn.n].iter_mut().zip(&spawn.probs) {
+        *q = (p * FIXED_ONE as f64).round() as i64;
+    }
+    odds[spawn.n - 1] = FIXED_ONE - odds[..spawn.n - 1].iter().sum::<i64>();
+    odds
+}
+
 /// Move node: try all 4 directions, pick best
 fn score_move_node(board: BB, depth: u32, cprob: f64) -> f64 {
+    stat(|s| s.move_nodes += 1);
+    if aborted() { return 0.0; }
+    let accumulate = CFG.with(|c| c.borrow().merge_accumulate);
     let mut best = 0.0f64;
-    for d in 0..4u8 {
-        let (nb, _ms, moved) = do_move(board, d);
+    for d in Direction::ALL {
+        let (nb, ms, moved) = timed(Phase::Moves, || do_move(board, d));
         if !moved { continue; }
-        let v = score_chance_node(nb, depth - 1, cprob);
+        let mut v = score_chance_node(nb, depth - 1, cprob);
+        if accumulate { v += ms; }
         if v > best { best = v; }
     }
     best
 }
 
-/// C ABI: given board (16 u16s) and depth, write ranked moves.
-/// Returns number of valid moves. Directions: 0=up, 1=down, 2=left, 3=right.
-#[no_mangle]
-pub extern "C" fn search_ranked_moves(
-    board_ptr: *const u16,
-    depth: u32,
-    scores_out: *mut f64,
-    dirs_out: *mut u8,
-) -> u32 {
-    init_tables();
+/// Value of playing `d`, averaged over the 8 symmetries of the board (each
+/// with the matching move), so mirror-image positions score alike whatever
+/// small asymmetries the evaluation and chance-cell choice have. No root
+/// cutoffs: a cut-short orientation would skew the mean. The values are
+/// summed in sorted order, so symmetric moves tie exactly.
+fn mirrored_value(board: BB, d: Direction, depth: u32) -> f64 {
+    let mut values: [f64; 8] = std::array::from_fn(|o| {
+        let nb = do_move(orient(board, o), d.oriented(o)).0;
+        if depth == 0 { evaluate(nb) } else { chance_node(nb, depth, 1.0, None) }
+    });
+    values.sort_by(f64::total_cmp);
+    values.iter().sum::<f64>() / 8.0
+}
 
-    // Convert u16 tile values → bitboard (log2 ranks)
-    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
-    let mut board: BB = 0;
-    for i in 0..16 {
-        let val = flat[i];
-        let rank = if val == 0 { 0u64 } else { (val as f64).log2() as u64 };
-        board |= (rank & 0xF) << (i * 4);
-    }
+/// Depth actually searched for a requested `depth`: distinct tiles − 2
+/// (nneonneo strategy) when that is deeper, within the cap; 0 stays 0
+fn adaptive_depth(board: BB, depth: u32, max_depth: u32) -> u32 {
+    if depth == 0 { return 0; }
+    let distinct = count_distinct(board);
+    let dd = if distinct >= 4 { distinct - 2 } else { 2 };
+    depth.max(dd).min(max_depth)  // use whichever is larger, within the cap
+}
 
-    // Adaptive depth: distinct tiles - 2 (nneonneo strategy)
-    let adaptive_depth = if depth > 0 {
-        let distinct = count_distinct(board);
-        let dd = if distinct >= 4 { distinct - 2 } else { 2 };
-        depth.max(dd)  // use whichever is larger
-    } else {
-        depth
-    };
+/// Rank the legal moves of `board`, best first, as (score, direction).
+/// Depth 0 is taken literally; any other depth is raised to the adaptive
+/// minimum for the board.
+fn search_root(board: BB, depth: u32) -> Vec<(f64, Direction)> {
+    begin_search();
+    let start = Instant::now();
+
+    let max_depth = CFG.with(|c| c.borrow().max_depth);
+    let adaptive_depth = adaptive_depth(board, depth, max_depth);
 
-    TT.with(|tt| tt.borrow_mut().clear());
+    stat(|s| s.depth = adaptive_depth);
+    if adaptive_depth != depth {
+        log(LOG_DEBUG, || format!("depth {depth} raised to {adaptive_depth} for {} distinct tiles", count_distinct(board)));
+    }
 
-    let mut moves: Vec<(f64, u8)> = Vec::new();
-    for d in 0..4u8 {
-        let (nb, _ms, moved) = do_move(board, d);
+    let (aggression, accumulate, penalty, objective) = CFG.with(|c| {
+        let c = c.borrow();
+        (c.aggression, c.merge_accumulate, c.variance_penalty, c.move_objective)
+    });
+    let upper = CFG.with(|c| value_upper_bound(&c.borrow()));
+    let mirror = CFG.with(|c| c.borrow().mirror_empty);
+    let mirrored = mirror > 0 && count_empty(board) > mirror && walls() == 0;
+    let mut alpha = f64::NEG_INFINITY;
+    let mut moves: Vec<(f64, Direction)> = Vec::new();
+    let mut cascades = [false; 4];
+    // No successor dedupe: two directions that both move never produce the
+    // same board (see the `moving_boards_never_coincide` test), and no-op
+    // moves, e.g. all four on an empty board, are skipped here
+    for d in Direction::ALL {
+        let (nb, ms, moved) = do_move(board, d);
         if !moved { continue; }
-        let score = score_chance_node(nb, adaptive_depth, 1.0);
+        let mut score = if mirrored {
+            mirrored_value(board, d, adaptive_depth)
+        } else if adaptive_depth == 0 {
+            evaluate(nb)
+        } else {
+            chance_node(nb, adaptive_depth, 1.0, upper.map(|u| (alpha, u)))
+        };
+        if penalty > 0.0 && adaptive_depth > 0 { score -= penalty * spawn_spread(nb, adaptive_depth); }
+        if let Some(cb) = objective {
+            score = cb(score, ms, count_empty(nb));
+        } else {
+            if accumulate { score += ms; }
+            alpha = alpha.max(score);
+            if aggression > 0.0 { score = (1.0 - aggression) * score + aggression * ms; }
+        }
+        cascades[d as usize] = cascade_length(nb) >= CASCADE_MIN_CHAIN;
         moves.push((score, d));
     }
-    moves.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    // A cancelled search's scores are meaningless: leave the margin, PV and
+    // decision log as the last finished search set them
+    if aborted() { return moves; }
 
-    let n = moves.len().min(4);
-    let scores = unsafe { std::slice::from_raw_parts_mut(scores_out, 4) };
-    let dirs = unsafe { std::slice::from_raw_parts_mut(dirs_out, 4) };
-    for i in 0..n {
-        scores[i] = moves[i].0;
-        dirs[i] = moves[i].1;
+    // Cascade bias: a move that keeps a near-solved chain ready to roll up
+    // beats any non-cascade move scoring less than CASCADE_MARGIN better.
+    if CFG.with(|c| c.borrow().cascade_bias) {
+        let best = moves.iter().map(|m| m.0).fold(f64::NEG_INFINITY, f64::max);
+        for m in moves.iter_mut() {
+            if cascades[m.1 as usize] { m.0 += best.abs() * CASCADE_MARGIN; }
+        }
+    }
+    // Look-back: undoing the caller's last move costs a small relative
+    // penalty, so a near-tie does not bounce between two moves
+    let back = CFG.with(|c| c.borrow().reverse_penalty);
+    if let Some(last) = LAST_MOVE.with(|m| m.get()).filter(|_| back > 0.0) {
+        let best = moves.iter().map(|m| m.0).fold(f64::NEG_INFINITY, f64::max);
+        for m in moves.iter_mut() {
+            if m.1 == last.reverse() { m.0 -= best.abs() * back; }
+        }
+    }
+    rank_moves(&mut moves);
+
+    let safe_eps = CFG.with(|c| c.borrow().corner_safe_eps);
+    if safe_eps > 0.0 { keep_corner(board, &mut moves, safe_eps); }
+    let unstick = CFG.with(|c| c.borrow().unstick_empty);
+    if count_empty(board) < unstick { unstick_move(board, &mut moves); }
+    let temp = CFG.with(|c| c.borrow().explore_temp);
+    if temp > 0.0 { explore_move(&mut moves, temp); }
+    if CFG.with(|c| c.borrow().survival_guard) { guard_survival(board, &mut moves); }
+
+    // The promotions above may put a lower score first, so the top two
+    // scores are looked up rather than read off the ranking
+    let mut top = moves.iter().map(|m| m.0).collect::<Vec<_>>();
+    top.sort_by(|a, b| b.total_cmp(a));
+    let margin = match top.as_slice() {
+        [] => 0.0,
+        [_] => f64::INFINITY,
+        [a, b, ..] => a - b,
+    };
+    LAST_MARGIN.with(|m| m.set(margin));
+    if let Some(&(_, best)) = moves.first().filter(|_| adaptive_depth > 0) {
+        let pv = principal_variation(board, best);
+        TT.with(|tt| tt.borrow_mut().set_pv(pv));
+    }
+    stat(|s| s.elapsed_us = start.elapsed().as_micros() as u64);
+    #[cfg(feature = "profiling")]
+    TIMINGS.with(|t| t.borrow_mut().total_ns = start.elapsed().as_nanos() as u64);
+    log_decision(board, &moves);
+    moves
+}
+
+/// Iterative deepening under a wall-clock budget: search at requested depths
+/// 0, 1, … `depth`, skipping those the adaptive rule raises to the depth just
+/// searched, and return the ranking of the deepest iteration that finished.
+/// Depth 0 runs before the clock starts, so some ranking always comes back,
+/// and with no budget at all it is the one that does. A watchdog thread
+/// raises the abort flag at the deadline; the iteration it interrupts is
+/// dropped whole, and the stats and margin are put back to the finished
+/// one's.
+fn search_timed(board: BB, depth: u32, budget: Duration) -> Vec<(f64, Direction)> {
+    let mut best = search_root(board, 0);
+    if budget.is_zero() { return best; }
+    let mut kept = (STATS.with(|s| *s.borrow()), LAST_MARGIN.with(|m| m.get()));
+    let flag = Arc::new(AtomicBool::new(false));
+    let (done, wait) = mpsc::channel::<()>();
+    let watchdog = {
+        let flag = flag.clone();
+        std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = wait.recv_timeout(budget) { flag.store(true, Ordering::Relaxed); }
+        })
+    };
+    let outer = ABORT.with(|a| a.replace(Some(flag)));
+    let max_depth = config().max_depth;
+    let mut searched = 0;
+    for d in 1..=depth {
+        let effective = adaptive_depth(board, d, max_depth);
+        if effective == searched { continue; }
+        let moves = search_root(board, d);
+        if aborted() { break; }
+        best = moves;
+        kept = (STATS.with(|s| *s.borrow()), LAST_MARGIN.with(|m| m.get()));
+        searched = effective;
+    }
+    drop(done);
+    let _ = watchdog.join();
+    ABORT.with(|a| *a.borrow_mut() = outer);
+    STATS.with(|s| *s.borrow_mut() = kept.0);
+    LAST_MARGIN.with(|m| m.set(kept.1));
+    best
+}
+
+/// Standard deviation of the values of the spawns after a root move, weighted
+/// by their odds: how far the move's average hides a bad spawn. Re-reads the
+/// chance node `chance_node` has just searched, so the subtrees come from
+/// the TT.
+fn spawn_spread(board: BB, depth: u32) -> f64 {
+    let (spawn, max_cells) = CFG.with(|c| {
+        let c = c.borrow();
+        (c.spawn, c.max_chance_cells)
+    });
+    let (cells, n) = chance_cells(board, max_cells);
+    if n == 0 { return 0.0; }
+    let (mut sum, mut sum_sq) = (0.0, 0.0);
+    for &i in &cells[..n] {
+        for (rank, p) in spawn.outcomes() {
+            let w = p / n as f64;
+            let v = score_move_node(board | ((rank as u64) << (i * 4)), depth, w);
+            sum += w * v;
+            sum_sq += w * v * v;
+        }
+    }
+    (sum_sq - sum * sum).max(0.0).sqrt()
+}
+
+/// TT keys on the principal variation after playing `best`: its afterstate
+/// and, for every spawn there, the afterstate of the reply the TT rates
+/// best. The next search starts from one of those spawns, so these are the
+/// entries it reuses first, and eviction keeps them.
+fn principal_variation(board: BB, best: Direction) -> Vec<BB> {
+    let after = do_move(board, best).0;
+    let spawn = CFG.with(|c| c.borrow().spawn);
+    let mut pv = vec![after];
+    TT.with(|tt| {
+        let tt = tt.borrow();
+        for i in 0..16 {
+            if (after >> (i * 4)) & 0xF != 0 { continue; }
+            for (rank, _) in spawn.outcomes() {
+                let s = after | ((rank as u64) << (i * 4));
+                let reply = Direction::ALL.iter()
+                    .filter_map(|&d| {
+                        let (nb, _, moved) = do_move(s, d);
+                        if moved { tt.get(nb).map(|(_, v)| (v, nb)) } else { None }
+                    })
+                    .max_by(|a, b| a.0.total_cmp(&b.0));
+                if let Some((_, nb)) = reply { pv.push(nb); }
+            }
+        }
+    });
+    pv
+}
+
+/// Part of a root move's score that is its own merge score `ms`, as
+/// `search_root` folds it in: all of it with merge accumulation, then
+/// scaled by the aggression blend, which adds its own share. Unknown, and
+/// taken as 0, under a move objective.
+fn merge_share(ms: f64, c: &Config) -> f64 {
+    if c.move_objective.is_some() { return 0.0; }
+    let acc = if c.merge_accumulate { ms } else { 0.0 };
+    if c.aggression > 0.0 { (1.0 - c.aggression) * acc + c.aggression * ms } else { acc }
+}
+
+/// Sort root moves best first. A NaN score (only reachable through
+/// non-finite n-tuple values) is replaced by 0, the score
+/// of a lost position, so it ranks last and never reaches the caller.
+fn rank_moves(moves: &mut [(f64, Direction)]) {
+    for m in moves.iter_mut() {
+        if m.0.is_nan() { m.0 = 0.0; }
+    }
+    moves.sort_by(|a, b| b.0.total_cmp(&a.0));
+}
+
+/// Exploration: draw the move to play from a softmax over the scores, with
+/// temperature `temp` × |best score|, and promote it (scores are left as
+/// they are). Draws come from `EXPLORE_RNG`, which carries on across
+/// searches and threads.
+fn explore_move(moves: &mut Vec<(f64, Direction)>, temp: f64) {
+    let Some(&(best, _)) = moves.first() else { return };
+    let t = (best.abs() * temp).max(f64::MIN_POSITIVE);
+    let weights: Vec<f64> = moves.iter().map(|m| ((m.0 - best) / t).exp()).collect();
+    let total: f64 = weights.iter().sum();
+    let mut x = explore_rng().next_f64() * total;
+    let k = weights.iter().position(|&w| { x -= w; x < 0.0 }).unwrap_or(0);
+    let m = moves.remove(k);
+    moves.insert(0, m);
+}
+
+static EXPLORE_RNG: Mutex<Rng> = Mutex::new(Rng(0));
+
+fn explore_rng() -> MutexGuard<'static, Rng> {
+    EXPLORE_RNG.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Corner safe mode: if the max tile sits in a corner and the best move
+/// pulls it out, promote the best move within `eps` × |best| that keeps it
+/// there (scores are left as they are). No-op when there is none.
+fn keep_corner(board: BB, moves: &mut Vec<(f64, Direction)>, eps: f64) {
+    let mt = max_rank(board);
+    let Some(&(r, c)) = CORNERS.iter().find(|&&(r, c)| cell(board, r, c) == mt) else { return };
+    let keeps = |d: Direction| {
+        let nb = do_move(board, d).0;
+        cell(nb, r, c) == max_rank(nb)
+    };
+    if moves.first().is_none_or(|m| keeps(m.1)) { return; }
+    let band = moves[0].0 - eps * moves[0].0.abs();
+    if let Some(k) = moves.iter().position(|m| m.0 >= band && keeps(m.1)) {
+        let m = moves.remove(k);
+        moves.insert(0, m);
+    }
+}
+
+/// Unstick mode: when no move scores above the static evaluation of the
+/// board itself, the search sees only losing lines and its top pick can be
+/// the quickest death. Promote the move leaving the most mobility instead
+/// (scores are left as they are).
+fn unstick_move(board: BB, moves: &mut Vec<(f64, Direction)>) {
+    let Some(&(best, _)) = moves.first() else { return };
+    if best > evaluate(board) { return; }
+    let k = (0..moves.len())
+        .max_by_key(|&k| (mobility(do_move(board, moves[k].1).0), std::cmp::Reverse(k)))
+        .unwrap_or(0);
+    let m = moves.remove(k);
+    moves.insert(0, m);
+}
+
+/// Survival guardrail: if the top move allows a spawn after which no move
+/// is legal, promote the best-ranked move that survives every spawn (scores
+/// are left as they are). No-op when there is none.
+fn guard_survival(board: BB, moves: &mut Vec<(f64, Direction)>) {
+    let safe = |d: Direction| survives_spawns(do_move(board, d).0);
+    if moves.first().is_none_or(|m| safe(m.1)) { return; }
+    if let Some(k) = moves.iter().position(|m| safe(m.1)) {
+        let m = moves.remove(k);
+        moves.insert(0, m);
+    }
+}
+
+/// Does every possible spawn on `b` still leave a legal move?
+fn survives_spawns(b: BB) -> bool {
+    let spawn = CFG.with(|c| c.borrow().spawn);
+    (0..16)
+        .filter(|&i| (b >> (i * 4)) & 0xF == 0)
+        .all(|i| spawn.outcomes().all(|(rank, _)| count_legal(b | ((rank as u64) << (i * 4))) > 0))
+}
+
+/// Room to manoeuvre after a move: whether every possible spawn still
+/// leaves a legal move, then empty cells plus adjacent equal pairs
+fn mobility(b: BB) -> (bool, u32) {
+    let survives = survives_spawns(b);
+    let mut pairs = 0;
+    for r in 0..4 {
+        for c in 0..4 {
+            let v = cell(b, r, c);
+            if v == 0 { continue; }
+            if c < 3 && cell(b, r, c + 1) == v { pairs += 1; }
+            if r < 3 && cell(b, r + 1, c) == v { pairs += 1; }
+        }
+    }
+    (survives, count_empty(b) + pairs)
+}
+
+/// Upper bound on any node value, for root chance-node cutoffs, or None when
+/// cutoffs are off or unsafe: anything that reshapes root scores after the
+/// chance node (aggression, cascade bias, the worst-case blend, the variance
+/// and look-back penalties, corner safe mode, unstick mode, exploration, the
+/// survival guardrail, a move objective) or node values without a cheap bound (board-level
+/// terms, n-tuple networks, accumulated merge scores), and fixed-point mode,
+/// whose chance nodes do not take a cut.
+fn value_upper_bound(c: &Config) -> Option<f64> {
+    if !c.chance_cutoff || c.aggression > 0.0 || c.cascade_bias || c.chance_blend > 0.0
+        || c.variance_penalty > 0.0 || c.reverse_penalty > 0.0 || c.corner_safe_eps > 0.0
+        || c.unstick_empty > 0 || c.explore_temp > 0.0 || c.survival_guard || c.merge_accumulate
+        || c.move_objective.is_some() || c.fixed_point
+        || c.ntuple.is_some() || has_board_terms(&c.weights) || has_board_terms(&c.survival_weights)
+    {
+        return None;
+    }
+    // 8 lines per board; dead boards score 0, also below this
+    let line_max = |t: &[f64; 65536]| t.iter().copied().fold(0.0, f64::max);
+    let tables = c.tables.as_deref()?;
+    let (build, survive) = (line_max(&tables.heur), line_max(&tables.heur_surv));
+    Some(8.0 * build.max(survive))
+}
+
+/// Prepare this thread for a top-level search: tables, config snapshot, fresh
+/// TT (kept, for a SearchContext search, unless its layout changed).
+fn begin_search() {
+    load_search_config();
+    let bits = CFG.with(|c| c.borrow().tt_array_bits);
+    let keep = KEEP_TT.with(|k| k.get());
+    TT.with(|tt| {
+        let mut tt = tt.borrow_mut();
+        if tt.array_bits() != bits {
+            *tt = TransTable::new(bits);
+        } else if !keep {
+            tt.clear();
+        }
+    });
+    reset_search_counters();
+}
+
+/// Zero this thread's counters and reseed its search RNG
+fn reset_search_counters() {
+    STATS.with(|s| *s.borrow_mut() = SearchStats::default());
+    #[cfg(feature = "profiling")]
+    TIMINGS.with(|t| *t.borrow_mut() = SearchTimings::default());
+    let seed = CFG.with(|c| c.borrow().search_seed);
+    SEARCH_RNG.with(|r| *r.borrow_mut() = seed.map(Rng));
+}
+
+/// `load_config`, plus the per-search adjustments: survival forced from the
+/// move number on, and the adversarial chance settings
+fn load_search_config() {
+    load_config();
+    if let Some(n) = MOVE_NUMBER.with(|m| m.get()) {
+        CFG.with(|c| {
+            let mut c = c.borrow_mut();
+            c.force_survival = c.survival_from_move > 0 && n >= c.survival_from_move;
+        });
+    }
+    if ADVERSARIAL.with(|a| a.get()) {
+        CFG.with(|c| {
+            let mut c = c.borrow_mut();
+            c.chance_blend = 1.0;
+            c.max_chance_cells = 16;
+            c.chance_selector = None;
+        });
+    }
+}
+
+/// Run `f` as a search of its own on a scratch TT and counters, leaving
+/// this thread's TT, counters and margin as the caller's last search set
+/// them
+fn scratch_search<T>(f: impl FnOnce() -> T) -> T {
+    load_search_config();
+    let tt = TT.with(|tt| tt.replace(TransTable::Map { map: HashMap::new(), pv: Vec::new() }));
+    let stats = STATS.with(|s| *s.borrow());
+    #[cfg(feature = "profiling")]
+    let timings = TIMINGS.with(|t| *t.borrow());
+    reset_search_counters();
+    let out = f();
+    TT.with(|t| *t.borrow_mut() = tt);
+    STATS.with(|s| *s.borrow_mut() = stats);
+    #[cfg(feature = "profiling")]
+    TIMINGS.with(|t| *t.borrow_mut() = timings);
+    out
+}
+
+/// Make the current global config visible to this thread's evaluation,
+/// building its tables first if a setter has dropped them
+fn load_config() {
+    let mut cfg = {
+        let mut global = config();
+        if global.tables.is_none() { global.tables = Some(Arc::new(Tables::new(&global))); }
+        global.clone()
+    };
+    cfg.weights = masked(&cfg.weights, cfg.term_mask);
+    cfg.survival_weights = masked(&cfg.survival_weights, cfg.term_mask);
+    cfg.spawn = cfg.spawn.risk_weighted(cfg.spawn_risk);
+    CFG.with(|c| *c.borrow_mut() = cfg);
+}
+
+// ── Best-first search ──
+// Grows an explicit expectimax tree one leaf at a time under a node budget
+// instead of sweeping every line to a fixed depth. Move nodes pick the child
+// with the best value plus a UCB exploration bonus; chance nodes pick the
+// spawn with the most probability per visit. Leaves are valued by
+// `evaluate`, and each expansion re-backs-up values along its path.
+
+struct BfNode {
+    board: BB,
+    value: f64,
+    visits: u32,
+    prob: f64,       // chance-node children: probability of this spawn; else 1
+    dir: Direction,  // move-node children: the move that led here
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+struct BfTree {
+    nodes: Vec<BfNode>,
+}
+
+impl BfTree {
+    fn push(&mut self, board: BB, value: f64, prob: f64, dir: Direction) -> usize {
+        self.nodes.push(BfNode { board, value, visits: 0, prob, dir, children: Vec::new(), expanded: false });
+        self.nodes.len() - 1
+    }
+
+    /// Create a node's children. Move nodes (even depth) get one chance node
+    /// per legal move, chance nodes one move node per spawn.
+    fn expand(&mut self, id: usize, is_move: bool) {
+        let board = self.nodes[id].board;
+        self.nodes[id].expanded = true;
+        if is_move {
+            stat(|s| s.move_nodes += 1);
+            for d in Direction::ALL {
+                let (nb, _, moved) = do_move(board, d);
+                if !moved { continue; }
+                let c = self.push(nb, evaluate(nb), 1.0, d);
+                self.nodes[id].children.push(c);
+            }
+        } else {
+            stat(|s| s.chance_nodes += 1);
+            let (spawn, max_cells) = CFG.with(|c| {
+                let c = c.borrow();
+                (c.spawn, c.max_chance_cells)
+            });
+            let (cells, n) = chance_cells(board, max_cells);
+            for &i in &cells[..n] {
+                for (rank, p) in spawn.outcomes() {
+                    let sb = board | ((rank as u64) << (i * 4));
+                    let c = self.push(sb, evaluate(sb), p / n as f64, Direction::Up);
+                    self.nodes[id].children.push(c);
+                }
+            }
+        }
+    }
+
+    /// Value implied by a node's children: best move, or spawn expectation.
+    /// Move nodes with no legal move are dead and worth 0; chance nodes with
+    /// no empty cell keep their static value.
+    fn backup(&mut self, id: usize, is_move: bool) {
+        let node = &self.nodes[id];
+        let kids = node.children.iter().map(|&c| &self.nodes[c]);
+        let value = if is_move {
+            kids.map(|c| c.value).fold(0.0, f64::max)
+        } else if node.children.is_empty() {
+            node.value
+        } else {
+            kids.map(|c| c.prob * c.value).sum()
+        };
+        self.nodes[id].value = value;
+    }
+
+    fn select(&self, id: usize, is_move: bool) -> usize {
+        let node = &self.nodes[id];
+        let score = |c: usize| {
+            let ch = &self.nodes[c];
+            if is_move {
+                let bonus = BF_EXPLORE * node.value.abs()
+                    * ((node.visits as f64 + 1.0).ln() / (ch.visits as f64 + 1.0)).sqrt();
+                ch.value + bonus
+            } else {
+                ch.prob / (ch.visits as f64 + 1.0)
+            }
+        };
+        node.children.iter().copied()
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+            .expect("selected node has children")
+    }
+
+    /// One descent: walk to an unexpanded node, expand it, back up the path.
+    /// Returns false if the walk ended on a terminal node instead; its path
+    /// still gains a visit, which steers the next descent elsewhere.
+    fn grow(&mut self) -> bool {
+        let mut path = vec![0usize];
+        let mut id = 0;
+        let mut grew = false;
+        loop {
+            if !self.nodes[id].expanded {
+                self.expand(id, path.len() % 2 == 1);
+                grew = true;
+                break;
+            }
+            if self.nodes[id].children.is_empty() { break; }
+            id = self.select(id, path.len() % 2 == 1);
+            path.push(id);
+        }
+        for (depth, &n) in path.iter().enumerate().rev() {
+            self.nodes[n].visits += 1;
+            self.backup(n, depth % 2 == 0);
+        }
+        grew
+    }
+}
+
+/// Rank root moves by best-first expansion of at most `budget` nodes
+fn search_best_first(board: BB, budget: usize) -> Vec<(f64, Direction)> {
+    begin_search();
+    let start = Instant::now();
+    let mut tree = BfTree { nodes: Vec::with_capacity(budget.min(1 << 20)) };
+    tree.push(board, evaluate(board), 1.0, Direction::Up);
+    tree.grow();
+    // Stalls are descents that only hit terminal nodes; after `budget` of
+    // them in a row the tree is (near enough) exhausted
+    let mut stalls = 0;
+    while tree.nodes.len() < budget && stalls < budget && !aborted() {
+        stalls = if tree.grow() { 0 } else { stalls + 1 };
+    }
+    let mut moves: Vec<(f64, Direction)> = tree.nodes[0].children.iter()
+        .map(|&c| (tree.nodes[c].value, tree.nodes[c].dir))
+        .collect();
+    rank_moves(&mut moves);
+    stat(|s| s.elapsed_us = start.elapsed().as_micros() as u64);
+    moves
+}
+
+// ── Decision log ──
+// Ring buffer of recent top-level searches from every thread, for "why did it
+// play that?" bug reports. Off unless `set_decision_log` gives it a size.
+
+/// One top-level search: the board searched, its ranked output, and the
+/// `evaluate_components` breakdown of the boards the top two moves produce
+/// (before any spawn), so the term that separated them can be read off
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DecisionRecord {
+    pub board: [u16; 16],
+    pub scores: [f64; 4],
+    pub dirs: [u8; 4],
+    pub count: u32,  // valid entries in scores/dirs
+    pub terms: [[f64; NUM_TERMS as usize]; 2],  // best and second move; zero where absent
+}
+
+static DECISION_LOG: Mutex<VecDeque<DecisionRecord>> = Mutex::new(VecDeque::new());
+
+fn decision_log() -> MutexGuard<'static, VecDeque<DecisionRecord>> {
+    DECISION_LOG.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn log_decision(board: BB, moves: &[(f64, Direction)]) {
+    let cap = CFG.with(|c| c.borrow().decision_log) as usize;
+    if cap == 0 { return; }
+    let mut rec = DecisionRecord {
+        board: board_tiles(board),
+        scores: [0.0; 4],
+        dirs: [0; 4],
+        count: 0,
+        terms: [[0.0; NUM_TERMS as usize]; 2],
+    };
+    for (i, &(score, dir)) in moves.iter().take(4).enumerate() {
+        rec.scores[i] = score;
+        rec.dirs[i] = dir as u8;
+        rec.count += 1;
+    }
+    CFG.with(|c| {
+        let c = c.borrow();
+        for (t, &(_, dir)) in rec.terms.iter_mut().zip(moves) {
+            let nb = do_move(board, dir).0;
+            *t = eval_components(nb, active_weights(nb, &c), &c);
+        }
+    });
+    let mut log = decision_log();
+    while log.len() >= cap { log.pop_front(); }
+    log.push_back(rec);
+}
+
+// ── Logging ──
+// Diagnostics go to a host-supplied callback; with none installed every log
+// site costs one relaxed atomic load.
+
+/// Log levels passed to the callback
+pub const LOG_ERROR: u32 = 0;
+pub const LOG_WARN:  u32 = 1;
+pub const LOG_INFO:  u32 = 2;  // configuration changes
+pub const LOG_DEBUG: u32 = 3;  // per-search detail: depth changes, TT clears
+
+/// Receives a level and a NUL-terminated message, valid only for the call.
+/// May be invoked from any thread that searches or changes configuration,
+/// sometimes mid-search, so it must not call back into the engine.
+pub type LogCallback = extern "C" fn(level: u32, msg: *const c_char);
+
+static LOG_ON: AtomicBool = AtomicBool::new(false);
+static LOG_SINK: Mutex<Option<LogCallback>> = Mutex::new(None);
+
+/// Emit a message; `msg` is only formatted when a callback is installed
+fn log(level: u32, msg: impl FnOnce() -> String) {
+    if !LOG_ON.load(Ordering::Relaxed) { return; }
+    let Some(cb) = *LOG_SINK.lock().unwrap_or_else(|e| e.into_inner()) else { return };
+    let text = msg().replace('\0', " ");
+    if let Ok(c) = std::ffi::CString::new(text) { cb(level, c.as_ptr()); }
+}
+
+fn log_set(name: &str, value: impl std::fmt::Display) {
+    log(LOG_INFO, || format!("config: {name} = {value}"));
+}
+
+/// C ABI: install (or with null, remove) the diagnostics callback
+#[no_mangle]
+pub extern "C" fn set_log_callback(cb: Option<LogCallback>) {
+    *LOG_SINK.lock().unwrap_or_else(|e| e.into_inner()) = cb;
+    LOG_ON.store(cb.is_some(), Ordering::Relaxed);
+}
+
+// ── Error reporting ──
+// Fallible C ABI calls clear the calling thread's last error on entry and
+// set it when they reject an argument, besides returning their documented
+// failure value, so callers can tell why a call returned 0 or null.
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    Ok = 0,
+    NullPointer = 1,      // a required pointer argument was null
+    InvalidDepth = 2,     // depth over the configured cap
+    MalformedBoard = 3,   // a cell that is neither 0 nor a power of two ≥ 2, or a tile off the board shape
+    InvalidArgument = 4,  // any other out-of-range argument
+    MalformedData = 5,    // an n-tuple or game-state blob that does not parse
+    Busy = 6,             // board shape in use by a background search or parallel batch
+}
+
+impl ErrorCode {
+    fn message(self) -> &'static std::ffi::CStr {
+        match self {
+            ErrorCode::Ok => c"ok",
+            ErrorCode::NullPointer => c"null pointer argument",
+            ErrorCode::InvalidDepth => c"depth exceeds the configured maximum",
+            ErrorCode::MalformedBoard => c"board holds a value that is not a tile",
+            ErrorCode::InvalidArgument => c"argument out of range",
+            ErrorCode::MalformedData => c"malformed data blob",
+            ErrorCode::Busy => c"board shape in use by a background search or parallel batch",
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: Cell<ErrorCode> = const { Cell::new(ErrorCode::Ok) };
+}
+
+fn clear_error() {
+    LAST_ERROR.with(|e| e.set(ErrorCode::Ok));
+}
+
+fn set_error(code: ErrorCode) {
+    LAST_ERROR.with(|e| e.set(code));
+}
+
+/// False (recording `NullPointer`) for a null argument
+fn non_null<T>(p: *const T) -> bool {
+    if p.is_null() { set_error(ErrorCode::NullPointer); }
+    !p.is_null()
+}
+
+/// False (recording `InvalidDepth`) for a depth over the cap
+fn depth_ok(depth: u32) -> bool {
+    let ok = depth <= config().max_depth;
+    if !ok { set_error(ErrorCode::InvalidDepth); }
+    ok
+}
+
+/// Is cell `i` a wall of the current board shape?
+fn off_board(i: usize) -> bool {
+    (walls() >> (i * 4)) & 0xF != 0
+}
+
+/// Read a board of raw tile values, or record why it cannot be read
+fn board_arg(board_ptr: *const u16) -> Option<BB> {
+    if !non_null(board_ptr) { return None; }
+    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
+    if flat.iter().enumerate().any(|(i, &v)| v == 1 || !(v == 0 || v.is_power_of_two()) || (v != 0 && off_board(i))) {
+        set_error(ErrorCode::MalformedBoard);
+        return None;
+    }
+    Some(read_board(board_ptr))
+}
+
+/// `board_arg` for a board of log2 ranks: every rank reads as a tile, but
+/// none may sit on a wall cell
+fn board_arg_log2(board_ptr: *const u8) -> Option<BB> {
+    if !non_null(board_ptr) { return None; }
+    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
+    if flat.iter().enumerate().any(|(i, &r)| r != 0 && off_board(i)) {
+        set_error(ErrorCode::MalformedBoard);
+        return None;
+    }
+    Some(read_board_log2(board_ptr))
+}
+
+/// `board_arg` for a board of signed exponents: every value reads (≤ 0 as
+/// empty), but no tile may sit on a wall cell
+fn board_arg_exp(board_ptr: *const i8) -> Option<BB> {
+    if !non_null(board_ptr) { return None; }
+    let flat = unsafe { std::slice::from_raw_parts(board_ptr, 16) };
+    if flat.iter().enumerate().any(|(i, &e)| e > 0 && off_board(i)) {
+        set_error(ErrorCode::MalformedBoard);
+        return None;
+    }
+    Some(read_board_exp(board_ptr))
+}
+
+/// C ABI: `ErrorCode` of this thread's most recent fallible call (0 = it
+/// succeeded)
+#[no_mangle]
+pub extern "C" fn last_error() -> u32 {
+    LAST_ERROR.with(|e| e.get()) as u32
+}
+
+/// C ABI: static description of `last_error`. The string must not be freed.
+#[no_mangle]
+pub extern "C" fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.get()).message().as_ptr()
+}
+
+// ── C ABI: configuration ──
+// Setters apply to searches started afterwards; getters return the defaults
+// until something is set.
+
+/// Replace the heuristic weights; the next search builds row tables for
+/// them, while searches already running keep their own.
+/// Null restores the built-in CMA-ES weights. A set holding a NaN or
+/// infinite weight is rejected (`InvalidArgument`), keeping the current one.
+#[no_mangle]
+pub extern "C" fn set_eval_weights(weights: *const EvalWeights) {
+    clear_error();
+    if validate_eval_weights(weights) == WEIGHTS_NON_FINITE {
+        set_error(ErrorCode::InvalidArgument);
+        return;
+    }
+    let w = if weights.is_null() { DEFAULT_WEIGHTS } else { unsafe { *weights } };
+    let mut cfg = config();
+    cfg.weights = w;
+    cfg.tables = None;
+    drop(cfg);
+    log_set("eval_weights", if weights.is_null() { "defaults" } else { "custom" });
+}
+
+#[no_mangle]
+pub extern "C" fn get_eval_weights(out: *mut EvalWeights) {
+    if out.is_null() { return; }
+    unsafe { *out = config().weights; }
+}
+
+/// Weights for the endgame (survival) profile. Null restores the preset,
+/// which trades structure for empty cells and merges. Non-finite sets are
+/// rejected as for `set_eval_weights`.
+#[no_mangle]
+pub extern "C" fn set_survival_weights(weights: *const EvalWeights) {
+    clear_error();
+    if validate_eval_weights(weights) == WEIGHTS_NON_FINITE {
+        set_error(ErrorCode::InvalidArgument);
+        return;
+    }
+    let w = if weights.is_null() { SURVIVAL_WEIGHTS } else { unsafe { *weights } };
+    let mut cfg = config();
+    cfg.survival_weights = w;
+    cfg.tables = None;
+    drop(cfg);
+    log_set("survival_weights", if weights.is_null() { "defaults" } else { "custom" });
+}
+
+#[no_mangle]
+pub extern "C" fn get_survival_weights(out: *mut EvalWeights) {
+    if out.is_null() { return; }
+    unsafe { *out = config().survival_weights; }
+}
+
+/// `validate_eval_weights` results
+pub const WEIGHTS_OK: u32 = 0;
+pub const WEIGHTS_NON_FINITE: u32 = 1;  // a NaN or infinite weight; the setters refuse these
+pub const WEIGHTS_NEGATIVE: u32 = 2;    // a negative weight; accepted, but it flips the term
+
+/// C ABI: check a weight set before installing it. Non-finite weights would
+/// turn every score NaN; negative ones turn bonuses into penalties and vice
+/// versa (a negative `lost` even lets playable boards score below dead
+/// ones), which is rarely intended. Returns the first problem found, non-
+/// finite before negative, or `WEIGHTS_OK`; null (the defaults) is fine.
+#[no_mangle]
+pub extern "C" fn validate_eval_weights(weights: *const EvalWeights) -> u32 {
+    if weights.is_null() { return WEIGHTS_OK; }
+    let values = unsafe { *weights }.values();
+    if values.iter().any(|v| !v.is_finite()) {
+        WEIGHTS_NON_FINITE
+    } else if values.iter().any(|&v| v < 0.0) {
+        WEIGHTS_NEGATIVE
+    } else {
+        WEIGHTS_OK
+    }
+}
+
+/// Enable only the evaluation terms whose `TERM_*` bits are set, in both
+/// profiles, without touching the stored weights: a cleared bit acts as a
+/// zero weight. For ablation runs. Default `TERM_ALL`.
+#[no_mangle]
+pub extern "C" fn set_eval_term_mask(mask: u32) {
+    clear_error();
+    let mut cfg = config();
+    cfg.term_mask = mask & TERM_ALL;
+    cfg.tables = None;
+    drop(cfg);
+    log_set("eval_term_mask", format_args!("{:#05x}", mask & TERM_ALL));
+}
+
+#[no_mangle]
+pub extern "C" fn get_eval_term_mask() -> u32 {
+    config().term_mask
+}
+
+/// Evaluate boards with fewer than `empty_cells` empties under the survival
+/// profile instead of the building one. 0 (default) disables the switch.
+#[no_mangle]
+pub extern "C" fn set_endgame_threshold(empty_cells: u32) {
+    config().endgame_empty = empty_cells.min(16);
+    log_set("endgame_threshold", config().endgame_empty);
+}
+
+#[no_mangle]
+pub extern "C" fn get_endgame_threshold() -> u32 {
+    config().endgame_empty
+}
+
+/// Tempo switch for `search_ranked_moves_with_context`: from move number
+/// `move_number` on, evaluate every board under the survival profile. Plain
+/// searches, which carry no move number, are unaffected. 0 (default) is off.
+#[no_mangle]
+pub extern "C" fn set_survival_from_move(move_number: u32) {
+    config().survival_from_move = move_number;
+    log_set("survival_from_move", config().survival_from_move);
+}
+
+#[no_mangle]
+pub extern "C" fn get_survival_from_move() -> u32 {
+    config().survival_from_move
+}
+
+/// Anchor the corner term to one corner (0 = top-left, 1 = top-right,
+/// 2 = bottom-left, 3 = bottom-right): only that corner earns the bonus and
+/// the other three count as edge cells. Any other value (e.g. `CORNER_ANY`,
+/// the default) accepts all four corners. Has no effect while
+/// `EvalWeights::corner` is 0.
+#[no_mangle]
+pub extern "C" fn set_preferred_corner(corner: u8) {
+    config().preferred_corner = if corner < 4 { corner } else { CORNER_ANY };
+    log_set("preferred_corner", config().preferred_corner);
+}
+
+#[no_mangle]
+pub extern "C" fn get_preferred_corner() -> u8 {
+    config().preferred_corner
+}
+
+/// Probability that a spawned tile is a 2 (the rest are 4s). Default 0.9.
+/// Replaces any distribution set with `set_spawn_distribution`. The 4s get
+/// 1 − p as rounded in f64 (0.09999999999999998 for 0.9, not the default's
+/// 0.1); `set_spawn_distribution` takes both probabilities as given.
+#[no_mangle]
+pub extern "C" fn set_spawn_probability(p: f64) {
+    if p.is_finite() {
+        let p = p.clamp(0.0, 1.0);
+        config().spawn = SpawnDist::two_four([p, 1.0 - p]);
+        log_set("spawn_probability", p);
+    }
+}
+
+/// Probability that a spawned tile is a 2 under the current distribution
+#[no_mangle]
+pub extern "C" fn get_spawn_probability() -> f64 {
+    config().spawn.outcomes().filter(|&(r, _)| r == 1).map(|(_, p)| p).sum()
+}
+
+/// Spawn one of `n` (1–4) tile values with the matching probabilities, for
+/// variants that spawn e.g. only 2s, or 8s. Values must be distinct powers of
+/// two from 2 to 32768 (the board stores log2 ranks, so 3s and 6s cannot be
+/// represented) and probabilities must be non-negative and sum to 1 within
+/// 1e-6; they are renormalised. Returns false, changing nothing, otherwise.
+/// Null `values` restores the standard 90% 2 / 10% 4.
+#[no_mangle]
+pub extern "C" fn set_spawn_distribution(values: *const u16, probs: *const f64, n: u32) -> bool {
+    clear_error();
+    if values.is_null() {
+        config().spawn = DEFAULT_CONFIG.spawn;
+        log_set("spawn_distribution", "defaults");
+        return true;
+    }
+    let n = n as usize;
+    if !non_null(probs) { return false; }
+    let reject = || { set_error(ErrorCode::InvalidArgument); false };
+    if n == 0 || n > MAX_SPAWN_OUTCOMES { return reject(); }
+    let values = unsafe { std::slice::from_raw_parts(values, n) };
+    let probs = unsafe { std::slice::from_raw_parts(probs, n) };
+    let mut dist = SpawnDist { ranks: [0; MAX_SPAWN_OUTCOMES], probs: [0.0; MAX_SPAWN_OUTCOMES], n };
+    for (i, (&v, &p)) in values.iter().zip(probs).enumerate() {
+        if !v.is_power_of_two() || v < 2 || !(p.is_finite() && p >= 0.0) { return reject(); }
+        let rank = v.trailing_zeros() as u8;
+        if dist.ranks[..i].contains(&rank) { return reject(); }
+        dist.ranks[i] = rank;
+        dist.probs[i] = p;
+    }
+    let sum: f64 = probs.iter().sum();
+    if (sum - 1.0).abs() > 1e-6 {
+        log(LOG_WARN, || format!("spawn distribution rejected: probabilities sum to {sum}"));
+        return reject();
+    }
+    for p in &mut dist.probs[..n] { *p /= sum; }
+    config().spawn = dist;
+    log_set("spawn_distribution", format_args!("{n} outcomes"));
+    true
+}
+
+/// Copy the spawn distribution into `values_out` / `probs_out` (4 slots
+/// each, null skipped); returns the number of outcomes.
+#[no_mangle]
+pub extern "C" fn get_spawn_distribution(values_out: *mut u16, probs_out: *mut f64) -> u32 {
+    let spawn = config().spawn;
+    for (i, (rank, p)) in spawn.outcomes().enumerate() {
+        if !values_out.is_null() { unsafe { *values_out.add(i) = 1 << rank; } }
+        if !probs_out.is_null() { unsafe { *probs_out.add(i) = p; } }
+    }
+    spawn.n as u32
+}
+
+/// Cap on empty cells expanded per chance node (1–16, default 16 = all).
+/// Capped nodes keep the cells with the most occupied neighbours.
+#[no_mangle]
+pub extern "C" fn set_max_chance_cells(n: u32) {
+    config().max_chance_cells = n.clamp(1, 16);
+    log_set("max_chance_cells", config().max_chance_cells);
+}
+
+#[no_mangle]
+pub extern "C" fn get_max_chance_cells() -> u32 {
+    config().max_chance_cells
+}
+
+/// Chance cell selector: given the board (16 tile values) and room for `max`
+/// cell indices (row-major, 0–15), writes the empty cells to expand and
+/// returns how many it wrote. Pointers are valid only for the call.
+pub type ChanceSelector = extern "C" fn(board: *const u16, out_cells: *mut u8, max: u32) -> u32;
+
+/// C ABI: install (or with null, remove) a chance cell selector, replacing
+/// the built-in top-K-by-neighbours choice at every chance node; `max` is the
+/// `set_max_chance_cells` cap. Returned indices that are not empty cells are
+/// ignored, and a node left with none falls back to the built-in choice.
+///
+/// The selector runs deep inside the search, once per chance node and on
+/// every searching thread at once, so it must be thread-safe and fast, and
+/// must not call back into the engine.
+#[no_mangle]
+pub extern "C" fn set_chance_selector(cb: Option<ChanceSelector>) {
+    config().chance_selector = cb;
+    log_set("chance_selector", if cb.is_some() { "custom" } else { "built-in" });
+}
+
+/// Tile value that counts as a win (default 2048). Must be a power of two.
+#[no_mangle]
+pub extern "C" fn set_win_target(tile: u16) {
+    if tile.is_power_of_two() && tile >= 4 {
+        config().win_target = tile;
+        log_set("win_target", tile);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_win_target() -> u16 {
+    config().win_target
+}
+
+/// Play on a `rows` × `cols` board (each 2–4), e.g. 3×4, instead of the
+/// full 4×4. The board stays a 16-cell row-major array: the shape occupies
+/// its top-left corner and the other cells must stay 0 (a tile there is a
+/// `MalformedBoard`). Internally they hold walls that never move, merge
+/// or take a spawn, and searches from then on build move and row heuristic
+/// tables whose lines stop at them. The line heuristic terms adapt; the
+/// board-level terms (corner, snake and the rest) assume 4×4, as do the
+/// default snake weights. No tile may reach 32768, the wall value, which a
+/// board this small never gets near. Returns false (`InvalidArgument`) for
+/// another size, and false (`Busy`) while a background search or parallel
+/// batch runs. The walls are shared by all threads: change the shape only
+/// while no search runs.
+#[no_mangle]
+pub extern "C" fn set_board_shape(rows: u8, cols: u8) -> bool {
+    clear_error();
+    if !(2..=4).contains(&rows) || !(2..=4).contains(&cols) {
+        set_error(ErrorCode::InvalidArgument);
+        return false;
+    }
+    let mut cfg = config();
+    if !shape_idle(&cfg) { return false; }
+    let mut w = 0u64;
+    for i in 0..16 {
+        if i / 4 >= rows as usize || i % 4 >= cols as usize { w |= 0xF << (i * 4); }
+    }
+    cfg.board_shape = [rows, cols];
+    WALLS.store(w, Ordering::Relaxed);
+    cfg.tables = None;
+    drop(cfg);
+    log_set("board_shape", format_args!("{rows}x{cols}"));
+    true
+}
+
+/// Current board shape as rows × columns
+#[no_mangle]
+pub extern "C" fn get_board_shape(rows_out: *mut u8, cols_out: *mut u8) {
+    clear_error();
+    if !non_null(rows_out) || !non_null(cols_out) { return; }
+    let [rows, cols] = config().board_shape;
+    unsafe {
+        *rows_out = rows;
+        *cols_out = cols;
+    }
+}
+
+/// Blend chance nodes between the expected value (0.0, default) and the
+/// worst single spawn (1.0). Values in between trade average strength for
+/// robustness against bad spawns; out-of-range values are clamped.
+#[no_mangle]
+pub extern "C" fn set_chance_blend(blend: f64) {
+    config().chance_blend = if blend.is_nan() { 0.0 } else { blend.clamp(0.0, 1.0) };
+    log_set("chance_blend", config().chance_blend);
+}
+
+#[no_mangle]
+pub extern "C" fn get_chance_blend() -> f64 {
+    config().chance_blend
+}
+
+/// Accepted range of `set_spawn_risk`
+pub const SPAWN_RISK_MIN: f64 = 0.1;
+pub const SPAWN_RISK_MAX: f64 = 10.0;
+
+/// Spawn risk weighting: chance nodes weigh each spawn's probability p as
+/// p^(1/risk), renormalized. 1.0 (default) is the true expectation; above
+/// it the rare 4 counts for more (at 2.0 the standard 90/10 odds weigh as
+/// 75/25), steering toward moves that cope with it; below it the common 2
+/// dominates. Games still spawn with the true odds. Clamped to
+/// `SPAWN_RISK_MIN`–`SPAWN_RISK_MAX`; NaN resets to 1.
+#[no_mangle]
+pub extern "C" fn set_spawn_risk(risk: f64) {
+    config().spawn_risk = if risk.is_nan() { 1.0 } else { risk.clamp(SPAWN_RISK_MIN, SPAWN_RISK_MAX) };
+    log_set("spawn_risk", config().spawn_risk);
+}
+
+#[no_mangle]
+pub extern "C" fn get_spawn_risk() -> f64 {
+    config().spawn_risk
+}
+
+/// One dial from patient to greedy: root moves score
+/// (1 − a)·lookahead + a·merge score. 0.0 (default) is the pure positional
+/// search; 1.0 just takes the biggest immediate merge. Lookahead values run
+/// to ~10⁶ against merge scores of 10²–10⁴, so the merge score only starts
+/// to decide moves close to 1. Out-of-range values are clamped.
+#[no_mangle]
+pub extern "C" fn set_aggression(aggression: f64) {
+    config().aggression = if aggression.is_nan() { 0.0 } else { aggression.clamp(0.0, 1.0) };
+    log_set("aggression", config().aggression);
+}
+
+#[no_mangle]
+pub extern "C" fn get_aggression() -> f64 {
+    config().aggression
+}
+
+/// Root move objective: given a legal move's expectimax value (after the
+/// variance penalty, if on), its merge score and the number of empty cells
+/// it leaves before the spawn, returns the score the move is ranked by.
+pub type MoveObjective = extern "C" fn(value: f64, merge_score: f64, empty_cells: u32) -> f64;
+
+/// C ABI: install (or with null, remove) a root move objective, replacing
+/// the built-in root score (the value, plus the merge score under merge
+/// accumulation, then the aggression blend), e.g. to rank by empty cells
+/// first and value second. Later root adjustments (cascade bias, look-back
+/// penalty, corner safe and unstick modes, exploration, the survival
+/// guardrail) still apply to its result. Called once per legal root move on
+/// the searching thread; it must be thread-safe if searches run in
+/// parallel, and must not call back into the engine.
+#[no_mangle]
+pub extern "C" fn set_move_objective(cb: Option<MoveObjective>) {
+    config().move_objective = cb;
+    log_set("move_objective", if cb.is_some() { "custom" } else { "built-in" });
+}
+
+// Score rescaling modes for `set_score_normalization`
+pub const SCORES_RAW:     u32 = 0;
+pub const SCORES_SOFTMAX: u32 = 1;
+pub const SCORES_MINMAX:  u32 = 2;
+
+/// Rescale the scores written by `search_ranked_moves` and friends.
+/// `SCORES_RAW` (default) leaves expectimax values as they are;
+/// `SCORES_SOFTMAX` turns them into probabilities summing to 1, with the
+/// temperature at 1% of the best score, so a move 1% behind gets 1/e of
+/// the best move's weight; `SCORES_MINMAX` maps the best to 1 and the worst
+/// to 0 (a lone move gets 1). Order is unchanged in every mode, and
+/// `get_move_margin` and the decision log stay raw. Unknown modes are ignored.
+#[no_mangle]
+pub extern "C" fn set_score_normalization(mode: u32) {
+    if mode <= SCORES_MINMAX {
+        config().score_norm = mode;
+        log_set("score_normalization", mode);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_score_normalization() -> u32 {
+    config().score_norm
+}
+
+/// Prefer root moves that leave a halving chain from the cornered max tile
+/// ending in a mergeable pair (at least 4 tiles), unless another move scores
+/// more than 1% better. Guards built snakes against greedy local gains.
+#[no_mangle]
+pub extern "C" fn set_cascade_bias(enabled: bool) {
+    config().cascade_bias = enabled;
+    log_set("cascade_bias", enabled);
+}
+
+#[no_mangle]
+pub extern "C" fn get_cascade_bias() -> bool {
+    config().cascade_bias
+}
+
+/// Survival guardrail: when the top-ranked move (after every other root
+/// adjustment, exploration included) lets a spawn end the game on the
+/// spot, play the best-ranked move that leaves a legal move after every
+/// spawn instead, if there is one. Expectimax averaging can otherwise take
+/// a small chance of instant death for a slightly better mean. The promoted
+/// move goes first with its own score. Default off.
+#[no_mangle]
+pub extern "C" fn set_survival_guard(enabled: bool) {
+    config().survival_guard = enabled;
+    log_set("survival_guard", enabled);
+}
+
+#[no_mangle]
+pub extern "C" fn get_survival_guard() -> bool {
+    config().survival_guard
+}
+
+/// Let the chain term (`EvalWeights::chain`) follow any descent of at least
+/// a halving per step instead of exact halving only. Default off (strict).
+#[no_mangle]
+pub extern "C" fn set_chain_relaxed(enabled: bool) {
+    config().chain_relaxed = enabled;
+    log_set("chain_relaxed", enabled);
+}
+
+#[no_mangle]
+pub extern "C" fn get_chain_relaxed() -> bool {
+    config().chain_relaxed
+}
+
+/// Fix the snake term to one orientation (0–7, numbered as for
+/// `snake_contribution_map`) instead of the best of all 8, once a game has
+/// committed to a corner: cheaper, and the engine stops drifting towards
+/// whichever snake currently fits best. Returns false for an index ≥ 8,
+/// leaving the setting unchanged.
+#[no_mangle]
+pub extern "C" fn pin_orientation(index: u8) -> bool {
+    clear_error();
+    if index >= 8 {
+        set_error(ErrorCode::InvalidArgument);
+        return false;
+    }
+    config().snake_pin = Some(index);
+    log_set("snake_pin", index);
+    true
+}
+
+/// Return the snake term to the best of the 8 orientations
+#[no_mangle]
+pub extern "C" fn unpin_orientation() {
+    config().snake_pin = None;
+    log_set("snake_pin", "none");
+}
+
+/// Pinned snake orientation, or 0xFF when none is pinned
+#[no_mangle]
+pub extern "C" fn get_pinned_orientation() -> u8 {
+    config().snake_pin.unwrap_or(0xFF)
+}
+
+/// Snake blend: when the runner-up snake orientation scores within
+/// `margin` × the best, blend it in instead of taking the best alone, so
+/// the snake term does not jump when the leading orientation changes hands.
+/// 0 (default) is the hard max; clamped to 0–1, NaN counts as 0. No effect
+/// while an orientation is pinned.
+#[no_mangle]
+pub extern "C" fn set_snake_blend(margin: f64) {
+    config().snake_blend = if margin > 0.0 { margin.min(1.0) } else { 0.0 };
+    log_set("snake_blend", config().snake_blend);
+}
+
+#[no_mangle]
+pub extern "C" fn get_snake_blend() -> f64 {
+    config().snake_blend
+}
+
+/// "Don't care" cell in a target pattern
+pub const PATTERN_ANY: u16 = u16::MAX;
+const PATTERN_ANY_RANK: u8 = 0xFF;
+
+/// Target pattern: 16 cells (row-major) that the evaluation steers the
+/// board toward, each a tile value, 0 for a cell that must stay empty, or
+/// `PATTERN_ANY`. Every cell that breaks it costs `EvalWeights::pattern`
+/// (0 by default, so set it too) × rank² of the tile that should be there,
+/// or of the one that should not. Null clears the pattern. Returns false
+/// (`InvalidArgument`) for any other cell value, keeping the current one.
+#[no_mangle]
+pub extern "C" fn set_target_pattern(template_ptr: *const u16) -> bool {
+    clear_error();
+    if template_ptr.is_null() {
+        config().target_pattern = None;
+        log_set("target_pattern", "none");
+        return true;
+    }
+    let cells = unsafe { std::slice::from_raw_parts(template_ptr, 16) };
+    if cells.iter().any(|&v| v != PATTERN_ANY && (v == 1 || !(v == 0 || v.is_power_of_two()))) {
+        set_error(ErrorCode::InvalidArgument);
+        return false;
+    }
+    let pattern: [u8; 16] = std::array::from_fn(|i| match cells[i] {
+        PATTERN_ANY => PATTERN_ANY_RANK,
+        v => tile_rank(v) as u8,
+    });
+    config().target_pattern = Some(pattern);
+    log_set("target_pattern", format_args!("{:?}", cells));
+    true
+}
+
+/// Copy the target pattern into `out` (16 cells, `PATTERN_ANY` for don't
+/// care). Returns false, writing nothing, when no pattern is set.
+#[no_mangle]
+pub extern "C" fn get_target_pattern(out: *mut u16) -> bool {
+    clear_error();
+    if !non_null(out) { return false; }
+    let Some(pattern) = config().target_pattern else { return false };
+    let cells = pattern.map(|r| match r {
+        PATTERN_ANY_RANK => PATTERN_ANY,
+        0 => 0,
+        r => 1 << r,
+    });
+    unsafe { std::slice::from_raw_parts_mut(out, 16) }.copy_from_slice(&cells);
+    true
+}
+
+/// Merge accumulation: add each move's merge score to its value at every
+/// move node, root included, so the search maximises game score plus the
+/// final heuristic instead of the heuristic alone. Off by default.
+#[no_mangle]
+pub extern "C" fn set_merge_accumulation(enabled: bool) {
+    config().merge_accumulate = enabled;
+    log_set("merge_accumulate", enabled);
+}
+
+#[no_mangle]
+pub extern "C" fn get_merge_accumulation() -> bool {
+    config().merge_accumulate
+}
+
+/// Corner safe mode: when the max tile is in a corner, play the best move
+/// that keeps it there if one scores within `eps` × |best score| of the
+/// best, even where a higher-scoring move would dislodge it. The promoted
+/// move goes first in the ranking with its own (lower) score. Clamped to
+/// 0–1; 0 (default) turns it off.
+#[no_mangle]
+pub extern "C" fn set_corner_safe(eps: f64) {
+    config().corner_safe_eps = if eps.is_nan() { 0.0 } else { eps.clamp(0.0, 1.0) };
+    log_set("corner_safe", config().corner_safe_eps);
+}
+
+#[no_mangle]
+pub extern "C" fn get_corner_safe() -> f64 {
+    config().corner_safe_eps
+}
+
+/// Unstick mode: with fewer than `empty_cells` empty cells, if no move
+/// scores above the static evaluation of the current board, play the move
+/// that leaves the most mobility (no spawn that ends the game first, then
+/// empty cells plus adjacent equal pairs) rather than the top-scoring one, which in such
+/// positions is often a trap. The promoted move goes first with its own
+/// score. Clamped to 0–16; 0 (default) turns it off.
+#[no_mangle]
+pub extern "C" fn set_unstick_threshold(empty_cells: u32) {
+    config().unstick_empty = empty_cells.min(16);
+    log_set("unstick_empty", empty_cells.min(16));
+}
+
+#[no_mangle]
+pub extern "C" fn get_unstick_threshold() -> u32 {
+    config().unstick_empty
+}
+
+/// Mirror averaging: with more than `empty_cells` empty cells (the opening),
+/// each root move is searched on all 8 reflections and rotations of the
+/// board and the results averaged, so near-symmetric positions get
+/// symmetric scores instead of ones split by rounding and tie-breaks. Costs
+/// 8 searches per move and disables root cutoffs; skipped on boards smaller
+/// than 4×4. Clamped to 0–16; 0 (default) turns it off.
+#[no_mangle]
+pub extern "C" fn set_mirror_averaging(empty_cells: u32) {
+    config().mirror_empty = empty_cells.min(16);
+    log_set("mirror_empty", empty_cells.min(16));
+}
+
+#[no_mangle]
+pub extern "C" fn get_mirror_averaging() -> u32 {
+    config().mirror_empty
+}
+
+/// Exploration: instead of the top-scoring move, play one drawn from a
+/// softmax over the ranked scores, e.g. for varied self-play training
+/// games. `temperature` is relative to the best score's magnitude (at 0.01
+/// a move scoring 1% below the best gets e⁻¹ of its weight); the drawn
+/// move goes first in the ranking with its own score. 0 (default) is the
+/// plain argmax; negative or NaN values count as 0.
+#[no_mangle]
+pub extern "C" fn set_exploration_temperature(temperature: f64) {
+    config().explore_temp = if temperature > 0.0 { temperature } else { 0.0 };
+    log_set("explore_temp", config().explore_temp);
+}
+
+#[no_mangle]
+pub extern "C" fn get_exploration_temperature() -> f64 {
+    config().explore_temp
+}
+
+/// Restart the exploration draws from `seed`, making sampled games
+/// reproducible (the sequence is shared by every searching thread).
+#[no_mangle]
+pub extern "C" fn set_exploration_seed(seed: u64) {
+    *explore_rng() = Rng(seed);
+    log_set("explore_seed", seed);
+}
+
+/// Variance penalty: each root move's score loses `weight` × the standard
+/// deviation of the values of its spawns (one ply ahead, probability
+/// weighted), so a move with a strong average but a disastrous spawn branch,
+/// typically a 4 landing in the wrong cell, ranks below a steadier one.
+/// 0 (default) is plain expectimax; negative or NaN values count as 0.
+#[no_mangle]
+pub extern "C" fn set_variance_penalty(weight: f64) {
+    config().variance_penalty = if weight > 0.0 { weight } else { 0.0 };
+    log_set("variance_penalty", config().variance_penalty);
+}
+
+#[no_mangle]
+pub extern "C" fn get_variance_penalty() -> f64 {
+    config().variance_penalty
+}
+
+/// Look-back penalty against oscillation: in `search_ranked_moves_after`
+/// and self-play, the move that reverses the previous one (left after
+/// right, up after down) loses `penalty` × |best score|, so it only wins
+/// when it is clearly better, not on a near-tie. 0.001 is usually enough.
+/// 0 (default) is off; negative or NaN values count as 0.
+#[no_mangle]
+pub extern "C" fn set_reverse_penalty(penalty: f64) {
+    config().reverse_penalty = if penalty > 0.0 { penalty } else { 0.0 };
+    log_set("reverse_penalty", config().reverse_penalty);
+}
+
+#[no_mangle]
+pub extern "C" fn get_reverse_penalty() -> f64 {
+    config().reverse_penalty
+}
+
+/// Stop expanding a root move's chance node once even the best possible
+/// values for its remaining cells could not beat the best move found so far.
+/// The chosen move is unchanged, but cut moves report an upper bound on
+/// their value (still below the best), so lower ranks and the margin may
+/// differ from a full search. Only takes effect when leaf values are
+/// bounded: no aggression, cascade bias, chance blend, board-level terms or
+/// n-tuple network. Default off.
+#[no_mangle]
+pub extern "C" fn set_chance_cutoff(enabled: bool) {
+    config().chance_cutoff = enabled;
+    log_set("chance_cutoff", enabled);
+}
+
+#[no_mangle]
+pub extern "C" fn get_chance_cutoff() -> bool {
+    config().chance_cutoff
+}
+
+/// Fixed-point evaluation, for rankings that are bit-identical on every
+/// platform and compiler. Leaf values come from integer row tables built
+/// without `powf` and are rounded to 1/1024; chance nodes average them in
+/// integers with spawn odds and chance blend rounded to 1/65536. Scores keep
+/// their usual scale but may differ from the default f64 path by those
+/// roundings. Board-level terms and n-tuple networks are still computed in
+/// f64 and then rounded, so a term using `powf` (a snake power or scatter
+/// falloff other than the defaults) can in principle round differently
+/// across platforms. Disables chance cutoffs. Default off.
+#[no_mangle]
+pub extern "C" fn set_fixed_point(enabled: bool) {
+    config().fixed_point = enabled;
+    log_set("fixed_point", enabled);
+}
+
+#[no_mangle]
+pub extern "C" fn get_fixed_point() -> bool {
+    config().fixed_point
+}
+
+/// Load an n-tuple network (layout above) as the leaf evaluator, replacing
+/// the handcrafted heuristic. Null or zero length unloads it. Returns the
+/// number of tuples loaded, or 0 if the blob is malformed (the previous
+/// network, if any, stays active).
+#[no_mangle]
+pub extern "C" fn load_ntuple(bytes: *const u8, len: usize) -> u32 {
+    clear_error();
+    if bytes.is_null() || len == 0 {
+        config().ntuple = None;
+        log_set("ntuple", "unloaded");
+        return 0;
+    }
+    let blob = unsafe { std::slice::from_raw_parts(bytes, len) };
+    match NTuple::parse(blob) {
+        Some(net) => {
+            let n = net.tuples.len() as u32;
+            config().ntuple = Some(Arc::new(net));
+            log_set("ntuple", format_args!("{n} tuples"));
+            n
+        }
+        None => {
+            log(LOG_WARN, || format!("n-tuple blob of {len} bytes is malformed; keeping the current evaluator"));
+            set_error(ErrorCode::MalformedData);
+            0
+        }
+    }
+}
+
+/// Cap on search depth (1–32, default 16). Requests above it return 0 with
+/// `last_error` set to `InvalidDepth`; adaptive deepening never exceeds it.
+#[no_mangle]
+pub extern "C" fn set_max_depth(depth: u32) {
+    config().max_depth = depth.clamp(1, MAX_DEPTH_LIMIT);
+    log_set("max_depth", config().max_depth);
+}
+
+#[no_mangle]
+pub extern "C" fn get_max_depth() -> u32 {
+    config().max_depth
+}
+
+/// Switch the transposition table to a direct-mapped array of 2^`bits`
+/// 32-byte entries (clamped to 10–26; 2^20 = 32 MiB). Lookups touch one
+/// cache line and clears are a flat memset, but colliding positions evict
+/// each other. 0 (default) restores the exact HashMap table.
+#[no_mangle]
+pub extern "C" fn set_tt_array(bits: u32) {
+    config().tt_array_bits = if bits == 0 { 0 } else { bits.clamp(10, 26) };
+    log_set("tt_array", config().tt_array_bits);
+}
+
+#[no_mangle]
+pub extern "C" fn get_tt_array() -> u32 {
+    config().tt_array_bits
+}
+
+/// Reuse transposition entries searched to any depth in the same band of
+/// `width` depths (depths 4–5 share a band at width 2) rather than only
+/// those searched at least as deep. Raises the hit rate on deep searches,
+/// which revisit positions one ply apart, at the price of taking some
+/// values from a shallower lookahead than asked for: scores drift a little
+/// and a close ranking can flip. Clamped to 1–8; 1 (default) is exact.
+#[no_mangle]
+pub extern "C" fn set_tt_depth_band(width: u32) {
+    config().tt_depth_band = width.clamp(1, 8);
+    log_set("tt_depth_band", width.clamp(1, 8));
+}
+
+#[no_mangle]
+pub extern "C" fn get_tt_depth_band() -> u32 {
+    config().tt_depth_band
+}
+
+/// Seed every randomized decision inside the search; each top-level search
+/// restarts from this seed, so identical seeds reproduce identical searches.
+/// Currently this randomizes tie-breaks among equally-crowded cells when
+/// `set_max_chance_cells` caps chance nodes. Without a seed (the default)
+/// the search is fully deterministic.
+#[no_mangle]
+pub extern "C" fn set_search_seed(seed: u64) {
+    config().search_seed = Some(seed);
+    log_set("search_seed", seed);
+}
+
+/// Return to fully deterministic search
+#[no_mangle]
+pub extern "C" fn clear_search_seed() {
+    config().search_seed = None;
+    log_set("search_seed", "none");
+}
+
+/// Keep the last `capacity` top-level searches (any thread) in the decision
+/// log; 0 (default) turns logging off. Either way the log is emptied.
+#[no_mangle]
+pub extern "C" fn set_decision_log(capacity: u32) {
+    config().decision_log = capacity;
+    decision_log().clear();
+    log_set("decision_log", capacity);
+}
+
+#[no_mangle]
+pub extern "C" fn get_decision_log_capacity() -> u32 {
+    config().decision_log
+}
+
+/// One number summarising every setting in this section (weights, spawn
+/// distribution, depth and chance limits, win target, masks, n-tuple
+/// network, …), to confirm two runs used identical configuration. Any change alters it;
+/// restoring the old values restores it. Stable across builds.
+#[no_mangle]
+pub extern "C" fn config_fingerprint() -> u64 {
+    config().fingerprint()
+}
+
+// ── C ABI: search ──
+
+/// C ABI: static name of a direction ("up", "down", "left", "right"), or
+/// null for an out-of-range value. The string must not be freed.
+#[no_mangle]
+pub extern "C" fn direction_name(dir: u8) -> *const c_char {
+    clear_error();
+    match Direction::from_u8(dir) {
+        Some(d) => d.name().as_ptr(),
+        None => {
+            set_error(ErrorCode::InvalidArgument);
+            std::ptr::null()
+        }
+    }
+}
+
+/// C ABI: how many of the four moves change the board (0 = game over),
+/// without searching or building the moved boards
+#[no_mangle]
+pub extern "C" fn legal_move_count(board_ptr: *const u16) -> u32 {
+    clear_error();
+    load_config();
+    board_arg(board_ptr).map_or(0, count_legal)
+}
+
+/// Marks an illegal direction in `empty_delta` output
+pub const EMPTY_DELTA_ILLEGAL: i8 = i8::MIN;
+
+/// C ABI: for each direction (indexed by its code), how many empty cells
+/// the move frees, before the spawn: one per merge, so 0 for a pure slide
+/// and never negative. Illegal moves get `EMPTY_DELTA_ILLEGAL`. A cheap
+/// mobility signal, no search involved. `out` must hold 4 entries; nothing
+/// is written for a bad argument.
+#[no_mangle]
+pub extern "C" fn empty_delta(board_ptr: *const u16, out: *mut i8) {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return };
+    if !non_null(out) { return; }
+    load_config();
+    let out = unsafe { std::slice::from_raw_parts_mut(out, 4) };
+    let before = count_empty(b) as i8;
+    for d in Direction::ALL {
+        let (nb, _, moved) = do_move(b, d);
+        out[d as usize] = if moved { count_empty(nb) as i8 - before } else { EMPTY_DELTA_ILLEGAL };
+    }
+}
+
+/// C ABI: greedy move, with no lookahead: the direction code whose moved
+/// board scores best by `evaluate` plus the move's merge score (ties go to
+/// the lower code). A baseline, and a fallback when there is no time to
+/// search. -1 when no move is legal or the board is invalid. This thread's
+/// TT, stats and margin from its last search are left as they were.
+#[no_mangle]
+pub extern "C" fn greedy_move(board_ptr: *const u16) -> i32 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return -1 };
+    load_search_config();
+    // `evaluate` counts its calls; keep the last search's counters
+    let stats = STATS.with(|s| *s.borrow());
+    let mut best: Option<(f64, Direction)> = None;
+    for d in Direction::ALL {
+        let (nb, ms, moved) = do_move(b, d);
+        if !moved { continue; }
+        let v = evaluate(nb) + ms;
+        if best.is_none_or(|(bv, _)| v > bv) { best = Some((v, d)); }
+    }
+    STATS.with(|s| *s.borrow_mut() = stats);
+    best.map_or(-1, |(_, d)| d as i32)
+}
+
+/// C ABI: `POS_*` category bits describing a board: its phase, whether the
+/// max tile holds an anchor corner (`set_preferred_corner`), whether the
+/// big tiles follow a snake, and whether it is close to dying. 0 with
+/// `last_error` set for a bad board.
+#[no_mangle]
+pub extern "C" fn classify_position(board_ptr: *const u16) -> u32 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return 0 };
+    load_config();
+    classify(b, &config())
+}
+
+/// C ABI: upper bound on the largest tile this board's tiles could ever
+/// merge into: their sum rounded down to a power of two (capped at 32768,
+/// the largest tile a board holds). Spawns are not counted. 0 for an empty
+/// or bad board.
+#[no_mangle]
+pub extern "C" fn max_achievable_tile(board_ptr: *const u16) -> u16 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return 0 };
+    let sum: u32 = board_tiles(b).iter().map(|&t| t as u32).sum();
+    if sum == 0 { 0 } else { (1u32 << sum.ilog2()).min(1 << 15) as u16 }
+}
+
+/// C ABI: disorder of a board from 0 (every row and column monotone, as in
+/// a clean snake) to 1 (each line climbs as much as it falls): the part of
+/// the rank change along lines that runs against the line's main
+/// direction. Rises as the engine loses structural control. NaN for a bad
+/// board.
+#[no_mangle]
+pub extern "C" fn board_entropy(board_ptr: *const u16) -> f64 {
+    clear_error();
+    board_arg(board_ptr).map_or(f64::NAN, disorder)
+}
+
+/// C ABI: the key the engine files a board under in its transposition
+/// table. There is no Zobrist table: the packed board (4 bits per cell,
+/// row-major from bit 0, log2 values) is itself a collision-free key. On a
+/// smaller board shape the wall cells are left out (read as empty), so a
+/// key depends on the tiles alone. 0 with `last_error` set for a bad board.
+#[no_mangle]
+pub extern "C" fn board_key(board_ptr: *const u16) -> u64 {
+    clear_error();
+    board_arg(board_ptr).map_or(0, |b| b & !walls())
+}
+
+/// C ABI: update a `board_key` in O(1) after cell `pos` (0–15, row-major)
+/// changes from `old_val` to `new_val` (raw tile values, 0 = empty). Equal
+/// to recomputing `board_key` on the edited board. Returns `key` unchanged,
+/// with `last_error` set, for `pos` ≥ 16 or an `old_val` the key does not
+/// hold there (`InvalidArgument`), and for a value that is not a tile or a
+/// tile on a wall cell of a smaller shape (`MalformedBoard`).
+#[no_mangle]
+pub extern "C" fn board_key_update(key: u64, pos: u32, old_val: u16, new_val: u16) -> u64 {
+    clear_error();
+    if pos >= 16 {
+        set_error(ErrorCode::InvalidArgument);
+        return key;
+    }
+    let tile = |v: u16| v == 0 || (v >= 2 && v.is_power_of_two());
+    let off_board = (walls() >> (pos * 4)) & 0xF != 0;
+    if !tile(old_val) || !tile(new_val) || (off_board && (old_val | new_val) != 0) {
+        set_error(ErrorCode::MalformedBoard);
+        return key;
+    }
+    if (key >> (pos * 4)) & 0xF != tile_rank(old_val) {
+        set_error(ErrorCode::InvalidArgument);
+        return key;
+    }
+    key ^ ((tile_rank(old_val) ^ tile_rank(new_val)) << (pos * 4))
+}
+
+/// C ABI: store a board in 8 bytes: 4 bits per cell, row-major from bit 0,
+/// each the tile's log2 (0 = empty), so the largest storable tile is 32768
+/// (2^15), the largest a u16 cell can hold anyway. Lossless, and the same
+/// value as `board_key`. A malformed board returns 0 with `last_error` set;
+/// 0 is also the empty board, so check it when packing untrusted input.
+#[no_mangle]
+pub extern "C" fn board_pack(board_ptr: *const u16) -> u64 {
+    board_key(board_ptr)
+}
+
+/// C ABI: expand a `board_pack` value back into 16 tile values at
+/// `out_board_ptr`. Every u64 is a valid packed board.
+#[no_mangle]
+pub extern "C" fn board_unpack(packed: u64, out_board_ptr: *mut u16) {
+    clear_error();
+    if !non_null(out_board_ptr) { return; }
+    write_board(packed, out_board_ptr);
+}
+
+/// C ABI: leaf evaluation of a board (16 u16s) under the current config;
+/// NaN if the board cannot be read
+#[no_mangle]
+pub extern "C" fn evaluate_board(board_ptr: *const u16) -> f64 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return f64::NAN };
+    load_config();
+    evaluate(b)
+}
+
+/// C ABI: `evaluate_board` over `count` contiguous boards (16 u16s each),
+/// writing one score per board to `out_scores`, with a single config load
+/// for the whole batch. A malformed board scores NaN and sets `last_error`
+/// without stopping the batch. Returns the number of scores written.
+#[no_mangle]
+pub extern "C" fn evaluate_batch(boards_ptr: *const u16, count: u32, out_scores: *mut f64) -> u32 {
+    clear_error();
+    if count == 0 { return 0; }
+    if !(non_null(boards_ptr) && non_null(out_scores)) { return 0; }
+    load_config();
+    let out = unsafe { std::slice::from_raw_parts_mut(out_scores, count as usize) };
+    for (i, score) in out.iter_mut().enumerate() {
+        *score = board_arg(unsafe { boards_ptr.add(i * 16) }).map_or(f64::NAN, evaluate);
+    }
+    count
+}
+
+/// C ABI: per-term breakdown of a board's handcrafted evaluation, in
+/// `TERM_*` bit order (empty, merges, mono, sum, corner, trapped, scatter,
+/// snake, chain, grip, anchor, pattern, breathing), under whichever profile `evaluate_board`
+/// would use and with the term mask applied. Penalties come out negative.
+/// The n-tuple network, if loaded, is ignored. Writes at most `len` entries;
+/// returns the number written (up to `NUM_TERMS`).
+#[no_mangle]
+pub extern "C" fn evaluate_components(board_ptr: *const u16, out_terms: *mut f64, len: u32) -> u32 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return 0 };
+    if !non_null(out_terms) { return 0; }
+    load_config();
+    let terms = CFG.with(|c| {
+        let c = c.borrow();
+        eval_components(b, active_weights(b, &c), &c)
+    });
+    write_terms(&terms, out_terms, len)
+}
+
+/// C ABI: `evaluate_components` of board A minus that of board B, term by
+/// term, e.g. to see which term made the engine prefer one move's result
+/// over another's. Same ordering and return value.
+#[no_mangle]
+pub extern "C" fn evaluate_diff(
+    board_a_ptr: *const u16,
+    board_b_ptr: *const u16,
+    out_terms: *mut f64,
+    len: u32,
+) -> u32 {
+    clear_error();
+    let (Some(a), Some(b)) = (board_arg(board_a_ptr), board_arg(board_b_ptr)) else { return 0 };
+    if !non_null(out_terms) { return 0; }
+    load_config();
+    let terms = CFG.with(|c| {
+        let c = c.borrow();
+        let tb = eval_components(b, active_weights(b, &c), &c);
+        let mut ta = eval_components(a, active_weights(a, &c), &c);
+        for (x, y) in ta.iter_mut().zip(tb) { *x -= y; }
+        ta
+    });
+    write_terms(&terms, out_terms, len)
+}
+
+fn write_terms(terms: &[f64], out_terms: *mut f64, len: u32) -> u32 {
+    if out_terms.is_null() { return 0; }
+    let n = terms.len().min(len as usize);
+    unsafe { std::slice::from_raw_parts_mut(out_terms, n) }.copy_from_slice(&terms[..n]);
+    n as u32
+}
+
+/// C ABI: per-cell breakdown of the snake term for the orientation it
+/// uses: cell i (row-major) gets tile value^`snake_power` × snake weight,
+/// under the profile `evaluate_board` would use, before scaling by
+/// `EvalWeights::snake`, so the 16 entries sum to the snake term.
+/// Writes at most `len` entries (none if `out` is null); returns the
+/// orientation used, the pinned one if any (0–7: bit 0 mirrors columns, bit 1 mirrors rows, bit 2
+/// transposes the row-wise snake headed at the top-left corner).
+#[no_mangle]
+pub extern "C" fn snake_contribution_map(board_ptr: *const u16, out: *mut f64, len: u32) -> u32 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return 0 };
+    load_config();
+    let (pin, power) = {
+        let c = config();
+        (c.snake_pin, active_weights(b, &c).snake_power)
+    };
+    let (o, _) = best_snake(b, pin, power);
+    let weights = &TBL_SNAKE[o];
+    let mut cells = [0.0; 16];
+    for ((c, &t), &w) in cells.iter_mut().zip(&snake_tiles(b, power)).zip(weights) {
+        *c = t * w;
+    }
+    write_terms(&cells, out, len);
+    o as u32
+}
+
+/// C ABI: merge hotspots: cell i (row-major) gets how many of its
+/// orthogonal neighbours hold the same tile (0–4), 0 for empty cells. Only
+/// direct neighbours count, not tiles that would meet across a gap.
+/// Writes at most `len` entries (none if `out` is null); returns how many.
+#[no_mangle]
+pub extern "C" fn merge_readiness(board_ptr: *const u16, out: *mut u8, len: u32) -> u32 {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return 0 };
+    if out.is_null() { return 0; }
+    let b = b & !walls();
+    let mut counts = [0u8; 16];
+    for r in 0..4 {
+        for c in 0..4 {
+            let v = cell(b, r, c);
+            if v == 0 { continue; }
+            // Each equal pair counts once for both of its cells
+            if c < 3 && cell(b, r, c + 1) == v {
+                counts[r * 4 + c] += 1;
+                counts[r * 4 + c + 1] += 1;
+            }
+            if r < 3 && cell(b, r + 1, c) == v {
+                counts[r * 4 + c] += 1;
+                counts[r * 4 + c + 4] += 1;
+            }
+        }
+    }
+    let n = counts.len().min(len as usize);
+    unsafe { std::slice::from_raw_parts_mut(out, n) }.copy_from_slice(&counts[..n]);
+    n as u32
+}
+
+/// C ABI: evaluate a board under both the building (`set_eval_weights`) and
+/// survival (`set_survival_weights`) profiles, sharing the line features.
+/// Ignores the endgame switch and any n-tuple network, so `out_build` equals
+/// `evaluate_board` whenever neither is active. Null outputs are skipped.
+#[no_mangle]
+pub extern "C" fn evaluate_dual(board_ptr: *const u16, out_build: *mut f64, out_survive: *mut f64) {
+    clear_error();
+    let Some(b) = board_arg(board_ptr) else { return };
+    load_config();
+    let lines = line_features(b);
+    CFG.with(|c| {
+        let c = c.borrow();
+        let profile = |w: &EvalWeights| {
+            let mut score = 0.0;
+            for f in &lines { score += row_heur(f, w); }
+            score + board_terms(b, w, &c)
+        };
+        if !out_build.is_null() { unsafe { *out_build = profile(&c.weights); } }
+        if !out_survive.is_null() { unsafe { *out_survive = profile(&c.survival_weights); } }
+    });
+}
+
+/// C ABI: given board (16 u16s) and depth, write ranked moves.
+/// Returns number of valid moves. Directions: 0=up, 1=down, 2=left, 3=right.
+/// `depth` counts reply moves after the root move: 0 = static evaluation of
+/// each move's result, ≥1 = expectimax at max(depth, distinct tiles − 2).
+/// Returns 0 with `last_error` set, without searching, if `depth` exceeds
+/// the configured cap (`InvalidDepth`, see `set_max_depth`) or an argument
+/// is null or malformed. A locked board (no legal move) returns 0
+/// with `last_error` clear and both buffers untouched. Scores are never NaN.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves(
+    board_ptr: *const u16,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let moves = search_root(board, depth);
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: `search_ranked_moves` for a position at `move_number` moves into
+/// the game, letting phase-dependent settings (`set_survival_from_move`)
+/// apply. Same depth rules and return values; with those settings off it is
+/// identical to `search_ranked_moves`.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_with_context(
+    board_ptr: *const u16,
+    depth: u32,
+    move_number: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    MOVE_NUMBER.with(|m| m.set(Some(move_number)));
+    let moves = search_root(board, depth);
+    MOVE_NUMBER.with(|m| m.set(None));
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: `search_ranked_moves` for a position reached by playing
+/// `last_move` (a direction, or 0xFF for none), which the look-back penalty
+/// (`set_reverse_penalty`) uses to discourage undoing it. Same depth rules
+/// and return values; 0 with `InvalidArgument` for any other `last_move`.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_after(
+    board_ptr: *const u16,
+    depth: u32,
+    last_move: u8,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let last = Direction::from_u8(last_move);
+    if last.is_none() && last_move != 0xFF {
+        set_error(ErrorCode::InvalidArgument);
+        return 0;
+    }
+    LAST_MOVE.with(|m| m.set(last));
+    let moves = search_root(board, depth);
+    LAST_MOVE.with(|m| m.set(None));
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: `search_ranked_moves` against an adversarial spawner: every
+/// chance node takes its worst spawn over all empty cells and tile values
+/// instead of the average, so each score is what the move guarantees if
+/// the game always spawns the worst tile in the worst place. The safest,
+/// most pessimistic play. Whatever the configuration says, all empty cells
+/// are considered and the selector and chance blend are ignored. Lines are
+/// still cut off where the expectimax would find them too improbable, to
+/// bound the cost. Same depth rules and return values.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_adversarial(
+    board_ptr: *const u16,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    ADVERSARIAL.with(|a| a.set(true));
+    let moves = search_root(board, depth);
+    ADVERSARIAL.with(|a| a.set(false));
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: `search_ranked_moves` with a time limit: deepens one depth at a
+/// time up to `depth` and stops once `time_ms` milliseconds have passed,
+/// writing the ranking of the deepest search that completed in time. An
+/// interrupted search contributes nothing, so scores never mix depths; the
+/// depth reached is in `get_search_stats` (which, like `get_move_margin`,
+/// then describes that search). The static ranking (depth 0) is always
+/// computed, even with `time_ms` 0. Same depth rules and return values.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_timed(
+    board_ptr: *const u16,
+    depth: u32,
+    time_ms: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let moves = search_timed(board, depth, Duration::from_millis(time_ms as u64));
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: `search_ranked_moves` that also splits each ranked score into a
+/// positional part and a points part. `positional_out[i]` (room for 4,
+/// aligned with `scores_out`/`dirs_out`; null skips it) is move i's score
+/// minus the share of the move's own merge score that the ranking adds,
+/// which is nonzero only with merge accumulation or aggression on (and
+/// taken as 0 under a move objective, which may use it in any way); the
+/// points part is `scores_out[i] − positional_out[i]`. Merges deeper in the
+/// tree stay in the positional part. The split is of raw scores, so it only
+/// adds up under `SCORES_RAW`. Same depth rules and return values.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_split(
+    board_ptr: *const u16,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+    positional_out: *mut f64,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let moves = search_root(board, depth);
+    let n = write_ranking(&moves, scores_out, dirs_out);
+    if positional_out.is_null() { return n; }
+    CFG.with(|c| {
+        let c = c.borrow();
+        for (i, &(score, dir)) in moves.iter().take(n as usize).enumerate() {
+            let ms = do_move(board, dir).1;
+            unsafe { *positional_out.add(i) = score - merge_share(ms, &c); }
+        }
+    });
+    n
+}
+
+/// C ABI: `search_ranked_moves` that also writes the board after the best
+/// move, before any spawn, to `out_board_ptr` (16 u16s; null skips it), so
+/// a caller about to play the move need not slide the tiles itself. Nothing
+/// is written there when no move is legal. Same depth rules and returns.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_with_board(
+    board_ptr: *const u16,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+    out_board_ptr: *mut u16,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let moves = search_root(board, depth);
+    if let Some(&(_, dir)) = moves.first().filter(|_| !out_board_ptr.is_null()) {
+        write_board(do_move(board, dir).0, out_board_ptr);
+    }
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: rank moves by best-first search instead of fixed-depth
+/// expectimax, creating at most `node_budget` tree nodes (at least the root
+/// and its moves). Cost grows with the budget rather than exponentially with
+/// depth; the tree goes deep where values are close and stays shallow
+/// elsewhere. Output as for `search_ranked_moves`; returns the move count.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_best_first(
+    board_ptr: *const u16,
+    node_budget: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let moves = search_best_first(board, node_budget as usize);
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: `search_ranked_moves` for boards given as 16 log2 ranks
+/// (0 = empty, 1 = 2, …, 11 = 2048; values above 15 are clamped to 32768),
+/// as exposed by many RL environments. Same depth rules and return values;
+/// 0 with `MalformedBoard` for a tile on a wall cell of a smaller shape.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_log2(
+    board_ptr: *const u8,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg_log2(board_ptr) else { return 0 };
+    let moves = search_root(board, depth);
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: the engine's intended plan as concrete boards. Searches at `depth`
+/// and plays the best move `steps` times with no tile spawns, writing each
+/// resulting board (16 u16s per step) to `out_boards_ptr` and each direction
+/// to `out_dirs_ptr`; both must hold `steps` entries. Stops early when no
+/// move is legal. Returns the number of steps played, or 0 with
+/// `InvalidDepth` if `depth` exceeds the cap.
+#[no_mangle]
+pub extern "C" fn best_line(
+    board_ptr: *const u16,
+    depth: u32,
+    steps: u32,
+    out_boards_ptr: *mut u16,
+    out_dirs_ptr: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    if steps > 0 && !(non_null(out_boards_ptr) && non_null(out_dirs_ptr)) { return 0; }
+    play_line(board, depth, steps, |k, b, dir| {
+        write_board(b, unsafe { out_boards_ptr.add(k * 16) });
+        unsafe { *out_dirs_ptr.add(k) = dir as u8; }
+    })
+}
+
+/// Play the best move at `depth` up to `steps` times with no spawns,
+/// passing each step's index, resulting board and direction to `on_step`.
+/// Returns the number of steps played.
+fn play_line(mut board: BB, depth: u32, steps: u32, mut on_step: impl FnMut(usize, BB, Direction)) -> u32 {
+    let mut played = 0;
+    while played < steps {
+        let Some(&(_, dir)) = search_root(board, depth).first() else { break };
+        board = do_move(board, dir).0;
+        on_step(played as usize, board, dir);
+        played += 1;
+    }
+    played
+}
+
+/// Most lines `alternative_lines` returns: one per direction
+pub const MAX_LINES: u32 = 4;
+
+/// C ABI: the engine's top `k` (at most `MAX_LINES`) options side by side.
+/// Searches at `depth`, then for each of the `k` best root moves writes a
+/// line of `steps` directions to `out_dirs` (`k` × `steps`, line by line):
+/// that root move, then the best move after it as `best_line` plays them,
+/// with no spawns. A line that runs out of legal moves is padded with
+/// 0xFF. `out_scores` (`k` entries, null to skip) gets each root move's
+/// score. Returns the number of lines written, fewer than `k` when fewer
+/// moves are legal; 0 for `steps` = 0 (`InvalidArgument`) or a `depth`
+/// over the cap (`InvalidDepth`).
+#[no_mangle]
+pub extern "C" fn alternative_lines(
+    board_ptr: *const u16,
+    depth: u32,
+    k: u32,
+    steps: u32,
+    out_dirs: *mut u8,
+    out_scores: *mut f64,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    if !non_null(out_dirs) { return 0; }
+    if steps == 0 {
+        set_error(ErrorCode::InvalidArgument);
+        return 0;
+    }
+    let moves = search_root(board, depth);
+    let lines = moves.len().min(k.min(MAX_LINES) as usize);
+    let steps = steps as usize;
+    let dirs = unsafe { std::slice::from_raw_parts_mut(out_dirs, lines * steps) };
+    dirs.fill(0xFF);
+    for (line, &(_, dir)) in dirs.chunks_mut(steps).zip(&moves) {
+        line[0] = dir as u8;
+        play_line(do_move(board, dir).0, depth, steps as u32 - 1, |k, _, d| line[k + 1] = d as u8);
+    }
+    if !out_scores.is_null() {
+        let scores = unsafe { std::slice::from_raw_parts_mut(out_scores, lines) };
+        for (s, m) in scores.iter_mut().zip(&moves) { *s = m.0; }
+    }
+    lines as u32
+}
+
+pub const HINT_MAX_LEN: u32 = 128;  // bytes, NUL included, that every move_hint text fits in
+const HINT_REASONS: usize = 3;      // evaluation terms a hint names at most
+
+/// Phrase for evaluation term `term` (`TERM_*` bit order) rating `after`, the
+/// chosen move's result, above `other`, the runner-up's
+fn hint_reason(term: usize, after: BB, other: BB) -> String {
+    match term {
+        0 => match count_empty(after) as i32 - count_empty(other) as i32 {
+            1 => "opens a cell".to_string(),
+            n if n > 1 => format!("opens {n} cells"),
+            _ => "keeps room to move".to_string(),
+        },
+        1 => match best_pair(after) {
+            0 => "sets up merges".to_string(),
+            rank => format!("sets up {} merge", 1u32 << rank),
+        },
+        2 => "keeps lines ordered".to_string(),
+        3 => "combines tiles".to_string(),
+        4 => format!("keeps {} in corner", 1u32 << max_rank(after)),
+        5 => "frees a trapped tile".to_string(),
+        6 => "brings duplicates together".to_string(),
+        7 => "follows the snake".to_string(),
+        8 => "extends the merge chain".to_string(),
+        9 => "guards the corner tile".to_string(),
+        10 => "holds the anchor row".to_string(),
+        11 => "fits the target pattern".to_string(),
+        _ => "keeps the snake tail open".to_string(),
+    }
+}
+
+/// Rank of the highest pair of equal tiles side by side, 0 if none
+fn best_pair(b: BB) -> u8 {
+    let b = b & !walls();
+    let rank = |i: usize| ((b >> (i * 4)) & 0xF) as u8;
+    (0..16)
+        .filter(|&i| rank(i) != 0)
+        .filter(|&i| (i % 4 < 3 && rank(i + 1) == rank(i)) || (i < 12 && rank(i + 4) == rank(i)))
+        .map(rank)
+        .max()
+        .unwrap_or(0)
+}
+
+/// "dir: reason; reason": the searched best move and what favours it. The
+/// reasons are its merge score, if any, then the terms by which the static
+/// evaluation of its result most exceeds that of the runner-up's result.
+fn move_hint_text(board: BB, depth: u32) -> Option<String> {
+    let moves = search_root(board, depth);
+    let &(_, best) = moves.first()?;
+    let (after, ms, _) = do_move(board, best);
+    let mut reasons = Vec::new();
+    if ms > 0.0 { reasons.push(format!("merges for {ms}")); }
+    match moves.get(1) {
+        None => reasons.push("only legal move".to_string()),
+        Some(&(_, alt)) => {
+            let other = do_move(board, alt).0;
+            let diff = CFG.with(|c| {
+                let c = c.borrow();
+                let mut d = eval_components(after, active_weights(after, &c), &c);
+                let o = eval_components(other, active_weights(other, &c), &c);
+                for (x, y) in d.iter_mut().zip(o) { *x -= y; }
+                d
+            });
+            let mut terms: Vec<usize> = (0..diff.len()).filter(|&t| diff[t] > 0.0).collect();
+            terms.sort_by(|&a, &b| diff[b].total_cmp(&diff[a]));
+            reasons.extend(terms.iter().take(HINT_REASONS).map(|&t| hint_reason(t, after, other)));
+        }
+    }
+    if reasons.is_empty() { reasons.push("best on lookahead".to_string()); }
+    Some(format!("{}: {}", best.name().to_str().unwrap_or("?"), reasons.join("; ")))
+}
+
+/// C ABI: one-line rationale for the move the engine would play, for
+/// teaching and assist tools, e.g. "left: merges for 8; keeps 2048 in
+/// corner; opens 2 cells". Searches at `depth`; the reasons come from
+/// comparing the chosen move's result with the runner-up's term by term
+/// (`evaluate_diff`), so they explain the static evaluation, not the whole
+/// lookahead. Writes the text NUL-terminated to `out`, truncated to `cap`
+/// bytes (`HINT_MAX_LEN` always suffices), and returns its length without
+/// the NUL: 0 when no move is legal, an argument is bad, or `depth`
+/// exceeds the cap (`InvalidDepth`).
+#[no_mangle]
+pub extern "C" fn move_hint(board_ptr: *const u16, depth: u32, out: *mut c_char, cap: u32) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    if !non_null(out) { return 0; }
+    let text = move_hint_text(board, depth).unwrap_or_default();
+    if cap > 0 {
+        let n = text.len().min(cap as usize - 1);
+        let buf = unsafe { std::slice::from_raw_parts_mut(out as *mut u8, n + 1) };
+        buf[..n].copy_from_slice(&text.as_bytes()[..n]);
+        buf[n] = 0;
+    }
+    text.len() as u32
+}
+
+/// C ABI: fewest moves that produce a `target` tile (a power of two ≥ 4)
+/// when no tiles spawn, by breadth-first search over move sequences.
+/// Returns 0 if the board already holds it, -1 if not reachable within
+/// `max_moves` or if an argument is invalid. Each ply keeps at most
+/// `BFS_FRONTIER_LIMIT` distinct boards, so on wide trees a longer answer
+/// (or -1) is possible when the shortest line was cut.
+#[no_mangle]
+pub extern "C" fn moves_to_target(board_ptr: *const u16, target: u16, max_moves: u32) -> i32 {
+    clear_error();
+    if !target.is_power_of_two() || target < 4 {
+        set_error(ErrorCode::InvalidArgument);
+        return -1;
+    }
+    let Some(board) = board_arg(board_ptr) else { return -1 };
+    load_config();
+    let rank = target.trailing_zeros() as u8;
+    if max_rank(board) >= rank { return 0; }
+    let mut seen = HashSet::from([board]);
+    let mut frontier = vec![board];
+    for ply in 1..=max_moves {
+        let mut next = Vec::new();
+        for &b in &frontier {
+            for d in Direction::ALL {
+                let (nb, _, moved) = do_move(b, d);
+                if !moved || !seen.insert(nb) { continue; }
+                if max_rank(nb) >= rank { return ply as i32; }
+                if next.len() < BFS_FRONTIER_LIMIT { next.push(nb); }
+            }
+        }
+        if next.is_empty() { break; }
+        frontier = next;
+    }
+    -1
+}
+
+/// C ABI: mask (bit i = cell i, row-major) of the empty cells where a spawn
+/// would hurt most: each empty cell gets a forced spawn, drawn from the
+/// configured distribution, and the best-move value after it is searched at
+/// a fixed shallow depth. The `THREAT_CELLS` lowest are flagged, but only
+/// those worse than the safest cell, so a board where every spawn is
+/// equally harmless gets 0. This thread's TT, stats and margin from its
+/// last search are left as they were.
+#[no_mangle]
+pub extern "C" fn threatened_cells(board_ptr: *const u16, out_mask: *mut u16) {
+    clear_error();
+    if !non_null(out_mask) { return; }
+    let Some(board) = board_arg(board_ptr) else { return };
+    let mut values: Vec<(f64, usize)> = scratch_search(|| {
+        let spawn = CFG.with(|c| c.borrow().spawn);
+        (0..16)
+            .filter(|&i| (board >> (i * 4)) & 0xF == 0)
+            .map(|i| {
+                let v = spawn.outcomes()
+                    .map(|(rank, p)| p * score_move_node(board | ((rank as u64) << (i * 4)), THREAT_DEPTH, 1.0))
+                    .sum::<f64>();
+                (v, i)
+            })
+            .collect()
+    });
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // Mirror-image cells can differ by rounding alone; ignore such gaps
+    let safest = values.last().map_or(0.0, |v| v.0);
+    let cutoff = safest - safest.abs() * 1e-12;
+    let mut mask = 0u16;
+    for &(v, i) in values.iter().take(THREAT_CELLS) {
+        if v < cutoff { mask |= 1 << i; }
+    }
+    unsafe { *out_mask = mask; }
+}
+
+/// Deepest lookahead `game_outcome_estimate` accepts
+pub const OUTCOME_MAX_DEPTH: u32 = 3;
+
+/// Probability that `depth` more moves can be made from `b`, choosing each
+/// move to maximize it. `memo` caches afterstates by remaining depth.
+fn survival_odds(b: BB, depth: u32, spawn: &SpawnDist, memo: &mut HashMap<(BB, u32), f64>) -> f64 {
+    if depth == 0 { return 1.0; }
+    let mut best = 0.0f64;
+    for d in Direction::ALL {
+        let (nb, _, moved) = do_move(b, d);
+        if !moved { continue; }
+        best = best.max(afterstate_odds(nb, depth - 1, spawn, memo));
+        if best == 1.0 { break; }
+    }
+    best
+}
+
+/// `survival_odds` averaged over the spawns after a move
+fn afterstate_odds(after: BB, depth: u32, spawn: &SpawnDist, memo: &mut HashMap<(BB, u32), f64>) -> f64 {
+    if depth == 0 { return 1.0; }
+    if let Some(&v) = memo.get(&(after, depth)) { return v; }
+    let empty: Vec<u32> = (0..16).filter(|&i| (after >> (i * 4)) & 0xF == 0).collect();
+    let v = if empty.is_empty() {
+        survival_odds(after, depth, spawn, memo)
+    } else {
+        let total: f64 = empty.iter()
+            .map(|&i| {
+                spawn.outcomes()
+                    .map(|(rank, p)| p * survival_odds(after | ((rank as u64) << (i * 4)), depth, spawn, memo))
+                    .sum::<f64>()
+            })
+            .sum();
+        total / empty.len() as f64
+    };
+    memo.insert((after, depth), v);
+    v
+}
+
+/// C ABI: one number for a progress bar, from −1 (lost) through 0
+/// (undecided) to +1 (won). A board holding the win target scores +1 and a
+/// board with no legal move −1. Otherwise the estimate is s·(1 + w) − 1,
+/// where s is the probability of surviving `depth` (1–`OUTCOME_MAX_DEPTH`)
+/// more moves with play chosen to survive, over the configured spawns, and
+/// w the progress to the target: 1 when some move makes the target tile,
+/// else the max tile over the target value. A fresh board is near 0, one
+/// merge from the target near +1, a board that probably dies within
+/// `depth` moves near −1. A rough guide, not a calibrated win probability:
+/// it looks only `depth` moves ahead. NaN for a bad board or depth (see
+/// `last_error`).
+#[no_mangle]
+pub extern "C" fn game_outcome_estimate(board_ptr: *const u16, depth: u32) -> f64 {
+    clear_error();
+    if !(1..=OUTCOME_MAX_DEPTH).contains(&depth) {
+        set_error(ErrorCode::InvalidDepth);
+        return f64::NAN;
+    }
+    let Some(board) = board_arg(board_ptr) else { return f64::NAN };
+    load_config();
+    let (spawn, target) = CFG.with(|c| {
+        let c = c.borrow();
+        (c.spawn, c.win_target)
+    });
+    if (1u32 << max_rank(board)) >= target as u32 { return 1.0; }
+    let survive = survival_odds(board, depth, &spawn, &mut HashMap::new());
+    let target_rank = target.trailing_zeros() as u8;
+    let progress = if Direction::ALL.iter().any(|&d| max_rank(do_move(board, d).0) >= target_rank) {
+        1.0
+    } else {
+        (1u32 << max_rank(board)) as f64 / target as f64
+    };
+    survive * (1.0 + progress) - 1.0
+}
+
+/// C ABI: `search_ranked_moves` for boards given as 16 signed exponents
+/// (i8), as some gym environments expose them. Empty-cell convention: any
+/// exponent ≤ 0 is empty, so both the 0-empty and the -1-empty encodings
+/// work; e ≥ 1 is the tile 2^e (1 = 2, 11 = 2048), clamped to 15 (32768).
+/// Same depth rules and return values; 0 with `MalformedBoard` for a tile
+/// on a wall cell of a smaller shape.
+#[no_mangle]
+pub extern "C" fn search_ranked_moves_exp(
+    board_ptr: *const i8,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg_exp(board_ptr) else { return 0 };
+    let moves = search_root(board, depth);
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// Copy a ranking into the 4-slot output buffers, rescaling the scores as
+/// configured by `set_score_normalization`; returns the move count
+fn write_ranking(moves: &[(f64, Direction)], scores_out: *mut f64, dirs_out: *mut u8) -> u32 {
+    if !(non_null(scores_out) && non_null(dirs_out)) { return 0; }
+    let n = moves.len().min(4);
+    let scores = unsafe { std::slice::from_raw_parts_mut(scores_out, 4) };
+    let dirs = unsafe { std::slice::from_raw_parts_mut(dirs_out, 4) };
+    for i in 0..n {
+        scores[i] = moves[i].0;
+        dirs[i] = moves[i].1 as u8;
+    }
+    normalize_scores(&mut scores[..n], config().score_norm);
+    n as u32
+}
+
+/// Rescale scores in place; monotone, so the order is kept. A promoted
+/// move may lead with a lower score, so best and worst are searched for.
+fn normalize_scores(scores: &mut [f64], mode: u32) {
+    if scores.is_empty() { return; }
+    let best = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let worst = scores.iter().copied().fold(f64::INFINITY, f64::min);
+    match mode {
+        SCORES_SOFTMAX => {
+            let temp = (best.abs() * SOFTMAX_REL_TEMP).max(f64::MIN_POSITIVE);
+            for v in scores.iter_mut() { *v = ((*v - best) / temp).exp(); }
+            let total: f64 = scores.iter().sum();
+            for v in scores.iter_mut() { *v /= total; }
+        }
+        SCORES_MINMAX => {
+            let span = best - worst;
+            for v in scores.iter_mut() { *v = if span > 0.0 { (*v - worst) / span } else { 1.0 }; }
+        }
+        _ => {}
+    }
+}
+
+/// C ABI: score gap between the best and second-best move of this thread's
+/// most recent search. Large = clear decision; near 0 = the top two are close
+/// and a deeper search may be worthwhile. Infinity when only one move was
+/// legal, 0 when none was.
+#[no_mangle]
+pub extern "C" fn get_move_margin() -> f64 {
+    LAST_MARGIN.with(|m| m.get())
+}
+
+/// C ABI: copy this thread's counters for its most recent search
+#[no_mangle]
+pub extern "C" fn get_search_stats(out: *mut SearchStats) {
+    clear_error();
+    if !non_null(out) { return; }
+    unsafe { *out = STATS.with(|s| *s.borrow()); }
+}
+
+/// C ABI: share of this thread's last search's TT lookups that collided
+/// (`tt_collisions / tt_probes`), 0 when it made none. Always 0 with the
+/// default HashMap table; with `set_tt_array` it shows whether the array is
+/// big enough for the depth searched.
+#[no_mangle]
+pub extern "C" fn get_tt_collision_rate() -> f64 {
+    let s = STATS.with(|s| *s.borrow());
+    if s.tt_probes == 0 { 0.0 } else { s.tt_collisions as f64 / s.tt_probes as f64 }
+}
+
+/// C ABI: `get_search_stats` plus a per-phase time breakdown of this
+/// thread's most recent expectimax search. The phases are timed where the
+/// tree calls them, so they do not nest; the rest of `total_ns` is the
+/// tree walk itself (and the clock reads). Only in builds with the
+/// `profiling` feature, which reads the clock on every node.
+#[cfg(feature = "profiling")]
+#[no_mangle]
+pub extern "C" fn get_search_timings(out: *mut SearchTimings) {
+    clear_error();
+    if !non_null(out) { return; }
+    let mut timings = TIMINGS.with(|t| *t.borrow());
+    timings.stats = STATS.with(|s| *s.borrow());
+    unsafe { *out = timings; }
+}
+
+/// C ABI: copy up to `cap` of the most recent decision log entries into
+/// `out`, oldest first. Returns the number written.
+#[no_mangle]
+pub extern "C" fn get_decision_log(out: *mut DecisionRecord, cap: u32) -> u32 {
+    clear_error();
+    if !non_null(out) { return 0; }
+    let log = decision_log();
+    let n = log.len().min(cap as usize);
+    let out = unsafe { std::slice::from_raw_parts_mut(out, n) };
+    for (o, rec) in out.iter_mut().zip(log.iter().skip(log.len() - n)) {
+        *o = *rec;
+    }
+    n as u32
+}
+
+/// Benchmark position (mid-game, 4 empty cells, max tile 512):
+///     2    8   32  512
+///     4   16   64  256
+///     2    .    8  128
+///     .    .    4    .
+const BENCH_BOARD: [u16; 16] = [
+    2, 8, 32, 512,
+    4, 16, 64, 256,
+    2, 0, 8, 128,
+    0, 0, 4, 0,
+];
+
+/// C ABI: search `BENCH_BOARD` `iterations` times at `depth` with a cold
+/// transposition table each time and return nodes (move + chance) per second.
+/// Fixed input, so numbers are comparable across builds and machines.
+#[no_mangle]
+pub extern "C" fn benchmark(depth: u32, iterations: u32) -> f64 {
+    let board = read_board(BENCH_BOARD.as_ptr());
+    let mut nodes = 0u64;
+    let mut secs = 0.0f64;
+    for _ in 0..iterations.max(1) {
+        let start = Instant::now();
+        search_root(board, depth);
+        secs += start.elapsed().as_secs_f64();
+        nodes += STATS.with(|s| {
+            let s = s.borrow();
+            s.move_nodes + s.chance_nodes
+        });
+    }
+    if secs > 0.0 { nodes as f64 / secs } else { 0.0 }
+}
+
+// ── Self-play ──
+
+/// SplitMix64: tiny, seedable and good enough for spawn placement
+#[derive(Clone, Copy)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+}
+
+/// Place a tile drawn from `spawn` in a random empty cell. None when the
+/// board is full.
+fn spawn_tile(board: BB, rng: &mut Rng, spawn: &SpawnDist) -> Option<BB> {
+    let empty: Vec<u32> = (0..16).filter(|i| (board >> (i * 4)) & 0xF == 0).collect();
+    if empty.is_empty() { return None; }
+    let i = empty[rng.below(empty.len() as u32) as usize];
+    let rank = spawn.sample(rng.next_f64()) as u64;
+    Some(board | (rank << (i * 4)))
+}
+
+/// C ABI: advance a game by one turn — apply `dir`, then spawn a tile chosen
+/// by `seed` using the configured spawn probability. Returns 0 if the move is
+/// illegal (the input board and a zero score are written back), 1 if it moved
+/// and spawned, 2 if it moved but left no empty cell for a spawn. A bad
+/// board or output pointer also returns 0, writing nothing and setting
+/// `last_error`.
+#[no_mangle]
+pub extern "C" fn step(
+    board_ptr: *const u16,
+    dir: u8,
+    seed: u64,
+    out_board_ptr: *mut u16,
+    out_score_ptr: *mut f64,
+) -> u8 {
+    clear_error();
+    let Some(board) = board_arg(board_ptr) else { return TURN_ILLEGAL };
+    if !non_null(out_board_ptr) { return TURN_ILLEGAL; }
+    load_config();
+    let (result, ms, status) = match Direction::from_u8(dir) {
+        Some(d) => play_turn(board, d, seed),
+        None => {
+            set_error(ErrorCode::InvalidArgument);
+            (board, 0.0, TURN_ILLEGAL)
+        }
+    };
+    write_board(result, out_board_ptr);
+    if !out_score_ptr.is_null() {
+        unsafe { *out_score_ptr = ms; }
+    }
+    status
+}
+
+// Turn statuses shared by `step` and `search_and_apply`
+const TURN_ILLEGAL: u8 = 0;
+const TURN_SPAWNED: u8 = 1;
+const TURN_FULL: u8 = 2;
+const TURN_ERR_DEPTH: u8 = 255;
+
+/// Move then spawn; an illegal move returns the board unchanged with score 0
+fn play_turn(board: BB, dir: Direction, seed: u64) -> (BB, f64, u8) {
+    let (nb, ms, moved) = do_move(board, dir);
+    if !moved { return (board, 0.0, TURN_ILLEGAL); }
+    let spawn = config().spawn;
+    match spawn_tile(nb, &mut Rng(seed), &spawn) {
+        Some(sb) => (sb, ms, TURN_SPAWNED),
+        None => (nb, ms, TURN_FULL),
+    }
+}
+
+/// `verify_replay` result when the arguments themselves are bad (null
+/// pointers or a malformed initial board); `last_error` says which
+pub const REPLAY_ERR_ARGS: i32 = i32::MIN;
+
+/// C ABI: check a recorded game and recompute its score. From the initial
+/// board, turn i plays `dirs[i]` and then places the spawn
+/// `spawns[2*i]` (cell index, row-major) = `spawns[2*i + 1]` (tile value).
+/// Returns the summed merge score after all `len` turns, or -(i + 1) for the
+/// first inconsistent turn i: a direction that is not 0–3 or does not move
+/// the board, or a spawn on an occupied or off-board cell, or of a tile the
+/// configured spawn distribution cannot produce.
+#[no_mangle]
+pub extern "C" fn verify_replay(
+    initial_board_ptr: *const u16,
+    dirs_ptr: *const u8,
+    spawns_ptr: *const u16,
+    len: u32,
+) -> i32 {
+    clear_error();
+    let Some(mut board) = board_arg(initial_board_ptr) else { return REPLAY_ERR_ARGS };
+    if len > 0 && !(non_null(dirs_ptr) && non_null(spawns_ptr)) { return REPLAY_ERR_ARGS; }
+    load_config();
+    let n = len as usize;
+    let (dirs, spawns) = if n == 0 {
+        (&[][..], &[][..])
+    } else {
+        unsafe { (std::slice::from_raw_parts(dirs_ptr, n), std::slice::from_raw_parts(spawns_ptr, 2 * n)) }
+    };
+    let spawn = config().spawn;
+    let mut score = 0.0;
+    for (i, (&dir, cell)) in dirs.iter().zip(spawns.chunks_exact(2)).enumerate() {
+        let bad = -(i as i32) - 1;
+        let Some(d) = Direction::from_u8(dir) else { return bad };
+        let (nb, ms, moved) = do_move(board, d);
+        if !moved { return bad; }
+        let (pos, val) = (cell[0] as u64, cell[1]);
+        let rank = val.trailing_zeros() as u8;
+        let spawnable = val.is_power_of_two() && spawn.outcomes().any(|(r, _)| r == rank);
+        if pos >= 16 || (nb >> (pos * 4)) & 0xF != 0 || !spawnable { return bad; }
+        board = nb | ((rank as u64) << (pos * 4));
+        score += ms;
+    }
+    score as i32
+}
+
+/// C ABI: take a whole turn — search at `depth`, play the best move, spawn a
+/// tile chosen by `seed` — and report the new board, the direction and the
+/// move's merge score. Statuses as for `step`: 0 = no legal move (board
+/// written back unchanged, direction 0xFF), 1 = moved and spawned, 2 = moved
+/// with no room to spawn; 255 = `depth` over the cap (nothing written). As
+/// with `step`, bad pointers or boards return 0 and set `last_error`.
+#[no_mangle]
+pub extern "C" fn search_and_apply(
+    board_ptr: *const u16,
+    depth: u32,
+    seed: u64,
+    out_board_ptr: *mut u16,
+    out_dir_ptr: *mut u8,
+    out_score_ptr: *mut f64,
+) -> u8 {
+    clear_error();
+    if !depth_ok(depth) { return TURN_ERR_DEPTH; }
+    let Some(board) = board_arg(board_ptr) else { return TURN_ILLEGAL };
+    if !non_null(out_board_ptr) { return TURN_ILLEGAL; }
+    let (result, dir, ms, status) = match search_root(board, depth).first() {
+        Some(&(_, d)) => {
+            let (nb, ms, status) = play_turn(board, d, seed);
+            (nb, d as u8, ms, status)
+        }
+        None => (board, 0xFF, 0.0, TURN_ILLEGAL),
+    };
+    write_board(result, out_board_ptr);
+    if !out_dir_ptr.is_null() { unsafe { *out_dir_ptr = dir; } }
+    if !out_score_ptr.is_null() { unsafe { *out_score_ptr = ms; } }
+    status
+}
+
+/// Per-move callback for `play_game_stream`: the board after the move and its
+/// spawn (16 tile values), the direction played and the running game score.
+/// Returning nonzero stops the game.
+pub type TraceCallback = extern "C" fn(user: *mut c_void, board: *const u16, dir: u8, score: f64) -> i32;
+
+/// C ABI: play one self-play game from two random tiles, searching each move
+/// at `depth`, and report every move to `cb` as it happens (nothing is
+/// buffered, so games may be arbitrarily long). The game ends when no move is
+/// legal, the win target is reached, or `cb` returns nonzero.
+/// Returns the number of moves played.
+#[no_mangle]
+pub extern "C" fn play_game_stream(
+    seed: u64,
+    depth: u32,
+    cb: Option<TraceCallback>,
+    user: *mut c_void,
+) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    load_config();
+    let mut game = new_game(seed);
+    stream_game(&mut game, depth, cb, user)
+}
+
+/// Play `game` on for `play_game_stream`, making it this thread's current
+/// game (see `game_state_save`) before every callback
+fn stream_game(game: &mut Game, depth: u32, cb: Option<TraceCallback>, user: *mut c_void) -> u32 {
+    let target = config().win_target.trailing_zeros() as u8;
+    CURRENT_GAME.with(|g| g.set(Some(*game)));
+    play_out(game, depth, target, |g, dir| {
+        CURRENT_GAME.with(|c| c.set(Some(*g)));
+        match cb {
+            Some(cb) => {
+                let tiles = board_tiles(g.board);
+                cb(user, tiles.as_ptr(), dir as u8, g.score) == 0
+            }
+            None => true,
+        }
+    })
+}
+
+/// A self-play game in progress: everything needed to continue it exactly
+#[derive(Clone, Copy)]
+struct Game {
+    board: BB,
+    score: f64,                // merge points so far
+    moves: u32,
+    rng: Rng,                  // spawn source
+    last: Option<Direction>,   // previous move, for the look-back penalty
+}
+
+/// A new game: two random tiles drawn from `seed`
+fn new_game(seed: u64) -> Game {
+    let spawn = config().spawn;
+    let mut rng = Rng(seed);
+    let mut board = with_walls(0);
+    for _ in 0..2 {
+        board = spawn_tile(board, &mut rng, &spawn).unwrap_or(board);
+    }
+    Game { board, score: 0.0, moves: 0, rng, last: None }
+}
+
+/// Self-play driver: play from two random tiles until no move is legal, the
+/// win target is reached, or `on_move` (given the post-spawn board, the move
+/// and the running score) returns false. Returns the number of moves played.
+fn play_game(seed: u64, depth: u32, mut on_move: impl FnMut(BB, Direction, f64) -> bool) -> u32 {
+    load_config();
+    let target = config().win_target.trailing_zeros() as u8;
+    let mut game = new_game(seed);
+    play_out(&mut game, depth, target, |g, dir| on_move(g.board, dir, g.score))
+}
+
+/// Play `game` on, searching each move at `depth` and spawning from its RNG,
+/// until no move is legal, a tile of rank `target` appears, or `on_move`
+/// (given the game after the move and its spawn, and the move) returns
+/// false. Returns the number of moves played by this call.
+fn play_out(
+    game: &mut Game,
+    depth: u32,
+    target: u8,
+    mut on_move: impl FnMut(&Game, Direction) -> bool,
+) -> u32 {
+    let spawn = config().spawn;
+    let mut played = 0u32;
+    while max_rank(game.board) < target {
+        // Each search knows the move before it, for the look-back penalty
+        LAST_MOVE.with(|m| m.set(game.last));
+        let Some(&(_, dir)) = search_root(game.board, depth).first() else { break };
+        let (nb, ms, _) = do_move(game.board, dir);
+        game.board = spawn_tile(nb, &mut game.rng, &spawn).unwrap_or(nb);
+        game.score += ms;
+        game.moves += 1;
+        game.last = Some(dir);
+        played += 1;
+        if !on_move(game, dir) { break; }
+    }
+    LAST_MOVE.with(|m| m.set(None));
+    played
+}
+
+/// Evaluation-term statistics over self-play positions (`term_correlations`)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TermStats {
+    pub positions: u64,
+    pub means: [f64; NUM_TERMS as usize],  // TERM_* bit order, as evaluate_components
+    pub correlations: [[f64; NUM_TERMS as usize]; NUM_TERMS as usize],  // Pearson; 0 where a term is constant
+}
+
+/// C ABI: play `games` self-play games at `depth` (seeds `seed`, `seed`+1,
+/// …) and gather every position's `evaluate_components` terms into means
+/// and pairwise correlations, e.g. to spot redundant heuristics. Terms that
+/// never vary (typically disabled ones) correlate 0 with everything,
+/// themselves included. Returns the number of positions sampled.
+#[no_mangle]
+pub extern "C" fn term_correlations(seed: u64, games: u32, depth: u32, out: *mut TermStats) -> u64 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    const N: usize = NUM_TERMS as usize;
+    let mut count = 0u64;
+    let mut sum = [0.0f64; N];
+    let mut prod = [[0.0f64; N]; N];
+    for g in 0..games {
+        play_game(seed.wrapping_add(g as u64), depth, |board, _, _| {
+            // search_root has just loaded the config snapshot into CFG
+            let t = CFG.with(|c| {
+                let c = c.borrow();
+                eval_components(board, active_weights(board, &c), &c)
+            });
+            count += 1;
+            for i in 0..N {
+                sum[i] += t[i];
+                for j in 0..N { prod[i][j] += t[i] * t[j]; }
+            }
+            true
+        });
+    }
+
+    if out.is_null() { return count; }
+    let n = count.max(1) as f64;
+    let means = sum.map(|s| s / n);
+    let var: [f64; N] = std::array::from_fn(|i| prod[i][i] / n - means[i] * means[i]);
+    // E[x²] − E[x]² leaves rounding noise on constant terms; treat as zero
+    let varies: [bool; N] = std::array::from_fn(|i| var[i] > 1e-9 * (1.0 + means[i] * means[i]));
+    let mut corr = [[0.0; N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            if varies[i] && varies[j] {
+                let cov = prod[i][j] / n - means[i] * means[j];
+                corr[i][j] = (cov / (var[i] * var[j]).sqrt()).clamp(-1.0, 1.0);
+            }
+        }
+    }
+    unsafe { *out = TermStats { positions: count, means, correlations: corr }; }
+    count
+}
+
+/// Aggregate outcome of a batch of self-play games (`play_games`)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct GameStats {
+    pub games: u32,
+    pub wins: u32,                   // games that reached the win target
+    pub total_moves: u64,
+    pub total_score: f64,            // sum of final game scores (merge totals)
+    pub best_score: f64,
+    pub max_tile_counts: [u32; 16],  // games whose largest tile was 2^i (index 0 unused)
+}
+
+/// Moves, final score and largest tile rank of one self-play game
+fn game_outcome(seed: u64, depth: u32) -> (u32, f64, u8) {
+    let (mut score, mut top) = (0.0, 0);
+    let moves = play_game(seed, depth, |board, _, s| {
+        score = s;
+        top = max_rank(board);
+        true
+    });
+    (moves, score, top)
+}
+
+/// Fold per-game outcomes in seed order, so the float sums do not depend
+/// on which thread finished first
+fn game_stats(outcomes: &[(u32, f64, u8)]) -> GameStats {
+    let target = config().win_target.trailing_zeros() as u8;
+    let mut st = GameStats::default();
+    for &(moves, score, top) in outcomes {
+        st.games += 1;
+        st.wins += (top >= target) as u32;
+        st.total_moves += moves as u64;
+        st.total_score += score;
+        st.best_score = st.best_score.max(score);
+        st.max_tile_counts[top as usize] += 1;
+    }
+    st
+}
+
+/// C ABI: play `games` self-play games at `depth` (seeds `seed_start`,
+/// `seed_start`+1, …) one after another and write their aggregate to
+/// `out_stats`. Returns the number of games played.
+#[no_mangle]
+pub extern "C" fn play_games(seed_start: u64, games: u32, depth: u32, out_stats: *mut GameStats) -> u32 {
+    clear_error();
+    if !(depth_ok(depth) && non_null(out_stats)) { return 0; }
+    let outcomes: Vec<_> = (0..games).map(|g| game_outcome(seed_start.wrapping_add(g as u64), depth)).collect();
+    unsafe { *out_stats = game_stats(&outcomes); }
+    games
+}
+
+/// C ABI: `play_games` spread over one worker thread per available core.
+/// Each worker has its own transposition table, and every game depends only
+/// on its seed, so the statistics are identical to a serial run.
+/// While it runs, `set_board_shape` refuses with `Busy`.
+#[cfg(feature = "parallel")]
+#[no_mangle]
+pub extern "C" fn play_games_parallel(seed_start: u64, games: u32, depth: u32, out_stats: *mut GameStats) -> u32 {
+    clear_error();
+    if !(depth_ok(depth) && non_null(out_stats)) { return 0; }
+    let outcomes = parallel_outcomes(seed_start, games, depth);
+    unsafe { *out_stats = game_stats(&outcomes); }
+    games
+}
+
+/// `game_outcome` of each seed, in seed order, computed by one worker
+/// thread per available core. The workers read the board shape, so the
+/// batch registers as a `ShapeReader` while it runs.
+#[cfg(feature = "parallel")]
+fn parallel_outcomes(seed_start: u64, games: u32, depth: u32) -> Vec<(u32, f64, u8)> {
+    let _reader = ShapeReader::new(&config());
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get()).min(games.max(1) as usize);
+    let next = AtomicU32::new(0);
+    let mut outcomes = vec![(0, 0.0, 0); games as usize];
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let g = next.fetch_add(1, Ordering::Relaxed);
+                    if g >= games { break done; }
+                    done.push((g, game_outcome(seed_start.wrapping_add(g as u64), depth)));
+                }
+            }))
+            .collect();
+        for h in handles {
+            for (g, outcome) in h.join().unwrap_or_default() { outcomes[g as usize] = outcome; }
+        }
+    });
+    outcomes
+}
+
+const CSV_HEADER: &str = "seed,score,max_tile,moves\n";
+const CSV_ROW_MAX: u64 = 20 + 20 + 5 + 10 + 4;  // u64 seed, whole score, tile, u32 moves, 3 commas and a newline
+
+/// C ABI: buffer size in bytes, NUL included, that `play_games_csv` needs
+/// for `games` games. An upper bound: rows are as long as their numbers.
+#[no_mangle]
+pub extern "C" fn play_games_csv_capacity(games: u32) -> u64 {
+    CSV_HEADER.len() as u64 + games as u64 * CSV_ROW_MAX + 1
+}
+
+/// C ABI: play `games` self-play games at `depth` (seeds `seed_start`,
+/// `seed_start`+1, …) and write one CSV row per game, after a
+/// `seed,score,max_tile,moves` header, to `out` as NUL-terminated text.
+/// Games run in parallel in builds with the `parallel` feature, with the
+/// same results. `cap` must be at least `play_games_csv_capacity(games)`;
+/// otherwise nothing is played and 0 is returned (`InvalidArgument`).
+/// Returns the length of the text without the NUL.
+#[no_mangle]
+pub extern "C" fn play_games_csv(seed_start: u64, games: u32, depth: u32, out: *mut c_char, cap: u64) -> u64 {
+    clear_error();
+    if !(depth_ok(depth) && non_null(out)) { return 0; }
+    if cap < play_games_csv_capacity(games) {
+        set_error(ErrorCode::InvalidArgument);
+        return 0;
+    }
+    #[cfg(feature = "parallel")]
+    let outcomes = parallel_outcomes(seed_start, games, depth);
+    #[cfg(not(feature = "parallel"))]
+    let outcomes: Vec<_> = (0..games).map(|g| game_outcome(seed_start.wrapping_add(g as u64), depth)).collect();
+    let mut csv = String::from(CSV_HEADER);
+    for (g, &(moves, score, top)) in outcomes.iter().enumerate() {
+        let _ = writeln!(csv, "{},{score:.0},{},{moves}", seed_start.wrapping_add(g as u64), 1u32 << top);
+    }
+    let buf = unsafe { std::slice::from_raw_parts_mut(out as *mut u8, csv.len() + 1) };
+    buf[..csv.len()].copy_from_slice(csv.as_bytes());
+    buf[csv.len()] = 0;
+    csv.len() as u64
+}
+
+/// Policy depth of `expected_final_score` rollouts: the literal 0, a greedy
+/// one-ply search, since a rollout plays every move to the end of the game
+const ROLLOUT_DEPTH: u32 = 0;
+
+/// C ABI: estimate the score still to be earned from `board_ptr` by playing
+/// `rollouts` games to the end (past the win target) with the engine's own
+/// moves, searched at `ROLLOUT_DEPTH`, and random spawns, and averaging their
+/// merge totals. Rollout k draws its spawns from seed `seed` + k, so the
+/// estimate is reproducible. The score already on the board is not
+/// included, as a board does not record it. Returns 0 for a locked board and
+/// NaN (see `last_error`) for a bad board or zero rollouts.
+#[no_mangle]
+pub extern "C" fn expected_final_score(board_ptr: *const u16, rollouts: u32, seed: u64) -> f64 {
+    clear_error();
+    let Some(board) = board_arg(board_ptr) else { return f64::NAN };
+    if rollouts == 0 {
+        set_error(ErrorCode::InvalidArgument);
+        return f64::NAN;
+    }
+    load_config();
+    let mut total = 0.0;
+    for k in 0..rollouts {
+        let mut game = Game { board, score: 0.0, moves: 0, rng: Rng(seed.wrapping_add(k as u64)), last: None };
+        play_out(&mut game, ROLLOUT_DEPTH, u8::MAX, |_, _| true);
+        total += game.score;
+    }
+    total / rollouts as f64
+}
+
+// ── Game checkpoints ──
+// A streamed self-play game can be saved and later resumed exactly, on any
+// thread or process with the same configuration. Blob layout
+// (little-endian, GAME_STATE_LEN bytes):
+//   4 × u8 magic "2048", u8 version (1)
+//   u64 board (as `board_pack`), f64 score, u32 moves played,
+//   u64 RNG state, u8 previous move (0xFF = none)
+
+const GAME_STATE_MAGIC: [u8; 4] = *b"2048";
+const GAME_STATE_VERSION: u8 = 1;
+
+/// Size of a `game_state_save` blob
+pub const GAME_STATE_LEN: u64 = 34;
+
+impl Game {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(GAME_STATE_LEN as usize);
+        out.extend_from_slice(&GAME_STATE_MAGIC);
+        out.push(GAME_STATE_VERSION);
+        out.extend_from_slice(&self.board.to_le_bytes());
+        out.extend_from_slice(&self.score.to_le_bytes());
+        out.extend_from_slice(&self.moves.to_le_bytes());
+        out.extend_from_slice(&self.rng.0.to_le_bytes());
+        out.push(self.last.map_or(0xFF, |d| d as u8));
+        out
+    }
+
+    fn from_bytes(blob: &[u8]) -> Option<Game> {
+        if blob.len() != GAME_STATE_LEN as usize || blob[..4] != GAME_STATE_MAGIC || blob[4] != GAME_STATE_VERSION {
+            return None;
+        }
+        let u64_at = |k: usize| u64::from_le_bytes(blob[k..k + 8].try_into().unwrap());
+        let score = f64::from_bits(u64_at(13));
+        let last = match blob[33] {
+            0xFF => None,
+            d => Some(Direction::from_u8(d)?),
+        };
+        if !score.is_finite() || score < 0.0 { return None; }
+        Some(Game {
+            board: u64_at(5),
+            score,
+            moves: u32::from_le_bytes(blob[21..25].try_into().unwrap()),
+            rng: Rng(u64_at(25)),
+            last,
+        })
+    }
+}
+
+thread_local! {
+    static CURRENT_GAME: Cell<Option<Game>> = const { Cell::new(None) };
+}
+
+/// C ABI: checkpoint this thread's current game: the one a streaming call
+/// (`play_game_stream`, `play_game_resume`) is playing, when called from its
+/// callback, else the one it last played or `game_state_load` last loaded.
+/// Writes `GAME_STATE_LEN` bytes to `out` and returns that length; returns 0
+/// with `last_error` set if there is no game yet, `out` is null or `cap` is
+/// too small.
+#[no_mangle]
+pub extern "C" fn game_state_save(out: *mut u8, cap: u64) -> u64 {
+    clear_error();
+    if !non_null(out) { return 0; }
+    let Some(game) = CURRENT_GAME.with(|g| g.get()) else {
+        set_error(ErrorCode::InvalidArgument);
+        return 0;
+    };
+    if cap < GAME_STATE_LEN {
+        set_error(ErrorCode::InvalidArgument);
+        return 0;
+    }
+    let blob = game.to_bytes();
+    unsafe { std::slice::from_raw_parts_mut(out, blob.len()) }.copy_from_slice(&blob);
+    GAME_STATE_LEN
+}
+
+/// C ABI: make a `game_state_save` blob this thread's current game, for
+/// `play_game_resume`. False (`MalformedData`) if the blob does not parse
+/// or has another version; the current game is then unchanged.
+#[no_mangle]
+pub extern "C" fn game_state_load(bytes: *const u8, len: u64) -> bool {
+    clear_error();
+    if !non_null(bytes) { return false; }
+    let blob = unsafe { std::slice::from_raw_parts(bytes, len as usize) };
+    match Game::from_bytes(blob) {
+        Some(game) => {
+            CURRENT_GAME.with(|g| g.set(Some(game)));
+            true
+        }
+        None => {
+            set_error(ErrorCode::MalformedData);
+            false
+        }
+    }
+}
+
+/// C ABI: continue this thread's current game as `play_game_stream` would
+/// have, reporting each move to `cb` with the running score carried over.
+/// With the configuration unchanged, a saved and reloaded game plays
+/// exactly the moves the original would have. Returns the number of moves
+/// played by this call; 0 with `InvalidArgument` if there is no game.
+#[no_mangle]
+pub extern "C" fn play_game_resume(depth: u32, cb: Option<TraceCallback>, user: *mut c_void) -> u32 {
+    clear_error();
+    if !depth_ok(depth) { return 0; }
+    let Some(mut game) = CURRENT_GAME.with(|g| g.get()) else {
+        set_error(ErrorCode::InvalidArgument);
+        return 0;
+    };
+    load_config();
+    stream_game(&mut game, depth, cb, user)
+}
+
+// ── Background search ──
+// Each search runs on its own thread and therefore gets its own thread-local
+// transposition table and stats. It shares two things with other threads:
+// the global configuration, snapshotted with its lookup tables when the
+// search starts, and the board shape, which it registers as reading
+// (`ShapeReader`) until it ends, so `set_board_shape` refuses meanwhile.
+
+pub struct SearchHandle {
+    thread: JoinHandle<Vec<(f64, Direction)>>,
+    cancel: Arc<AtomicBool>,
+}
+
+/// C ABI: start searching `board_ptr` (16 u16s, copied) at `depth` on a
+/// background thread. Until it is finished, `set_board_shape` refuses with
+/// `Busy`. Returns an opaque handle, or null (see `last_error`) if `depth`
+/// exceeds the cap or the board is invalid. Every handle must be finished
+/// by exactly one `poll_search` that returns ≥ 0 or one `cancel_search`;
+/// both release it.
+#[no_mangle]
+pub extern "C" fn start_search(board_ptr: *const u16, depth: u32) -> *mut SearchHandle {
+    clear_error();
+    if !depth_ok(depth) { return std::ptr::null_mut(); }
+    let Some(board) = board_arg(board_ptr) else { return std::ptr::null_mut() };
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    let reader = ShapeReader::new(&config());
+    let thread = std::thread::spawn(move || {
+        let _reader = reader;
+        ABORT.with(|a| *a.borrow_mut() = Some(flag));
+        search_root(board, depth)
+    });
+    Box::into_raw(Box::new(SearchHandle { thread, cancel }))
+}
+
+/// C ABI: check on a background search. Returns -1 while it is still running.
+/// Once done, writes the ranking exactly like `search_ranked_moves`, releases
+/// the handle and returns the number of moves (≥ 0).
+#[no_mangle]
+pub extern "C" fn poll_search(handle: *mut SearchHandle, scores_out: *mut f64, dirs_out: *mut u8) -> i32 {
+    clear_error();
+    if !non_null(handle) { return 0; }
+    if !unsafe { &*handle }.thread.is_finished() { return -1; }
+    let job = unsafe { Box::from_raw(handle) };
+    let moves = job.thread.join().unwrap_or_default();
+    write_ranking(&moves, scores_out, dirs_out) as i32
+}
+
+/// C ABI: stop a background search, wait for its thread to unwind (this is
+/// quick: every move node checks the cancel flag) and release the handle.
+#[no_mangle]
+pub extern "C" fn cancel_search(handle: *mut SearchHandle) {
+    if handle.is_null() { return; }
+    let job = unsafe { Box::from_raw(handle) };
+    job.cancel.store(true, Ordering::Relaxed);
+    let _ = job.thread.join();
+}
+
+// ── Search contexts ──
+// A SearchContext owns the state a search otherwise keeps in the calling
+// thread's thread-locals: transposition table, counters and move margin.
+// `search_with_context` swaps it in for one search and back out, so a
+// caller can keep several independent workspaces on one thread, and the
+// context's TT carries over from one of its searches to the next.
+
+pub struct SearchContext {
+    tt: TransTable,
+    stats: SearchStats,
+    margin: f64,
+    fingerprint: u64,  // config the TT entries were computed under
+}
+
+/// C ABI: allocate a search context, its TT sized for the current
+/// configuration. Release it with `search_context_free`.
+#[no_mangle]
+pub extern "C" fn search_context_new() -> *mut SearchContext {
+    let (bits, fingerprint) = {
+        let c = config();
+        (c.tt_array_bits, c.fingerprint())
+    };
+    Box::into_raw(Box::new(SearchContext {
+        tt: TransTable::new(bits),
+        stats: SearchStats::default(),
+        margin: 0.0,
+        fingerprint,
+    }))
+}
+
+/// C ABI: release a search context (null is ignored)
+#[no_mangle]
+pub extern "C" fn search_context_free(ctx: *mut SearchContext) {
+    if ctx.is_null() { return; }
+    drop(unsafe { Box::from_raw(ctx) });
+}
+
+/// C ABI: empty a context's TT, e.g. between games
+#[no_mangle]
+pub extern "C" fn search_context_clear(ctx: *mut SearchContext) {
+    clear_error();
+    if !non_null(ctx) { return; }
+    unsafe { &mut *ctx }.tt.clear();
+}
+
+/// C ABI: `search_ranked_moves` using `ctx` instead of the calling thread's
+/// workspace. The context's TT is kept between its searches (and emptied
+/// when the configuration has changed since), so consecutive positions of
+/// one game reuse earlier work; the thread's own TT, counters and margin are
+/// left untouched. A context may move between threads but must not be used
+/// by two at once. Same depth rules and return values; 0 with `last_error`
+/// set for a null context.
+#[no_mangle]
+pub extern "C" fn search_with_context(
+    ctx: *mut SearchContext,
+    board_ptr: *const u16,
+    depth: u32,
+    scores_out: *mut f64,
+    dirs_out: *mut u8,
+) -> u32 {
+    clear_error();
+    if !non_null(ctx) { return 0; }
+    if !depth_ok(depth) { return 0; }
+    let Some(board) = board_arg(board_ptr) else { return 0 };
+    let ctx = unsafe { &mut *ctx };
+    let fingerprint = config().fingerprint();
+    if ctx.fingerprint != fingerprint {
+        ctx.tt.clear();
+        ctx.fingerprint = fingerprint;
+    }
+
+    let swap = |ctx: &mut SearchContext| {
+        TT.with(|tt| std::mem::swap(&mut *tt.borrow_mut(), &mut ctx.tt));
+        STATS.with(|s| std::mem::swap(&mut *s.borrow_mut(), &mut ctx.stats));
+        LAST_MARGIN.with(|m| ctx.margin = m.replace(ctx.margin));
+    };
+    swap(ctx);
+    KEEP_TT.with(|k| k.set(true));
+    let moves = search_root(board, depth);
+    KEEP_TT.with(|k| k.set(false));
+    swap(ctx);
+    write_ranking(&moves, scores_out, dirs_out)
+}
+
+/// C ABI: trim a context's TT to the principal variation of its last
+/// search (the best move's afterstate and the best reply to each spawn on
+/// it), the entries its next search is most likely to reuse. A HashMap TT
+/// does this by itself when full; an array TT is cleared. Returns the number
+/// of entries left.
+#[no_mangle]
+pub extern "C" fn search_context_evict(ctx: *mut SearchContext) -> u64 {
+    clear_error();
+    if !non_null(ctx) { return 0; }
+    let tt = &mut unsafe { &mut *ctx }.tt;
+    tt.evict();
+    tt.len() as u64
+}
+
+/// C ABI: number of positions in a context's TT
+#[no_mangle]
+pub extern "C" fn search_context_tt_entries(ctx: *const SearchContext) -> u64 {
+    clear_error();
+    if !non_null(ctx) { return 0; }
+    unsafe { &*ctx }.tt.len() as u64
+}
+
+/// C ABI: copy the counters of the context's most recent search
+#[no_mangle]
+pub extern "C" fn search_context_stats(ctx: *const SearchContext, out: *mut SearchStats) {
+    clear_error();
+    if !(non_null(ctx) && non_null(out)) { return; }
+    unsafe { *out = (*ctx).stats; }
+}
+
+/// C ABI: `get_move_margin` for the context's most recent search
+#[no_mangle]
+pub extern "C" fn search_context_margin(ctx: *const SearchContext) -> f64 {
+    clear_error();
+    if !non_null(ctx) { return f64::NAN; }
+    unsafe { (*ctx).margin }
+}
+
+// ── Search tree export ──
+// The full expectimax tree of a small search as JSON, for tutorials and bug
+// reports. It is rebuilt without the TT, so repeated positions appear in full,
+// but with the search's own chance cells, pruning and blending, so its
+// values match the ranked scores before any root adjustments.
+
+/// Deepest tree `dump_search_tree` builds
+pub const DUMP_MAX_DEPTH: u32 = 3;
+/// Node budget; a tree this large is refused rather than returned
+const DUMP_MAX_NODES: usize = 1 << 20;
+
+struct TreeDump {
+    json: String,
+    nodes: usize,
+    spawn: SpawnDist,
+    max_cells: u32,
+    blend: f64,
+    accumulate: bool,
+}
+
+impl TreeDump {
+    /// Node type and board; None once the node budget is spent
+    fn head(&mut self, kind: &str, board: BB) -> Option<()> {
+        self.nodes += 1;
+        if self.nodes > DUMP_MAX_NODES { return None; }
+        write!(self.json, "\"type\":\"{kind}\",\"board\":{:?}", board_tiles(board)).ok()
+    }
+
+    /// Value and closing brace (JSON has no NaN or infinity)
+    fn close(&mut self, v: f64) {
+        if v.is_finite() {
+            let _ = write!(self.json, ",\"value\":{v}}}");
+        } else {
+            self.json.push_str(",\"value\":null}");
+        }
+    }
+
+    fn leaf(&mut self, board: BB) -> Option<f64> {
+        self.head("leaf", board)?;
+        let v = evaluate(board);
+        self.close(v);
+        Some(v)
+    }
+
+    /// Mirrors `chance_node`; children carry their cell, tile and probability
+    fn chance(&mut self, board: BB, depth: u32, cprob: f64) -> Option<f64> {
+        if cprob < CPROB_THRESH || depth == 0 { return self.leaf(board); }
+        let (cells, n) = chance_cells(board, self.max_cells);
+        if n == 0 { return self.leaf(board); }
+        self.head("chance", board)?;
+        self.json.push_str(",\"children\":[");
+        let spawn = self.spawn;
+        let (mut mean, mut worst) = (0.0, f64::INFINITY);
+        for &i in &cells[..n] {
+            for (rank, p) in spawn.outcomes() {
+                if !self.json.ends_with('[') { self.json.push(','); }
+                let prob = p / n as f64;
+                let _ = write!(self.json, "{{\"cell\":{i},\"tile\":{},\"prob\":{prob},", 1u32 << rank);
+                let v = self.max(board | ((rank as u64) << (i * 4)), depth - 1, cprob * prob)?;
+                mean += prob * v;
+                worst = worst.min(v);
+            }
+        }
+        self.json.push(']');
+        let v = if self.blend > 0.0 { (1.0 - self.blend) * mean + self.blend * worst } else { mean };
+        self.close(v);
+        Some(v)
+    }
+
+    /// Mirrors `score_move_node`, searching the chance nodes below at
+    /// `child_depth`; children carry their move and merge score
+    fn max(&mut self, board: BB, child_depth: u32, cprob: f64) -> Option<f64> {
+        self.head("max", board)?;
+        self.json.push_str(",\"children\":[");
+        let mut best = 0.0f64;
+        for d in Direction::ALL {
+            let (nb, ms, moved) = do_move(board, d);
+            if !moved { continue; }
+            if !self.json.ends_with('[') { self.json.push(','); }
+            let name = d.name().to_str().unwrap_or("?");
+            let _ = write!(self.json, "{{\"move\":\"{name}\",\"merge_score\":{ms},");
+            let mut v = self.chance(nb, child_depth, cprob)?;
+            if self.accumulate { v += ms; }
+            best = best.max(v);
+        }
+        self.json.push(']');
+        self.close(best);
+        Some(best)
+    }
+}
+
+/// C ABI: the expectimax tree a search of `board_ptr` at `depth` (taken
+/// literally, 0–`DUMP_MAX_DEPTH`) explores, as a JSON object written to
+/// `*out_json`. Nodes are `{"type", "board", "children", "value"}` with type
+/// "max", "chance" or "leaf" (no children); a max node's children add
+/// "move" and "merge_score", a chance node's add "cell" (0–15), "tile" and
+/// "prob". A max node without legal moves is worth 0. The string must be
+/// released with `free_json`. Returns the number of nodes, or -1 (see
+/// `last_error`) for a bad argument, a depth over the cap, or a tree over
+/// the node budget (`InvalidArgument`); `*out_json` is then null.
+#[no_mangle]
+pub extern "C" fn dump_search_tree(board_ptr: *const u16, depth: u32, out_json: *mut *mut c_char) -> i32 {
+    clear_error();
+    if !non_null(out_json) { return -1; }
+    unsafe { *out_json = std::ptr::null_mut(); }
+    let Some(board) = board_arg(board_ptr) else { return -1 };
+    if depth > DUMP_MAX_DEPTH {
+        set_error(ErrorCode::InvalidDepth);
+        return -1;
+    }
+    begin_search();
+    let mut dump = CFG.with(|c| {
+        let c = c.borrow();
+        TreeDump {
+            json: String::new(),
+            nodes: 0,
+            spawn: c.spawn,
+            max_cells: c.max_chance_cells,
+            blend: c.chance_blend,
+            accumulate: c.merge_accumulate,
+        }
+    });
+    dump.json.push('{');
+    if dump.max(board, depth, 1.0).is_none() {
+        set_error(ErrorCode::InvalidArgument);
+        return -1;
+    }
+    let Ok(json) = std::ffi::CString::new(dump.json) else { return -1 };
+    unsafe { *out_json = json.into_raw(); }
+    dump.nodes as i32
+}
+
+/// C ABI: release a string returned by `dump_search_tree` (null is ignored)
+#[no_mangle]
+pub extern "C" fn free_json(json: *mut c_char) {
+    if json.is_null() { return; }
+    drop(unsafe { std::ffi::CString::from_raw(json) });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The configuration and the board shape are process-wide, so tests
+    // that touch them take this lock and start from the defaults
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    fn fresh() -> MutexGuard<'static, ()> {
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if walls() != 0 { set_board_shape(4, 4); }
+        *config() = DEFAULT_CONFIG;
+        guard
+    }
+
+    /// `search_ranked_moves` output as (score, direction code) pairs
+    fn ranked(tiles: &[u16; 16], depth: u32) -> Vec<(f64, u8)> {
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        let n = search_ranked_moves(tiles.as_ptr(), depth, scores.as_mut_ptr(), dirs.as_mut_ptr());
+        assert!(n <= 4, "search failed: {n}");
+        (0..n as usize).map(|i| (scores[i], dirs[i])).collect()
+    }
+
+    fn directions(moves: &[(f64, u8)]) -> Vec<u8> {
+        moves.iter().map(|m| m.1).collect()
+    }
+
+    const MIDGAME: [u16; 16] = [
+        2, 8, 32, 512,
+        4, 16, 64, 256,
+        2, 0, 8, 128,
+        0, 0, 4, 0,
+    ];
+    const OPENING: [u16; 16] = [
+        0, 0, 2, 0,
+        0, 4, 0, 0,
+        0, 8, 2, 0,
+        0, 0, 0, 4,
+    ];
+    const CROWDED: [u16; 16] = [
+        1024, 512, 256, 128,
+        8, 16, 32, 64,
+        4, 2, 8, 4,
+        2, 0, 4, 2,
+    ];
+
+    /// Rankings of `MIDGAME`, `OPENING` and `CROWDED` at depth 2, capped at 2
+    fn rank_boards() -> Vec<Vec<(f64, u8)>> {
+        [MIDGAME, OPENING, CROWDED].iter().map(|b| ranked(b, 2)).collect()
+    }
+
+    #[test]
+    fn fixed_point_matches_golden_and_float() {
+        let _g = fresh();
+        set_max_depth(2);
+        let float = rank_boards();
+        set_fixed_point(true);
+        let fixed = rank_boards();
+        // Multiples of 1/1024, exact on every platform
+        let golden = [
+            vec![(1437118.9970703125, 3), (1399746.724609375, 2), (1378308.671875, 1)],
+            vec![(1605313.185546875, 1), (1605102.263671875, 0), (1604520.4501953125, 2), (1603400.4619140625, 3)],
+            vec![(1410348.7080078125, 3), (1408588.74609375, 2), (621595.484375, 1)],
+        ];
+        assert_eq!(fixed, golden);
+        assert_eq!(rank_boards(), fixed, "fixed-point search is not deterministic");
+        for (f, x) in float.iter().zip(&fixed) {
+            assert_eq!(directions(f), directions(x));
+            for (a, b) in f.iter().zip(x) { assert!((a.0 - b.0).abs() < 1e-6 * a.0); }
+        }
+    }
+
+    #[test]
+    fn fixed_point_follows_custom_weights() {
+        let w = EvalWeights { empty: 500.0, mono: 20.0, ..DEFAULT_WEIGHTS };
+        for survival in [false, true] {
+            let _g = fresh();
+            set_max_depth(2);
+            if survival {
+                set_endgame_threshold(16);
+                set_survival_weights(&w);
+            } else {
+                set_eval_weights(&w);
+            }
+            let float = rank_boards();
+            set_fixed_point(true);
+            for (f, x) in float.iter().zip(&rank_boards()) {
+                assert_eq!(directions(f), directions(x));
+                for (a, b) in f.iter().zip(x) { assert!((a.0 - b.0).abs() < 1e-6 * a.0, "{a:?} vs {b:?}"); }
+            }
+        }
+    }
+
+    /// A setter by name, returning whether it took effect
+    type Setter = (&'static str, fn() -> bool);
+
+    /// Every setter that replaces the lookup tables
+    fn table_setters() -> [Setter; 4] {
+        [
+            ("set_eval_weights", || { set_eval_weights(std::ptr::null()); last_error() == 0 }),
+            ("set_survival_weights", || { set_survival_weights(std::ptr::null()); last_error() == 0 }),
+            ("set_eval_term_mask", || { set_eval_term_mask(TERM_ALL); last_error() == 0 }),
+            ("set_board_shape", || set_board_shape(4, 4)),
+        ]
+    }
+
+    /// Did the setter do what it should while another thread searches: the
+    /// board shape refused (`Busy`), the rest accepted?
+    fn set_while_searching((name, set): Setter) -> bool {
+        let shape = name == "set_board_shape";
+        set() != shape && last_error() == if shape { ErrorCode::Busy as u32 } else { 0 }
+    }
+
+    #[test]
+    fn only_the_board_shape_waits_for_a_background_search() {
+        let _g = fresh();
+        let handle = start_search(MIDGAME.as_ptr(), 8);
+        assert!(!handle.is_null());
+        for setter in table_setters() {
+            assert!(set_while_searching(setter), "{} under a background search", setter.0);
+        }
+        cancel_search(handle);
+        for (name, set) in table_setters() { assert!(set(), "{name} still refused"); }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn only_the_board_shape_waits_for_a_parallel_batch() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        static WRONG: AtomicU32 = AtomicU32::new(0);
+        // Runs on the worker threads, mid-batch; defers to the built-in choice
+        extern "C" fn probe(_: *const u16, _: *mut u8, _: u32) -> u32 {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            for setter in table_setters() {
+                if !set_while_searching(setter) { WRONG.fetch_add(1, Ordering::Relaxed); }
+            }
+            0
+        }
+        let _g = fresh();
+        set_max_depth(1);
+        set_win_target(32);
+        set_chance_selector(Some(probe));
+        let mut stats = GameStats::default();
+        assert_eq!(play_games_parallel(1, 2, 1, &mut stats), 2);
+        assert!(CALLS.load(Ordering::Relaxed) > 0);
+        assert_eq!(WRONG.load(Ordering::Relaxed), 0);
+        set_chance_selector(None);
+        for (name, set) in table_setters() { assert!(set(), "{name} still refused"); }
+    }
+
+    #[test]
+    fn a_running_search_keeps_its_tables() {
+        // Runs mid-search, at every chance node; defers to the built-in choice
+        extern "C" fn reweigh(_: *const u16, _: *mut u8, _: u32) -> u32 {
+            set_eval_term_mask(TERM_EMPTY);
+            0
+        }
+        let _g = fresh();
+        set_max_depth(2);
+        let expect = ranked(&MIDGAME, 2);
+        set_chance_selector(Some(reweigh));
+        assert_eq!(ranked(&MIDGAME, 2), expect);
+        // The next search builds tables for the new mask
+        set_chance_selector(None);
+        assert_eq!(get_eval_term_mask(), TERM_EMPTY);
+        assert_ne!(ranked(&MIDGAME, 2), expect);
+    }
+
+    #[test]
+    fn normalization_and_margin_survive_promotion() {
+        let _g = fresh();
+        set_max_depth(2);
+        // At this temperature nearly every draw promotes some move other
+        // than the top-scoring one
+        set_exploration_temperature(10.0);
+        set_exploration_seed(164);
+        let mut promoted = 0;
+        for _ in 0..40 {
+            set_score_normalization(SCORES_RAW);
+            let raw = ranked(&MIDGAME, 2);
+            let margin = get_move_margin();
+            let best = raw.iter().map(|m| m.0).fold(f64::NEG_INFINITY, f64::max);
+            if raw[0].0 < best { promoted += 1; }
+            assert!(margin >= 0.0, "margin {margin} for {raw:?}");
+
+            set_score_normalization(SCORES_MINMAX);
+            let mm = ranked(&MIDGAME, 2);
+            assert!(mm.iter().all(|m| (0.0..=1.0).contains(&m.0)), "{mm:?}");
+            assert_eq!(mm.iter().map(|m| m.0).fold(0.0, f64::max), 1.0);
+            assert_eq!(mm.iter().map(|m| m.0).fold(1.0, f64::min), 0.0);
+
+            set_score_normalization(SCORES_SOFTMAX);
+            let sm = ranked(&MIDGAME, 2);
+            assert!(sm.iter().all(|m| (0.0..=1.0).contains(&m.0)), "{sm:?}");
+            assert!((sm.iter().map(|m| m.0).sum::<f64>() - 1.0).abs() < 1e-12);
+        }
+        assert!(promoted > 0, "no draw promoted a lower-scoring move");
+    }
+
+    /// This thread's last-search state: counters, TT size and margin
+    fn search_state() -> (u64, u64, u64, u64, u32, usize, f64) {
+        let st = STATS.with(|s| *s.borrow());
+        (st.move_nodes, st.chance_nodes, st.evals, st.tt_hits, st.depth,
+         TT.with(|t| t.borrow().len()), get_move_margin())
+    }
+
+    #[test]
+    fn side_queries_keep_the_last_search() {
+        let _g = fresh();
+        set_max_depth(2);
+        ranked(&MIDGAME, 2);
+        let before = search_state();
+        assert!(before.5 > 0);
+        assert!(greedy_move(MIDGAME.as_ptr()) >= 0);
+        assert_eq!(search_state(), before, "greedy_move");
+        let mut mask = 0u16;
+        threatened_cells(CROWDED.as_ptr(), &mut mask);
+        assert_eq!(last_error(), 0);
+        assert_eq!(search_state(), before, "threatened_cells");
+    }
+
+    const HEADER: &str = include_str!(concat!(env!("OUT_DIR"), "/search2048.h"));
+
+    #[test]
+    fn header_declares_every_export() {
+        let missing: Vec<&str> = include_str!("lib.rs")
+            .lines()
+            .filter_map(|l| l.strip_prefix("pub extern \"C\" fn "))
+            .map(|rest| &rest[..rest.find('(').unwrap()])
+            .filter(|name| !HEADER.contains(&format!(" {name}(")))
+            .collect();
+        assert!(missing.is_empty(), "not in search2048.h: {missing:?}");
+        assert!(HEADER.contains(
+            "uint32_t search_ranked_moves(const uint16_t* board_ptr, uint32_t depth, double* scores_out, uint8_t* dirs_out);"
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(not(has_c_compiler), ignore = "no C compiler to check the header with")]
+    fn header_compiles_as_c() {
+        let path = concat!(env!("OUT_DIR"), "/search2048.h");
+        // Feature-gated declarations are checked too
+        let out = std::process::Command::new("cc")
+            .args(["-fsyntax-only", "-Wall", "-Werror", "-x", "c", path])
+            .args(["-DSEARCH2048_FEATURE_PARALLEL", "-DSEARCH2048_FEATURE_PROFILING"])
+            .output()
+            .expect("running cc");
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    }
+
+    #[test]
+    fn board_key_updates_match_recompute() {
+        let _g = fresh();
+        for (rows, cols) in [(4, 4), (3, 3), (2, 4)] {
+            assert!(set_board_shape(rows, cols));
+            let on_board: Vec<u32> = (0..16).filter(|i| (walls() >> (i * 4)) & 0xF == 0).collect();
+            let mut rng = Rng(130);
+            let mut tiles = [0u16; 16];
+            let mut key = board_key(tiles.as_ptr());
+            assert_eq!(key, 0, "walls leaked into the key");
+            for _ in 0..2000 {
+                let pos = on_board[rng.below(on_board.len() as u32) as usize];
+                let rank = rng.below(12);
+                let new = if rank == 0 { 0 } else { 1u16 << rank };
+                key = board_key_update(key, pos, tiles[pos as usize], new);
+                assert_eq!(last_error(), 0);
+                tiles[pos as usize] = new;
+                assert_eq!(key, board_key(tiles.as_ptr()));
+                assert_eq!(key, board_pack(tiles.as_ptr()));
+            }
+
+            // Bad updates leave the key as it was
+            let held = tiles[on_board[0] as usize];
+            let bad = [
+                (16, 0, 2, ErrorCode::InvalidArgument),
+                (on_board[0], if held == 4 { 8 } else { 4 }, 2, ErrorCode::InvalidArgument),
+                (on_board[0], held, 3, ErrorCode::MalformedBoard),
+                (on_board[0], held, 1, ErrorCode::MalformedBoard),
+            ];
+            for (pos, old, new, code) in bad {
+                assert_eq!(board_key_update(key, pos, old, new), key);
+                assert_eq!(last_error(), code as u32, "pos {pos} {old} -> {new}");
+            }
+            if let Some(wall) = (0..16).find(|p| !on_board.contains(p)) {
+                assert_eq!(board_key_update(key, wall, 0, 2), key);
+                assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn log2_boards_are_checked_against_the_shape() {
+        let _g = fresh();
+        set_max_depth(2);
+        let ranks: [u8; 16] = MIDGAME.map(|v| if v == 0 { 0 } else { v.trailing_zeros() as u8 });
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        let n = search_ranked_moves_log2(ranks.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr());
+        let expect = ranked(&MIDGAME, 2);
+        assert_eq!(n as usize, expect.len());
+        assert_eq!(directions(&expect), dirs[..n as usize]);
+
+        assert!(set_board_shape(3, 3));
+        let mut small = [0u8; 16];
+        small[0] = 1;
+        small[5] = 2;
+        let n = search_ranked_moves_log2(small.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr());
+        assert!(n > 0 && last_error() == 0);
+        // Cell 3 is a wall on 3×3
+        small[3] = 1;
+        assert_eq!(search_ranked_moves_log2(small.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+        assert_eq!(search_ranked_moves_log2(std::ptr::null(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+    }
+
+    #[test]
+    fn exp_boards_are_checked_against_the_shape() {
+        let _g = fresh();
+        set_max_depth(2);
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        let expect = ranked(&MIDGAME, 2);
+        // Both empty-cell conventions read the same board
+        for empty in [0i8, -1] {
+            let exps: [i8; 16] = MIDGAME.map(|v| if v == 0 { empty } else { v.trailing_zeros() as i8 });
+            let n = search_ranked_moves_exp(exps.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            assert_eq!(directions(&expect), dirs[..n as usize]);
+        }
+
+        assert!(set_board_shape(3, 3));
+        let mut small = [-1i8; 16];
+        small[0] = 1;
+        small[5] = 2;
+        let n = search_ranked_moves_exp(small.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr());
+        assert!(n > 0 && last_error() == 0);
+        // Cell 12 is a wall on 3×3
+        small[12] = 3;
+        assert_eq!(search_ranked_moves_exp(small.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+    }
+
+    /// Reference move: a slow cell-by-cell slide to cross-check the
+    /// table-driven moves, in particular the transposed up/down paths. Each
+    /// line slides towards the wall its tiles move to, skipping cells
+    /// outside the board shape, with the tables' rank cap and scoring.
+    fn reference_move(b: BB, dir: Direction) -> (BB, f64) {
+        let w = walls();
+        let mut out: BB = w;
+        let mut score = 0.0;
+        for line in 0..4 {
+            let idx: Vec<usize> = (0..4)
+                .map(|k| match dir {
+                    Direction::Left => line * 4 + k,
+                    Direction::Right => line * 4 + 3 - k,
+                    Direction::Up => k * 4 + line,
+                    Direction::Down => (3 - k) * 4 + line,
+                })
+                .filter(|&i| (w >> (i * 4)) & 0xF == 0)
+                .collect();
+            let tiles: Vec<u8> = idx.iter().map(|&i| ((b >> (i * 4)) & 0xF) as u8).filter(|&r| r != 0).collect();
+            let mut packed = Vec::with_capacity(4);
+            let mut k = 0;
+            while k < tiles.len() {
+                // On a smaller shape no merge may make the wall rank
+                if k + 1 < tiles.len() && tiles[k] == tiles[k + 1] && !(w != 0 && tiles[k] + 1 >= WALL_RANK) {
+                    let nr = tiles[k] + 1;
+                    score += (1u64 << nr) as f64;
+                    packed.push(nr.min(15));
+                    k += 2;
+                } else {
+                    packed.push(tiles[k]);
+                    k += 1;
+                }
+            }
+            for (&i, &r) in idx.iter().zip(&packed) {
+                out |= (r as u64) << (i * 4);
+            }
+        }
+        (out, score)
+    }
+
+    /// Random board for the current shape: about a third of the cells
+    /// empty, the rest spread over every rank a tile can hold there
+    fn random_board(rng: &mut Rng) -> BB {
+        let top = if walls() == 0 { 15 } else { WALL_RANK as u32 - 1 };
+        let mut b: BB = 0;
+        for i in 0..16 {
+            let rank = if rng.below(3) == 0 { 0 } else { 1 + rng.below(top) as u64 };
+            b |= rank << (i * 4);
+        }
+        with_walls(b)
+    }
+
+    #[test]
+    fn table_moves_match_reference_slide() {
+        let _g = fresh();
+        for (rows, cols) in [(4, 4), (3, 4), (4, 3), (3, 3), (2, 4), (4, 2), (2, 2)] {
+            assert!(set_board_shape(rows, cols));
+            load_config();
+            let mut rng = Rng(136);
+            for _ in 0..2000 {
+                let b = random_board(&mut rng);
+                for d in Direction::ALL {
+                    let (nb, sc, moved) = do_move(b, d);
+                    assert_eq!((nb, sc), reference_move(b, d), "{rows}x{cols} {b:016x} {d:?}");
+                    assert_eq!(moved, nb != b);
+                    // An illegal move leaves the board as it was and scores nothing
+                    if !moved { assert_eq!(sc, 0.0); }
+                }
+            }
+        }
+    }
+
+    /// Two different moves that both slide: do they give the same board?
+    fn moves_collide(b: BB) -> bool {
+        let after: Vec<BB> = Direction::ALL.iter()
+            .map(|&d| do_move(b, d))
+            .filter_map(|(nb, _, moved)| moved.then_some(nb))
+            .collect();
+        (0..after.len()).any(|i| after[i + 1..].contains(&after[i]))
+    }
+
+    // search_root skips successor dedupe on this: every board of empties,
+    // 2s and 4s on the smaller shapes, and a sample of them and of boards
+    // with any tiles on 4×4
+    #[test]
+    fn moving_boards_never_coincide() {
+        let _g = fresh();
+        for (rows, cols) in [(4, 4), (3, 4), (4, 3), (3, 3), (2, 4), (4, 2), (2, 3), (2, 2)] {
+            assert!(set_board_shape(rows, cols));
+            load_config();
+            let cells: Vec<usize> = (0..16).filter(|i| (walls() >> (i * 4)) & 0xF == 0).collect();
+            let mut rng = Rng(154);
+            let mut boards: Vec<BB> = Vec::new();
+            if cells.len() <= 12 {
+                for n in 0..3u64.pow(cells.len() as u32) {
+                    let mut b = 0;
+                    let mut x = n;
+                    for &i in &cells {
+                        b |= (x % 3) << (i * 4);
+                        x /= 3;
+                    }
+                    boards.push(with_walls(b));
+                }
+            } else {
+                boards.extend((0..100_000).map(|_| {
+                    cells.iter().fold(0, |b, &i| b | (rng.below(3) as u64) << (i * 4))
+                }));
+            }
+            boards.extend((0..20_000).map(|_| random_board(&mut rng)));
+            let bad: Vec<String> = boards.iter().filter(|&&b| moves_collide(b)).map(|b| format!("{b:016x}")).take(5).collect();
+            assert!(bad.is_empty(), "{rows}x{cols}: {bad:?}");
+        }
+    }
+
+    // Brute-force expectimax over every spawn, with no TT, cell cap, fast
+    // path or pruning, built on `reference_move`. Under the default spawns
+    // every chance node expanded at depth 2 has probability ≥ 0.1 / 16, far
+    // above `CPROB_THRESH`; from depth 3 the cutoff prunes by design.
+    const ORACLE_MAX_DEPTH: u32 = 2;
+
+    /// Oracle chance node: mean over every empty cell and spawn outcome
+    fn oracle_chance(b: BB, depth: u32, accumulate: bool, spawn: &SpawnDist) -> f64 {
+        if depth == 0 { return evaluate(b); }
+        let empty: Vec<u32> = (0..16).filter(|&i| (b >> (i * 4)) & 0xF == 0).collect();
+        if empty.is_empty() { return evaluate(b); }
+        let mut total = 0.0;
+        for &i in &empty {
+            let mut cell_total = 0.0;
+            for (rank, p) in spawn.outcomes() {
+                cell_total += p * oracle_move(b | ((rank as u64) << (i * 4)), depth, accumulate, spawn);
+            }
+            total += cell_total;
+        }
+        total / empty.len() as f64
+    }
+
+    /// Oracle move node: best legal move, 0 when there is none
+    fn oracle_move(b: BB, depth: u32, accumulate: bool, spawn: &SpawnDist) -> f64 {
+        let mut best = 0.0f64;
+        for d in Direction::ALL {
+            let (nb, ms) = reference_move(b, d);
+            if nb == b { continue; }
+            let mut v = oracle_chance(nb, depth - 1, accumulate, spawn);
+            if accumulate { v += ms; }
+            best = best.max(v);
+        }
+        best
+    }
+
+    /// Root moves of `boards` random boards (about half empty, the rest up
+    /// to 1024 so merges are common) on which the search and the oracle
+    /// disagree, on legality or beyond rounding on value, with merge
+    /// accumulation off and on
+    fn expectimax_disagreements(seed: u64, boards: u32, depth: u32) -> u32 {
+        let mut rng = Rng(seed);
+        let mut disagreements = 0;
+        for _ in 0..boards {
+            let mut b: BB = 0;
+            for i in 0..16 {
+                let rank = if rng.below(2) == 0 { 0 } else { 1 + rng.below(10) as u64 };
+                b |= rank << (i * 4);
+            }
+            begin_search();
+            let spawn = CFG.with(|c| c.borrow().spawn);
+            for accumulate in [false, true] {
+                CFG.with(|c| c.borrow_mut().merge_accumulate = accumulate);
+                TT.with(|tt| tt.borrow_mut().clear());
+                for d in Direction::ALL {
+                    let (nb, ms, moved) = do_move(b, d);
+                    let (rb, rms) = reference_move(b, d);
+                    if moved != (rb != b) {
+                        disagreements += 1;
+                        continue;
+                    }
+                    if !moved { continue; }
+                    let mut fast = chance_node(nb, depth, 1.0, None);
+                    let mut slow = oracle_chance(rb, depth, accumulate, &spawn);
+                    if accumulate {
+                        fast += ms;
+                        slow += rms;
+                    }
+                    if (fast - slow).abs() > 1e-9 * slow.abs().max(1.0) { disagreements += 1; }
+                }
+            }
+        }
+        disagreements
+    }
+
+    #[test]
+    fn expectimax_matches_brute_force() {
+        let _g = fresh();
+        for depth in 1..=ORACLE_MAX_DEPTH {
+            assert_eq!(expectimax_disagreements(7, 16, depth), 0, "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn expectimax_matches_brute_force_with_board_terms() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { corner: 1.0, snake: 0.5, trapped: 2.0, breathing: 50.0, ..DEFAULT_WEIGHTS });
+        assert_eq!(expectimax_disagreements(8, 8, ORACLE_MAX_DEPTH), 0);
+    }
+
+    // ── Per-request behaviour ──
+
+    #[test]
+    fn chance_blend_changes_the_ranking() {
+        let _g = fresh();
+        // Four distinct ranks keep the adaptive depth at 2
+        let board = [
+            2, 16, 0, 2,
+            8, 16, 4, 0,
+            0, 0, 8, 16,
+            16, 16, 0, 0,
+        ];
+        let mean = ranked(&board, 2);
+        set_chance_blend(1.0);
+        let worst = ranked(&board, 2);
+        assert_eq!((mean[0].1, worst[0].1), (2, 1), "expected left on average, down against the worst spawn");
+        // No move is worth more against its worst spawn than on average
+        for &(v, d) in &worst {
+            assert!(v <= mean.iter().find(|m| m.1 == d).unwrap().0);
+        }
+        set_chance_blend(7.0);
+        assert_eq!(config().chance_blend, 1.0, "blend not clamped");
+    }
+
+    #[test]
+    fn config_getters_read_back_defaults_and_settings() {
+        let _g = fresh();
+        let mut w = EvalWeights { empty: 0.0, ..DEFAULT_WEIGHTS };
+        get_eval_weights(&mut w);
+        let d = DEFAULT_WEIGHTS;
+        assert_eq!((w.lost, w.empty, w.merges, w.mono, w.sum), (d.lost, d.empty, d.merges, d.mono, d.sum));
+        assert_eq!(get_spawn_probability(), 0.9);
+        assert_eq!(get_max_chance_cells(), 16);
+        assert_eq!(get_win_target(), 2048);
+
+        let custom = EvalWeights { empty: 123.0, mono: 4.5, ..DEFAULT_WEIGHTS };
+        set_eval_weights(&custom);
+        set_spawn_probability(0.75);
+        set_max_chance_cells(6);
+        set_win_target(4096);
+        get_eval_weights(&mut w);
+        assert_eq!((w.empty, w.mono), (123.0, 4.5));
+        assert_eq!(get_spawn_probability(), 0.75);
+        assert_eq!(get_max_chance_cells(), 6);
+        assert_eq!(get_win_target(), 4096);
+
+        // Out-of-range values are clamped or ignored
+        set_spawn_probability(f64::NAN);
+        set_max_chance_cells(99);
+        set_win_target(3000);
+        assert_eq!(get_spawn_probability(), 0.75);
+        assert_eq!(get_max_chance_cells(), 16);
+        assert_eq!(get_win_target(), 4096);
+    }
+
+    #[test]
+    fn cascade_bias_keeps_a_ready_snake() {
+        let _g = fresh();
+        // Moving left merges the 4s on top into an 8, lining up 32, 16, 8, 8
+        // in the left column to roll up in a run of merges; moving down
+        // scores a little better unbiased
+        let snake = [
+            0, 4, 0, 4,
+            0, 8, 16, 0,
+            16, 4, 8, 0,
+            32, 16, 0, 0,
+        ];
+        let plain = ranked(&snake, 1);
+        set_cascade_bias(true);
+        assert!(get_cascade_bias());
+        let biased = ranked(&snake, 1);
+        assert_eq!((plain[0].1, biased[0].1), (1, 2), "expected down unbiased, left biased");
+        let down = |r: &[(f64, u8)]| r.iter().find(|m| m.1 == 1).unwrap().0;
+        assert_eq!(down(&plain), down(&biased), "a non-cascade move was rescored");
+    }
+
+    /// A board of raw tile values, read as the C ABI reads it
+    fn board(tiles: &[u16; 16]) -> BB {
+        board_arg(tiles.as_ptr()).expect("malformed test board")
+    }
+
+    #[test]
+    fn corner_softening_eases_a_forced_detour() {
+        let _g = fresh();
+        // Only a move right is legal, and it pulls the 2048 off its corner;
+        // a move left then puts it back
+        let forced = [
+            2048, 4, 0, 0,
+            8, 2, 0, 0,
+            4, 8, 0, 0,
+            2, 4, 0, 0,
+        ];
+        let after = do_move(board(&forced), Direction::Right).0;
+        assert!(corner_recoverable(after, &CORNERS));
+        assert_eq!(corner_term(after, 0.25, &CORNERS), 0.75 * corner_term(after, 0.0, &CORNERS));
+        // From the interior no single move reaches a corner, so no softening
+        let interior = board(&[0, 0, 0, 0, 0, 2048, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(corner_term(interior, 0.25, &CORNERS), corner_term(interior, 0.0, &CORNERS));
+
+        let mut values = Vec::new();
+        for soften in [0.0, 0.5] {
+            set_eval_weights(&EvalWeights { corner: 1.0, corner_soften: soften, ..DEFAULT_WEIGHTS });
+            let moves = ranked(&forced, 1);
+            assert_eq!(directions(&moves), [Direction::Right as u8]);
+            values.push(moves[0].0);
+        }
+        assert!(values[1] > values[0], "softening did not raise the forced move: {values:?}");
+    }
+
+    #[test]
+    fn stream_callback_sees_every_move_and_can_stop_the_game() {
+        let _g = fresh();
+        struct Trace { moves: u32, stop_at: u32, score: f64 }
+        extern "C" fn on_move(user: *mut c_void, board: *const u16, dir: u8, score: f64) -> i32 {
+            let t = unsafe { &mut *(user as *mut Trace) };
+            assert!(dir < 4 && score >= t.score);
+            assert!(board_arg(board).is_some());
+            t.moves += 1;
+            t.score = score;
+            (t.moves == t.stop_at) as i32
+        }
+        let mut trace = Trace { moves: 0, stop_at: 5, score: 0.0 };
+        let played = play_game_stream(3, 0, Some(on_move), &mut trace as *mut Trace as *mut c_void);
+        assert_eq!((played, trace.moves), (5, 5));
+
+        // Left alone, the same game runs on to its end
+        let mut trace = Trace { moves: 0, stop_at: 0, score: 0.0 };
+        set_win_target(64);
+        let played = play_game_stream(3, 0, Some(on_move), &mut trace as *mut Trace as *mut c_void);
+        assert!(played > 5 && played == trace.moves);
+    }
+
+    #[test]
+    fn depth_zero_and_one_node_counts() {
+        let _g = fresh();
+        set_max_depth(1);
+        let b = board(&MIDGAME);
+
+        // Depth 0: each root move is the static evaluation of its board
+        let moves = ranked(&MIDGAME, 0);
+        let legal = moves.len() as u64;
+        let st = STATS.with(|s| *s.borrow());
+        assert_eq!((st.depth, st.move_nodes, st.chance_nodes, st.evals), (0, 0, 0, legal));
+        for &(v, d) in &moves {
+            assert_eq!(v, evaluate(do_move(b, Direction::ALL[d as usize]).0));
+        }
+
+        // Depth 1: one chance node per root move, one reply move per spawn
+        ranked(&MIDGAME, 1);
+        let st = STATS.with(|s| *s.borrow());
+        let spawns: u64 = Direction::ALL.iter()
+            .map(|&d| do_move(b, d))
+            .filter(|m| m.2)
+            .map(|m| 2 * count_empty(m.0) as u64)
+            .sum();
+        assert_eq!((st.depth, st.chance_nodes, st.move_nodes), (1, legal, spawns));
+    }
+
+    #[test]
+    fn step_leaves_the_board_alone_on_an_illegal_move() {
+        let _g = fresh();
+        // Nothing can move left
+        let tiles = [2, 0, 0, 0, 4, 0, 0, 0, 8, 2, 0, 0, 0, 0, 0, 0];
+        let (mut out, mut score) = ([7u16; 16], 1.0);
+        assert_eq!(step(tiles.as_ptr(), Direction::Left as u8, 1, out.as_mut_ptr(), &mut score), TURN_ILLEGAL);
+        assert_eq!((out, score, last_error()), (tiles, 0.0, 0));
+
+        // A legal move slides, then adds exactly one spawned tile
+        assert_eq!(step(tiles.as_ptr(), Direction::Right as u8, 1, out.as_mut_ptr(), &mut score), TURN_SPAWNED);
+        let moved = board_tiles(do_move(board(&tiles), Direction::Right).0);
+        let diff: Vec<usize> = (0..16).filter(|&i| out[i] != moved[i]).collect();
+        assert_eq!(diff.len(), 1);
+        assert!(moved[diff[0]] == 0 && (out[diff[0]] == 2 || out[diff[0]] == 4));
+        assert_eq!(score, 0.0);
+
+        assert_eq!(step(tiles.as_ptr(), 4, 1, out.as_mut_ptr(), &mut score), TURN_ILLEGAL);
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    /// N-tuple blob for `tuples`, every weight 0 except `set` (tuple,
+    /// index, weight)
+    fn ntuple_blob(tuples: &[&[u8]], set: &[(usize, usize, f32)]) -> Vec<u8> {
+        let mut blob = (tuples.len() as u32).to_le_bytes().to_vec();
+        for t in tuples {
+            blob.extend((t.len() as u32).to_le_bytes());
+            blob.extend(*t);
+        }
+        for (k, t) in tuples.iter().enumerate() {
+            for idx in 0..1usize << (4 * t.len()) {
+                let w = set.iter().find(|s| s.0 == k && s.1 == idx).map_or(0.0, |s| s.2);
+                blob.extend(w.to_le_bytes());
+            }
+        }
+        blob
+    }
+
+    #[test]
+    fn ntuple_network_replaces_the_heuristic() {
+        let _g = fresh();
+        let heuristic = evaluate_board(MIDGAME.as_ptr());
+        let zero = ntuple_blob(&[&[0, 1], &[4, 5, 6]], &[]);
+        assert_eq!(load_ntuple(zero.as_ptr(), zero.len()), 2);
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), 0.0);
+
+        // MIDGAME holds 2 and 8 in cells 0 and 1: ranks 1 and 3
+        let one = ntuple_blob(&[&[0, 1]], &[(0, 1 | 3 << 4, 1.5)]);
+        assert_eq!(load_ntuple(one.as_ptr(), one.len()), 1);
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), 1.5);
+        // A truncated blob is refused and the loaded network stays
+        assert_eq!(load_ntuple(one.as_ptr(), one.len() - 1), 0);
+        assert_eq!(last_error(), ErrorCode::MalformedData as u32);
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), 1.5);
+
+        assert_eq!(load_ntuple(std::ptr::null(), 0), 0);
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), heuristic);
+    }
+
+    #[test]
+    fn direction_names_follow_the_abi_codes() {
+        for (code, name) in [(0, "up"), (1, "down"), (2, "left"), (3, "right")] {
+            let p = direction_name(code);
+            assert_eq!(unsafe { std::ffi::CStr::from_ptr(p) }.to_str().unwrap(), name);
+            assert_eq!(Direction::ALL[code as usize] as u8, code);
+        }
+        assert!(direction_name(4).is_null());
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    #[test]
+    fn endgame_profile_changes_only_crowded_choices() {
+        let _g = fresh();
+        let crowded = [
+            0, 4, 4, 16,
+            4, 2, 16, 4,
+            4, 16, 8, 16,
+            16, 2, 4, 2,
+        ];
+        let before = (ranked(&crowded, 1), ranked(&OPENING, 1));
+        set_endgame_threshold(4);
+        let after = (ranked(&crowded, 1), ranked(&OPENING, 1));
+        assert_eq!((before.0[0].1, after.0[0].1), (Direction::Up as u8, Direction::Left as u8));
+        assert_eq!(before.1, after.1, "the survival profile reached an open board");
+    }
+
+    #[test]
+    fn over_deep_searches_are_refused() {
+        let _g = fresh();
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        assert_eq!(search_ranked_moves(OPENING.as_ptr(), 100, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::InvalidDepth as u32);
+        assert_eq!(get_max_depth(), 16);
+        set_max_depth(1000);
+        assert_eq!(get_max_depth(), MAX_DEPTH_LIMIT);
+        set_max_depth(2);
+        assert_eq!(search_ranked_moves(OPENING.as_ptr(), 3, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::InvalidDepth as u32);
+        assert_eq!(dirs, [0; 4], "a refused search leaves the buffers alone");
+        // Adaptive deepening stays within the cap
+        assert!(search_ranked_moves(MIDGAME.as_ptr(), 1, scores.as_mut_ptr(), dirs.as_mut_ptr()) > 0);
+        assert_eq!(STATS.with(|s| s.borrow().depth), 2);
+    }
+
+    #[test]
+    fn margin_separates_clear_and_close_decisions() {
+        let _g = fresh();
+        set_max_depth(2);
+        // Left opens the board; up, the only other move, leaves it nearly full
+        let dominant = [
+            0, 16, 4, 8,
+            64, 32, 8, 128,
+            16, 8, 64, 16,
+            4, 256, 32, 64,
+        ];
+        let best = ranked(&dominant, 1)[0];
+        assert_eq!(best.1, Direction::Left as u8);
+        assert!(get_move_margin() > 0.5 * best.0, "margin {}", get_move_margin());
+
+        // Up and down, the best moves, mirror each other
+        let symmetric = [0, 0, 0, 0, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let best = ranked(&symmetric, 1)[0];
+        assert!(get_move_margin() < 1e-9 * best.0, "margin {}", get_move_margin());
+
+        let single = [2048, 4, 0, 0, 8, 16, 0, 0, 4, 2, 0, 0, 2, 8, 0, 0];
+        ranked(&single, 1);
+        assert_eq!(get_move_margin(), f64::INFINITY);
+        let locked = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 2, 4, 4, 2, 4, 2];
+        assert!(ranked(&locked, 1).is_empty());
+        assert_eq!(get_move_margin(), 0.0);
+    }
+
+    #[test]
+    fn preferred_corner_steers_the_max_tile() {
+        let _g = fresh();
+        set_max_depth(2);
+        set_eval_weights(&EvalWeights { corner: 1.0, ..DEFAULT_WEIGHTS });
+        // Left takes the 2048 to the top-left corner, right to the top-right
+        let edge = [
+            0, 2048, 0, 0,
+            0, 2, 0, 0,
+            0, 0, 4, 0,
+            0, 0, 0, 0,
+        ];
+        let picks: Vec<u8> = [0, 1].iter().map(|&c| {
+            set_preferred_corner(c);
+            ranked(&edge, 1)[0].1
+        }).collect();
+        assert_eq!(picks, [Direction::Left as u8, Direction::Right as u8]);
+
+        // Only the preferred corner earns the bonus
+        let top_right = do_move(board(&edge), Direction::Right).0;
+        assert!(corner_term(top_right, 0.0, anchor_corners(CORNER_ANY)) > 0.0);
+        assert!(corner_term(top_right, 0.0, anchor_corners(0)) < 0.0);
+        set_preferred_corner(9);
+        assert_eq!(get_preferred_corner(), CORNER_ANY);
+    }
+
+    #[test]
+    fn benchmark_reports_positive_throughput() {
+        let _g = fresh();
+        set_max_depth(2);
+        let rate = benchmark(1, 3);
+        assert!(rate.is_finite() && rate > 0.0, "{rate}");
+        // Every iteration starts cold, so each searches as many nodes
+        let nodes = || STATS.with(|s| { let s = s.borrow(); s.move_nodes + s.chance_nodes });
+        let last = nodes();
+        search_root(board(&BENCH_BOARD), 1);
+        assert_eq!(nodes(), last);
+    }
+
+    #[test]
+    fn trapped_tile_is_penalised() {
+        let _g = fresh();
+        // The 256 is wedged between the 512, the 1024 and a 2
+        let mut wedged = [
+            512, 256, 1024, 0,
+            0, 2, 0, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+        ];
+        assert_eq!(trapped_term(board(&wedged)), 64.0);
+        let plain = evaluate_board(wedged.as_ptr());
+        set_eval_weights(&EvalWeights { trapped: 10.0, ..DEFAULT_WEIGHTS });
+        assert_eq!(evaluate_board(wedged.as_ptr()), plain - 640.0);
+
+        // With the 2 gone it can slide out
+        wedged[5] = 0;
+        assert_eq!(trapped_term(board(&wedged)), 0.0);
+    }
+
+    #[test]
+    fn background_search_polls_to_completion_and_cancels_promptly() {
+        use std::time::Duration;
+        let _g = fresh();
+        set_max_depth(2);
+        let expect = ranked(&MIDGAME, 1);
+        let handle = start_search(MIDGAME.as_ptr(), 1);
+        assert!(!handle.is_null());
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        let n = loop {
+            let n = poll_search(handle, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            if n >= 0 { break n as usize; }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!((0..n).map(|i| (scores[i], dirs[i])).collect::<Vec<_>>(), expect);
+
+        // Far too deep to finish in a debug build, yet cancelled at once
+        set_max_depth(12);
+        let handle = start_search(MIDGAME.as_ptr(), 12);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(poll_search(handle, scores.as_mut_ptr(), dirs.as_mut_ptr()), -1);
+        let start = Instant::now();
+        cancel_search(handle);
+        assert!(start.elapsed() < Duration::from_millis(500), "cancel took {:?}", start.elapsed());
+
+        assert!(start_search(MIDGAME.as_ptr(), 13).is_null());
+        assert_eq!(last_error(), ErrorCode::InvalidDepth as u32);
+    }
+
+    #[test]
+    fn search_and_apply_takes_a_whole_turn() {
+        let _g = fresh();
+        set_max_depth(2);
+        let (mut out, mut dir, mut score) = ([0u16; 16], 0u8, -1.0);
+        let status = search_and_apply(MIDGAME.as_ptr(), 1, 42, out.as_mut_ptr(), &mut dir, &mut score);
+        // Right closes the gaps of the bottom rows, then seed 42 spawns a 2
+        // in the bottom-left corner
+        let expect = [
+            2, 8, 32, 512,
+            4, 16, 64, 256,
+            0, 2, 8, 128,
+            2, 0, 0, 4,
+        ];
+        assert_eq!((status, out, dir, score), (TURN_SPAWNED, expect, Direction::Right as u8, 0.0));
+        assert_eq!(dir, ranked(&MIDGAME, 1)[0].1);
+        let mut stepped = [0u16; 16];
+        step(MIDGAME.as_ptr(), dir, 42, stepped.as_mut_ptr(), std::ptr::null_mut());
+        assert_eq!(stepped, out);
+
+        let locked = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 2, 4, 4, 2, 4, 2];
+        let status = search_and_apply(locked.as_ptr(), 1, 42, out.as_mut_ptr(), &mut dir, &mut score);
+        assert_eq!((status, out, dir), (TURN_ILLEGAL, locked, 0xFF));
+        assert_eq!(search_and_apply(MIDGAME.as_ptr(), 3, 42, out.as_mut_ptr(), &mut dir, &mut score), TURN_ERR_DEPTH);
+    }
+
+    #[test]
+    fn array_tt_packs_two_entries_per_line_and_ranks_alike() {
+        let _g = fresh();
+        set_max_depth(2);
+        assert_eq!((std::mem::size_of::<TtEntry>(), std::mem::align_of::<TtEntry>()), (32, 32));
+        let map = rank_boards();
+        set_tt_array(3);
+        assert_eq!(get_tt_array(), 10);
+        let array = rank_boards();
+        assert_eq!(TT.with(|t| t.borrow().array_bits()), 10);
+        // Collisions only cost work: every entry is checked against its key
+        assert_eq!(map, array);
+    }
+
+    #[test]
+    fn dual_evaluation_matches_each_profile() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { corner: 1.0, snake: 0.5, ..DEFAULT_WEIGHTS });
+        for tiles in [MIDGAME, OPENING, CROWDED] {
+            let (mut build, mut survive) = (0.0, 0.0);
+            evaluate_dual(tiles.as_ptr(), &mut build, &mut survive);
+            set_endgame_threshold(0);
+            assert_eq!(build, evaluate_board(tiles.as_ptr()));
+            // Every board here has fewer than 16 empties: all survival
+            set_endgame_threshold(16);
+            assert_eq!(survive, evaluate_board(tiles.as_ptr()));
+            assert_ne!(build, survive);
+        }
+    }
+
+    #[test]
+    fn search_seed_reproduces_sampled_searches() {
+        let _g = fresh();
+        set_max_depth(2);
+        // Capped chance nodes on a sparse board: many equally crowded cells
+        set_max_chance_cells(2);
+        assert_eq!(ranked(&OPENING, 2), ranked(&OPENING, 2), "unseeded search is not deterministic");
+        let by_seed: Vec<Vec<(f64, u8)>> = (0..4).map(|seed| {
+            set_search_seed(seed);
+            let first = ranked(&OPENING, 2);
+            assert_eq!(ranked(&OPENING, 2), first, "seed {seed} did not reproduce");
+            first
+        }).collect();
+        assert!(by_seed.iter().any(|r| *r != by_seed[0]), "the seed never changed the sampled cells");
+        clear_search_seed();
+        assert_eq!(config().search_seed, None);
+    }
+
+    #[test]
+    fn best_line_boards_follow_its_directions() {
+        let _g = fresh();
+        set_max_depth(2);
+        let start = [2, 2, 4, 8, 0, 4, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0];
+        let (mut boards, mut dirs) = ([0u16; 16 * 6], [0u8; 6]);
+        assert_eq!(best_line(start.as_ptr(), 1, 6, boards.as_mut_ptr(), dirs.as_mut_ptr()), 6);
+        let mut b = board(&start);
+        for (k, &d) in dirs.iter().enumerate() {
+            let (nb, _, moved) = do_move(b, Direction::ALL[d as usize]);
+            assert!(moved);
+            assert_eq!(boards[k * 16..(k + 1) * 16], board_tiles(nb), "step {k}");
+            b = nb;
+        }
+        // The first step is the searched best move
+        assert_eq!(dirs[0], ranked(&start, 1)[0].1);
+
+        let locked = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 2, 4, 4, 2, 4, 2];
+        assert_eq!(best_line(locked.as_ptr(), 1, 6, boards.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+    }
+
+    /// Pairwise scatter penalty: every high tile against every other
+    fn scatter_pairwise(b: BB, reach: f64, falloff: f64) -> f64 {
+        let rank = |i: usize| ((b >> (i * 4)) & 0xF) as u8;
+        (0..16)
+            .filter(|&i| rank(i) >= SCATTER_MIN_RANK)
+            .filter_map(|i| {
+                let d = (0..16)
+                    .filter(|&j| j != i && rank(j) == rank(i))
+                    .map(|j| (i / 4).abs_diff(j / 4) + (i % 4).abs_diff(j % 4))
+                    .min()? as f64;
+                let scale = if falloff == 0.0 { 1.0 } else { (d / SCATTER_MAX_DIST).powf(falloff) };
+                (d > reach).then(|| scale * (rank(i) as f64).powi(2))
+            })
+            .sum()
+    }
+
+    #[test]
+    fn bucketed_scatter_matches_the_pairwise_scan() {
+        let _g = fresh();
+        // Two 64s in opposite corners; the 128s touch
+        let b = board(&[64, 128, 0, 0, 0, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 64]);
+        assert_eq!(scatter_term(b, 1.0, 0.0), 2.0 * 36.0);
+        let mut rng = Rng(124);
+        for _ in 0..500 {
+            // Dense with duplicates of 64–512
+            let b = (0..16).fold(0, |b, i| b | (rng.below(4) as u64 + 6) << (i * 4));
+            for (reach, falloff) in [(1.0, 0.0), (2.0, 0.0), (1.0, 1.5)] {
+                let (fast, slow) = (scatter_term(b, reach, falloff), scatter_pairwise(b, reach, falloff));
+                // Same terms, summed per bucket rather than per tile
+                assert!((fast - slow).abs() <= 1e-12 * slow, "{b:016x}: {fast} vs {slow}");
+            }
+        }
+    }
+
+    #[test]
+    fn decision_log_keeps_the_latest_searches_in_order() {
+        let _g = fresh();
+        set_max_depth(2);
+        let boards = [MIDGAME, OPENING, CROWDED, BENCH_BOARD, OPENING];
+        ranked(&MIDGAME, 0);
+        set_decision_log(3);
+        let rankings: Vec<Vec<(f64, u8)>> = boards.iter().map(|b| ranked(b, 0)).collect();
+        let mut out = vec![decision_log()[0]; 5];
+        assert_eq!(get_decision_log(out.as_mut_ptr(), 5), 3);
+        for (rec, (b, r)) in out.iter().zip(boards.iter().zip(&rankings).skip(2)) {
+            assert_eq!(rec.board, *b);
+            let n = rec.count as usize;
+            assert_eq!((0..n).map(|i| (rec.scores[i], rec.dirs[i])).collect::<Vec<_>>(), *r);
+        }
+        // A smaller buffer gets the newest entries
+        assert_eq!(get_decision_log(out.as_mut_ptr(), 1), 1);
+        assert_eq!(out[0].board, OPENING);
+
+        set_decision_log(0);
+        ranked(&MIDGAME, 0);
+        assert_eq!(get_decision_log(out.as_mut_ptr(), 5), 0);
+    }
+
+    #[test]
+    fn corner_term_waits_for_the_min_tile() {
+        let _g = fresh();
+        let small = [0, 0, 0, 0, 0, 8, 2, 0, 0, 0, 4, 0, 0, 0, 0, 0];
+        let large = [0, 0, 0, 0, 0, 128, 2, 0, 0, 0, 4, 0, 0, 0, 0, 0];
+        let plain = (evaluate_board(small.as_ptr()), evaluate_board(large.as_ptr()));
+        set_eval_weights(&EvalWeights { corner: 1.0, ..DEFAULT_WEIGHTS });
+        assert!(evaluate_board(small.as_ptr()) < plain.0);
+        set_eval_weights(&EvalWeights { corner: 1.0, corner_min_tile: 64.0, ..DEFAULT_WEIGHTS });
+        assert_eq!(evaluate_board(small.as_ptr()), plain.0);
+        assert!(evaluate_board(large.as_ptr()) < plain.1);
+    }
+
+    #[test]
+    fn moves_to_target_counts_spawn_free_moves() {
+        let _g = fresh();
+        let one = [1024, 1024, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(moves_to_target(one.as_ptr(), 2048, 5), 1);
+        assert_eq!(moves_to_target(one.as_ptr(), 1024, 5), 0);
+        // Two 256s and a 512: the 256s merge, then the 512s
+        let two = [256, 0, 0, 256, 0, 0, 0, 0, 0, 0, 0, 0, 512, 0, 0, 0];
+        assert_eq!(moves_to_target(two.as_ptr(), 1024, 5), 2);
+        assert_eq!(moves_to_target(two.as_ptr(), 1024, 1), -1);
+        assert_eq!(moves_to_target(two.as_ptr(), 2048, 8), -1);
+        assert_eq!(moves_to_target(two.as_ptr(), 3, 5), -1);
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    #[test]
+    fn cleared_snake_bit_drops_the_snake_term() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { snake: 0.5, ..DEFAULT_WEIGHTS });
+        let on = evaluate_board(MIDGAME.as_ptr());
+        set_eval_term_mask(TERM_ALL & !TERM_SNAKE);
+        assert_eq!(get_eval_term_mask(), TERM_ALL & !TERM_SNAKE);
+        let off = evaluate_board(MIDGAME.as_ptr());
+        let mut terms = [1.0; NUM_TERMS as usize];
+        assert_eq!(evaluate_components(MIDGAME.as_ptr(), terms.as_mut_ptr(), NUM_TERMS), NUM_TERMS);
+        assert_eq!(terms[TERM_SNAKE.trailing_zeros() as usize], 0.0);
+        // Same as a zero snake weight, and the stored weight survives the mask
+        set_eval_weights(&EvalWeights { snake: 0.0, ..DEFAULT_WEIGHTS });
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), off);
+        set_eval_weights(&EvalWeights { snake: 0.5, ..DEFAULT_WEIGHTS });
+        set_eval_term_mask(TERM_ALL);
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), on);
+        assert_ne!(on, off);
+    }
+
+    #[test]
+    fn board_terms_sum_the_component_values() {
+        let _g = fresh();
+        let all = EvalWeights {
+            corner: 1.0, trapped: 2.0, scatter: 3.0, snake: 0.5, chain: 4.0, grip: 5.0, anchor: 6.0,
+            breathing: 7.0, ..DEFAULT_WEIGHTS
+        };
+        for tiles in [OPENING, MIDGAME, CROWDED] {
+            let b = board(&tiles);
+            assert_eq!(board_terms(b, &DEFAULT_WEIGHTS, &DEFAULT_CONFIG), 0.0);
+            let values = board_term_values(b, &all, &DEFAULT_CONFIG);
+            assert!(values.iter().any(|&v| v != 0.0));
+            assert_eq!(board_terms(b, &all, &DEFAULT_CONFIG), values.iter().sum::<f64>());
+        }
+    }
+
+    #[test]
+    fn default_spawn_odds_score_as_the_baseline_engine() {
+        let _g = fresh();
+        assert_eq!(config().spawn.outcomes().collect::<Vec<_>>(), [(1, 0.9), (2, 0.1)]);
+        // The score the engine gave before spawn distributions existed; a 4
+        // weighted 1 − 0.9 = 0.09999999999999998 gives 1601398.422
+        let tiles = [
+            2, 0, 0, 2,
+            4, 8, 0, 0,
+            16, 0, 2, 0,
+            0, 0, 0, 4,
+        ];
+        let best = ranked(&tiles, 4)[0].0;
+        assert!((best - 1601393.535).abs() < 1e-3, "{best}");
+    }
+
+    #[test]
+    fn custom_spawn_distribution_changes_the_chance_expectation() {
+        let _g = fresh();
+        set_max_depth(2);
+        let standard = ranked(&OPENING, 1);
+        assert!(set_spawn_distribution([4u16].as_ptr(), [1.0].as_ptr(), 1));
+        let fours = ranked(&OPENING, 1);
+        assert_eq!((standard[0].1, fours[0].1), (Direction::Down as u8, Direction::Up as u8));
+        assert_eq!(get_spawn_probability(), 0.0);
+
+        let (mut values, mut probs) = ([0u16; 4], [0.0; 4]);
+        assert!(set_spawn_distribution([2u16, 8].as_ptr(), [0.25, 0.75].as_ptr(), 2));
+        assert_eq!(get_spawn_distribution(values.as_mut_ptr(), probs.as_mut_ptr()), 2);
+        assert_eq!((values[..2].to_vec(), probs[..2].to_vec()), (vec![2, 8], vec![0.25, 0.75]));
+        // Bad sums, non-powers of two and repeats change nothing
+        for (v, p) in [([2u16, 4], [0.5, 0.4]), ([3, 6], [0.9, 0.1]), ([2, 2], [0.9, 0.1])] {
+            assert!(!set_spawn_distribution(v.as_ptr(), p.as_ptr(), 2));
+            assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+        }
+        assert_eq!(get_spawn_distribution(values.as_mut_ptr(), probs.as_mut_ptr()), 2);
+        assert_eq!(values[1], 8);
+        assert!(set_spawn_distribution(std::ptr::null(), std::ptr::null(), 0));
+        assert_eq!(get_spawn_probability(), 0.9);
+        assert_eq!(ranked(&OPENING, 1), standard);
+    }
+
+    #[test]
+    fn evaluate_diff_of_a_board_with_itself_is_zero() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { corner: 1.0, snake: 0.5, trapped: 1.0, ..DEFAULT_WEIGHTS });
+        let n = NUM_TERMS as usize;
+        let mut diff = [1.0; NUM_TERMS as usize];
+        for b in [MIDGAME, OPENING, CROWDED] {
+            assert_eq!(evaluate_diff(b.as_ptr(), b.as_ptr(), diff.as_mut_ptr(), NUM_TERMS), NUM_TERMS);
+            assert_eq!(diff, [0.0; NUM_TERMS as usize]);
+        }
+        // Otherwise it is the components of A minus those of B, in the same order
+        let (mut a, mut b) = ([0.0; NUM_TERMS as usize], [0.0; NUM_TERMS as usize]);
+        evaluate_components(MIDGAME.as_ptr(), a.as_mut_ptr(), NUM_TERMS);
+        evaluate_components(CROWDED.as_ptr(), b.as_mut_ptr(), NUM_TERMS);
+        evaluate_diff(MIDGAME.as_ptr(), CROWDED.as_ptr(), diff.as_mut_ptr(), NUM_TERMS);
+        assert!((0..n).all(|i| diff[i] == a[i] - b[i]));
+        assert!(diff.iter().any(|&d| d != 0.0));
+        assert_eq!(evaluate_diff(MIDGAME.as_ptr(), CROWDED.as_ptr(), diff.as_mut_ptr(), 3), 3);
+    }
+
+    #[test]
+    fn aggression_moves_toward_the_biggest_merge() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [
+            512, 256, 128, 64,
+            8, 0, 0, 0,
+            8, 0, 0, 4,
+            2, 0, 0, 2,
+        ];
+        let patient = ranked(&b, 1);
+        assert_eq!(patient[0].1, Direction::Left as u8);
+        set_aggression(0.5);
+        assert_eq!(get_aggression(), 0.5);
+        let balanced = ranked(&b, 1);
+        // Up and Down merge the 8s for 16; Left and Right only the 2s
+        let merge = |d: u8| if d < 2 { 16.0 } else { 4.0 };
+        for &(score, d) in &balanced {
+            let value = patient.iter().find(|m| m.1 == d).unwrap().0;
+            assert!((score - (0.5 * value + 0.5 * merge(d))).abs() < 1e-6);
+        }
+        set_aggression(1.0);
+        let greedy = ranked(&b, 1);
+        assert!(greedy[0].1 < 2 && greedy[0].0 == 16.0, "{greedy:?}");
+        set_aggression(7.0);
+        assert_eq!(get_aggression(), 1.0);
+    }
+
+    #[test]
+    fn late_move_numbers_switch_to_the_survival_profile() {
+        let _g = fresh();
+        set_max_depth(2);
+        let crowded = [
+            0, 2, 32, 128,
+            8, 128, 16, 64,
+            64, 8, 0, 32,
+            8, 128, 512, 32,
+        ];
+        let at_move = |n: u32| {
+            let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+            let k = search_ranked_moves_with_context(crowded.as_ptr(), 1, n, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            (0..k as usize).map(|i| (scores[i], dirs[i])).collect::<Vec<_>>()
+        };
+        let plain = ranked(&crowded, 1);
+        assert_eq!(at_move(500), plain, "move numbers are ignored by default");
+        set_survival_from_move(100);
+        assert_eq!(at_move(99), plain);
+        assert_eq!(ranked(&crowded, 1), plain);
+        let late = at_move(150);
+        assert_eq!((plain[0].1, late[0].1), (Direction::Right as u8, Direction::Down as u8));
+        // Same as evaluating everything under the survival profile
+        set_survival_from_move(0);
+        set_endgame_threshold(16);
+        assert_eq!(ranked(&crowded, 1), late);
+    }
+
+    #[test]
+    fn config_changes_reach_the_log_callback() {
+        let _g = fresh();
+        static LINES: Mutex<Vec<(u32, String)>> = Mutex::new(Vec::new());
+        extern "C" fn sink(level: u32, msg: *const c_char) {
+            let text = unsafe { std::ffi::CStr::from_ptr(msg) }.to_string_lossy().into_owned();
+            LINES.lock().unwrap().push((level, text));
+        }
+        set_log_callback(Some(sink));
+        set_aggression(0.25);
+        set_max_chance_cells(6);
+        set_log_callback(None);
+        set_aggression(0.5);
+        let lines = std::mem::take(&mut *LINES.lock().unwrap());
+        assert_eq!(lines, [
+            (LOG_INFO, "config: aggression = 0.25".to_string()),
+            (LOG_INFO, "config: max_chance_cells = 6".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn softmax_scores_sum_to_one_and_keep_the_order() {
+        let _g = fresh();
+        set_max_depth(2);
+        for b in [MIDGAME, OPENING, CROWDED] {
+            let raw = ranked(&b, 1);
+            set_score_normalization(SCORES_SOFTMAX);
+            assert_eq!(get_score_normalization(), SCORES_SOFTMAX);
+            let soft = ranked(&b, 1);
+            set_score_normalization(SCORES_RAW);
+            assert_eq!(directions(&soft), directions(&raw));
+            assert!(soft.windows(2).all(|w| w[0].0 >= w[1].0), "{soft:?}");
+            assert!((soft.iter().map(|m| m.0).sum::<f64>() - 1.0).abs() < 1e-12);
+        }
+        set_score_normalization(7);
+        assert_eq!(get_score_normalization(), SCORES_RAW);
+    }
+
+    #[test]
+    fn snake_map_sums_to_the_snake_term() {
+        let _g = fresh();
+        let snake = TERM_SNAKE.trailing_zeros() as usize;
+        for power in [1.0, 0.5] {
+            set_eval_weights(&EvalWeights { snake: 0.5, snake_power: power, ..DEFAULT_WEIGHTS });
+            for b in [MIDGAME, OPENING, CROWDED] {
+                let mut cells = [0.0; 16];
+                let orientation = snake_contribution_map(b.as_ptr(), cells.as_mut_ptr(), 16);
+                assert!(orientation < 8);
+                let mut terms = [0.0; NUM_TERMS as usize];
+                evaluate_components(b.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+                let sum: f64 = cells.iter().sum();
+                assert!((0.5 * sum - terms[snake]).abs() <= 1e-9 * sum, "{sum} vs {}", terms[snake]);
+                // A pinned orientation is the one reported
+                assert!(pin_orientation(orientation as u8 ^ 1));
+                assert_eq!(snake_contribution_map(b.as_ptr(), cells.as_mut_ptr(), 16), orientation ^ 1);
+                unpin_orientation();
+            }
+        }
+    }
+
+    #[test]
+    fn every_setting_moves_the_fingerprint() {
+        let _g = fresh();
+        let base = config_fingerprint();
+        type Change = (&'static str, fn(), fn());
+        let changes: [Change; 14] = [
+            ("weights", || set_eval_weights(&EvalWeights { snake: 0.5, ..DEFAULT_WEIGHTS }), || set_eval_weights(std::ptr::null())),
+            ("survival weights", || set_survival_weights(&DEFAULT_WEIGHTS), || set_survival_weights(std::ptr::null())),
+            ("term mask", || set_eval_term_mask(TERM_ALL & !TERM_MONO), || set_eval_term_mask(TERM_ALL)),
+            ("spawn", || set_spawn_probability(0.8), || { set_spawn_distribution(std::ptr::null(), std::ptr::null(), 0); }),
+            ("spawn risk", || set_spawn_risk(2.0), || set_spawn_risk(1.0)),
+            ("chance cells", || set_max_chance_cells(6), || set_max_chance_cells(16)),
+            ("win target", || set_win_target(4096), || set_win_target(2048)),
+            ("aggression", || set_aggression(0.3), || set_aggression(0.0)),
+            ("normalization", || set_score_normalization(SCORES_MINMAX), || set_score_normalization(SCORES_RAW)),
+            ("max depth", || set_max_depth(6), || set_max_depth(16)),
+            ("corner", || set_preferred_corner(2), || set_preferred_corner(CORNER_ANY)),
+            ("tt array", || set_tt_array(12), || set_tt_array(0)),
+            ("search seed", || set_search_seed(7), || clear_search_seed()),
+            ("snake pin", || { pin_orientation(3); }, || unpin_orientation()),
+        ];
+        let mut seen = std::collections::HashSet::from([base]);
+        for (name, change, restore) in changes {
+            change();
+            assert!(seen.insert(config_fingerprint()), "{name} left the fingerprint unchanged or repeated");
+            restore();
+            assert_eq!(config_fingerprint(), base, "restoring {name}");
+        }
+    }
+
+    #[test]
+    fn best_first_agrees_with_expectimax_on_easy_positions() {
+        let _g = fresh();
+        set_max_depth(2);
+        let best_first = |b: &[u16; 16], budget: u32| {
+            let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+            let n = search_ranked_moves_best_first(b.as_ptr(), budget, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            (0..n as usize).map(|i| (scores[i], dirs[i])).collect::<Vec<_>>()
+        };
+        // With no budget past the root's moves it is the depth-0 search
+        let mut rng = Rng(141);
+        for _ in 0..50 {
+            let tiles = board_tiles(random_board(&mut rng));
+            assert_eq!(best_first(&tiles, 1), ranked(&tiles, 0));
+        }
+        for b in [MIDGAME, OPENING, CROWDED, BENCH_BOARD] {
+            let full = ranked(&b, 2);
+            let grown = best_first(&b, 3000);
+            assert_eq!(grown[0].1, full[0].1, "{grown:?} vs {full:?}");
+            assert_eq!(grown.len(), full.len());
+        }
+    }
+
+    #[test]
+    fn chance_cutoff_keeps_the_top_move() {
+        let _g = fresh();
+        set_max_depth(2);
+        let mut rng = Rng(142);
+        let (mut full_evals, mut cut_evals) = (0, 0);
+        for _ in 0..100 {
+            let mut b = [0u16; 16];
+            for c in &mut b {
+                if rng.below(2) == 0 { *c = 1 << (1 + rng.below(10)); }
+            }
+            let full = ranked(&b, 1);
+            full_evals += STATS.with(|s| s.borrow().evals);
+            set_chance_cutoff(true);
+            let cut = ranked(&b, 1);
+            cut_evals += STATS.with(|s| s.borrow().evals);
+            set_chance_cutoff(false);
+            if full.is_empty() { continue; }
+            assert_eq!(cut[0], full[0], "{b:?}");
+            assert!(cut[1..].iter().all(|m| m.0 <= cut[0].0));
+        }
+        assert!(cut_evals < full_evals, "{cut_evals} of {full_evals} evaluations");
+    }
+
+    #[test]
+    fn term_correlations_fill_the_stats() {
+        let _g = fresh();
+        set_win_target(128);
+        const N: usize = NUM_TERMS as usize;
+        let mut stats = TermStats { positions: 0, means: [f64::NAN; N], correlations: [[f64::NAN; N]; N] };
+        let positions = term_correlations(5, 2, 0, &mut stats);
+        assert!(positions > 20 && stats.positions == positions);
+        let (empty, mono, snake) = (0, TERM_MONO.trailing_zeros() as usize, TERM_SNAKE.trailing_zeros() as usize);
+        assert!(stats.means[empty] > 0.0 && stats.means[mono] < 0.0, "{:?}", stats.means);
+        // Zero-weight terms never vary, so correlate with nothing
+        assert_eq!(stats.means[snake], 0.0);
+        assert!(stats.correlations[snake].iter().all(|&c| c == 0.0));
+        for i in 0..N {
+            for j in 0..N {
+                let c = stats.correlations[i][j];
+                assert!((-1.0..=1.0).contains(&c) && c == stats.correlations[j][i]);
+            }
+        }
+        assert!((stats.correlations[empty][empty] - 1.0).abs() < 1e-9);
+        assert!((stats.correlations[mono][mono] - 1.0).abs() < 1e-9);
+        // A second run with the same seeds sees the same positions
+        let mut again = stats;
+        assert_eq!(term_correlations(5, 2, 0, &mut again), positions);
+        assert_eq!(again.means, stats.means);
+    }
+
+    #[test]
+    fn corner_safe_mode_keeps_the_max_tile_cornered() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [
+            512, 0, 2, 128,
+            0, 16, 32, 0,
+            0, 4, 0, 0,
+            0, 64, 0, 0,
+        ];
+        // Down scores best but takes the 512 out of its corner; Up trails by ~1.2%
+        let raw = ranked(&b, 1);
+        assert_eq!(directions(&raw)[..2], [Direction::Down as u8, Direction::Up as u8]);
+        assert_eq!(board_tiles(do_move(board(&b), Direction::Down).0)[12], 512);
+        set_corner_safe(0.01);
+        assert_eq!(ranked(&b, 1), raw, "Up is outside a 1% band");
+        set_corner_safe(0.02);
+        let safe = ranked(&b, 1);
+        assert_eq!(safe[..2], [raw[1], raw[0]]);
+        assert_eq!(safe[2..], raw[2..]);
+        assert_eq!(get_corner_safe(), 0.02);
+    }
+
+    #[test]
+    fn legal_move_count_matches_the_ranking() {
+        let _g = fresh();
+        let locked = [
+            2, 4, 2, 4,
+            4, 2, 4, 2,
+            2, 4, 2, 4,
+            4, 2, 4, 2,
+        ];
+        assert_eq!(legal_move_count(locked.as_ptr()), 0);
+        assert_eq!(legal_move_count(OPENING.as_ptr()), 4);
+        let mut rng = Rng(146);
+        for _ in 0..200 {
+            let tiles = board_tiles(random_board(&mut rng));
+            assert_eq!(legal_move_count(tiles.as_ptr()) as usize, ranked(&tiles, 0).len());
+        }
+        assert_eq!(legal_move_count(std::ptr::null()), 0);
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+    }
+
+    #[test]
+    fn relaxed_chain_credits_a_loose_descent() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { chain: 1.0, ..DEFAULT_WEIGHTS });
+        let chain = |tiles: &[u16; 16]| {
+            let mut terms = [0.0; NUM_TERMS as usize];
+            evaluate_components(tiles.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+            terms[TERM_CHAIN.trailing_zeros() as usize]
+        };
+        let loose = [1024, 256, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let exact = [1024, 512, 256, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let strict = (chain(&loose), chain(&exact));
+        assert!(!get_chain_relaxed());
+        set_chain_relaxed(true);
+        // Strict mode stops at the 1024 (rank 10); relaxed adds ranks 8 and 7
+        assert_eq!(chain(&loose) / strict.0, 25.0 / 10.0);
+        assert_eq!(chain(&exact), strict.1);
+    }
+
+    #[test]
+    fn null_pointers_set_the_error_code_and_message() {
+        let _g = fresh();
+        let message = || unsafe { std::ffi::CStr::from_ptr(last_error_message()) }.to_str().unwrap().to_owned();
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        assert_eq!(search_ranked_moves(std::ptr::null(), 0, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+        assert_eq!(message(), "null pointer argument");
+        assert_eq!(evaluate_components(MIDGAME.as_ptr(), std::ptr::null_mut(), NUM_TERMS), 0);
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+
+        // The next fallible call clears it
+        assert_eq!(legal_move_count(OPENING.as_ptr()), 4);
+        assert_eq!((last_error(), message()), (ErrorCode::Ok as u32, "ok".to_owned()));
+
+        let bad = [3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(search_ranked_moves(bad.as_ptr(), 0, scores.as_mut_ptr(), dirs.as_mut_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+        assert_eq!(message(), "board holds a value that is not a tile");
+    }
+
+    #[test]
+    fn batch_scores_match_single_evaluations() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { snake: 0.5, corner: 1.0, ..DEFAULT_WEIGHTS });
+        let mut rng = Rng(149);
+        let mut boards: Vec<u16> = [MIDGAME, OPENING, CROWDED].concat();
+        for _ in 0..20 { boards.extend(board_tiles(random_board(&mut rng))); }
+        boards[16 * 5] = 3;
+        let n = boards.len() / 16;
+        let mut scores = vec![0.0; n];
+        assert_eq!(evaluate_batch(boards.as_ptr(), n as u32, scores.as_mut_ptr()), n as u32);
+        assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+        for (i, tiles) in boards.chunks(16).enumerate() {
+            let single = evaluate_board(tiles.as_ptr());
+            assert!(scores[i] == single || (i == 5 && scores[i].is_nan() && single.is_nan()), "board {i}");
+        }
+    }
+
+    #[test]
+    fn crafted_boards_classify_as_expected() {
+        let _g = fresh();
+        let opening = [0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0];
+        let snake = [512, 256, 128, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let dying = [
+            4, 8, 4, 8,
+            8, 2048, 8, 4,
+            4, 8, 4, 8,
+            8, 4, 8, 0,
+        ];
+        assert_eq!(classify_position(opening.as_ptr()), POS_OPENING);
+        assert_eq!(classify_position(snake.as_ptr()), POS_MIDGAME | POS_CORNER_HELD | POS_SNAKE_ALIGNED);
+        assert_eq!(classify_position(dying.as_ptr()), POS_ENDGAME | POS_IN_DANGER);
+        // A scrambled head row is no snake
+        let broken = [512, 128, 256, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        assert_eq!(classify_position(broken.as_ptr()), POS_MIDGAME | POS_CORNER_HELD);
+        // Only the anchor corner counts as held
+        set_preferred_corner(3);
+        assert_eq!(classify_position(snake.as_ptr()) & POS_CORNER_HELD, 0);
+        assert_eq!(classify_position(std::ptr::null()), 0);
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+    }
+
+    #[test]
+    fn pinned_orientation_scores_only_that_snake() {
+        let _g = fresh();
+        set_eval_weights(&EvalWeights { snake: 0.5, ..DEFAULT_WEIGHTS });
+        let snake_term = |tiles: &[u16; 16]| {
+            let mut terms = [0.0; NUM_TERMS as usize];
+            evaluate_components(tiles.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+            terms[TERM_SNAKE.trailing_zeros() as usize]
+        };
+        for tiles in [MIDGAME, CROWDED] {
+            let sums = snake_sums(board(&tiles), 1.0);
+            let best = sums.iter().copied().fold(0.0, f64::max);
+            assert_eq!(snake_term(&tiles), 0.5 * best);
+            for (o, &sum) in sums.iter().enumerate() {
+                assert!(pin_orientation(o as u8));
+                assert_eq!(get_pinned_orientation(), o as u8);
+                assert_eq!(snake_term(&tiles), 0.5 * sum);
+            }
+            unpin_orientation();
+            assert_eq!(get_pinned_orientation(), 0xFF);
+            assert_eq!(snake_term(&tiles), 0.5 * best);
+        }
+        assert!(!pin_orientation(8));
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+        assert_eq!(get_pinned_orientation(), 0xFF);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_games_match_serial_games() {
+        let _g = fresh();
+        set_max_depth(1);
+        set_win_target(128);
+        let (mut serial, mut parallel) = (GameStats::default(), GameStats::default());
+        assert_eq!(play_games(152, 8, 0, &mut serial), 8);
+        assert_eq!(play_games_parallel(152, 8, 0, &mut parallel), 8);
+        let fields = |s: &GameStats| (s.games, s.wins, s.total_moves, s.total_score, s.best_score, s.max_tile_counts);
+        assert_eq!(fields(&parallel), fields(&serial));
+        assert!(serial.wins > 0 && serial.total_moves > 8 * 20);
+    }
+
+    #[test]
+    fn returned_board_is_the_best_move_applied() {
+        let _g = fresh();
+        set_max_depth(2);
+        let mut rng = Rng(153);
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        for _ in 0..30 {
+            let tiles = board_tiles(random_board(&mut rng));
+            let mut after = [1u16; 16];
+            let n = search_ranked_moves_with_board(tiles.as_ptr(), 1, scores.as_mut_ptr(), dirs.as_mut_ptr(), after.as_mut_ptr());
+            assert_eq!((0..n as usize).map(|i| (scores[i], dirs[i])).collect::<Vec<_>>(), ranked(&tiles, 1));
+            if n == 0 {
+                assert_eq!(after, [1; 16]);
+                continue;
+            }
+            assert_eq!(after, board_tiles(do_move(board(&tiles), Direction::ALL[dirs[0] as usize]).0));
+        }
+        let n = search_ranked_moves_with_board(MIDGAME.as_ptr(), 1, scores.as_mut_ptr(), dirs.as_mut_ptr(), std::ptr::null_mut());
+        assert!(n > 0 && last_error() == 0);
+    }
+
+    #[test]
+    fn merge_accumulation_changes_the_choice() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [
+            0, 0, 0, 0,
+            0, 0, 32, 512,
+            0, 0, 128, 4,
+            0, 128, 512, 0,
+        ];
+        let plain = ranked(&b, 1);
+        assert!(!get_merge_accumulation());
+        set_merge_accumulation(true);
+        let scored = ranked(&b, 1);
+        // Neither move merges, but the replies after Down bank more score
+        assert_eq!((plain[0].1, scored[0].1), (Direction::Right as u8, Direction::Down as u8));
+        for &(v, d) in &scored {
+            assert!(v > plain.iter().find(|m| m.1 == d).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn grip_rewards_the_second_tile_beside_the_corner() {
+        let _g = fresh();
+        let gripped = [1024, 512, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0];
+        let loose = [1024, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 512];
+        let off_corner = [0, 0, 0, 0, 0, 1024, 512, 0, 0, 0, 2, 0, 0, 0, 0, 0];
+        let plain = [gripped, loose, off_corner].map(|b| evaluate_board(b.as_ptr()));
+        set_eval_weights(&EvalWeights { grip: 100.0, ..DEFAULT_WEIGHTS });
+        let with = [gripped, loose, off_corner].map(|b| evaluate_board(b.as_ptr()));
+        // 512 is rank 9
+        assert_eq!(with[0], plain[0] + 900.0);
+        assert_eq!(with[1..], plain[1..]);
+    }
+
+    #[test]
+    fn unstick_mode_plays_for_mobility_near_death() {
+        let _g = fresh();
+        set_max_depth(1);
+        let b = [
+            4, 32, 64, 8,
+            4, 2, 16, 0,
+            64, 2, 16, 8,
+            16, 4, 32, 64,
+        ];
+        // Does some 2 or 4 spawned after this move leave no legal move?
+        let can_die = |d: Direction| {
+            let after = board_tiles(do_move(board(&b), d).0);
+            (0..16).filter(|&i| after[i] == 0).any(|i| {
+                [2, 4].iter().any(|&t| {
+                    let mut spawned = after;
+                    spawned[i] = t;
+                    legal_move_count(spawned.as_ptr()) == 0
+                })
+            })
+        };
+        let raw = ranked(&b, 0);
+        assert_eq!(raw[0].1, Direction::Right as u8);
+        assert!(can_die(Direction::Right));
+        set_unstick_threshold(1);
+        assert_eq!(ranked(&b, 0), raw, "one empty cell is not below 1");
+        set_unstick_threshold(2);
+        let unstuck = ranked(&b, 0);
+        assert_eq!(unstuck[0], raw[1]);
+        assert_eq!(unstuck[0].1, Direction::Down as u8);
+        assert!(!can_die(Direction::Down));
+    }
+
+    #[test]
+    fn decision_log_breaks_down_the_top_two_moves() {
+        let _g = fresh();
+        set_max_depth(2);
+        set_eval_weights(&EvalWeights { snake: 0.5, corner: 1.0, ..DEFAULT_WEIGHTS });
+        set_decision_log(1);
+        let moves = ranked(&CROWDED, 1);
+        let mut rec = [decision_log()[0]];
+        assert_eq!(get_decision_log(rec.as_mut_ptr(), 1), 1);
+        for (terms, &(_, d)) in rec[0].terms.iter().zip(&moves) {
+            let after = board_tiles(do_move(board(&CROWDED), Direction::ALL[d as usize]).0);
+            let mut expected = [0.0; NUM_TERMS as usize];
+            evaluate_components(after.as_ptr(), expected.as_mut_ptr(), NUM_TERMS);
+            assert_eq!(*terms, expected);
+        }
+        assert_ne!(rec[0].terms[0], rec[0].terms[1]);
+    }
+
+    #[test]
+    fn single_empty_cell_fast_path_matches_the_general_path() {
+        let _g = fresh();
+        set_max_depth(2);
+        // One empty cell: the chance node is the 0.9/0.1 mix of its two spawns
+        let b = board(&[
+            2, 8, 32, 512,
+            4, 16, 64, 256,
+            2, 4, 8, 128,
+            8, 2, 0, 4,
+        ]);
+        begin_search();
+        let two = score_move_node(b | (1 << (14 * 4)), 1, 0.9);
+        let four = score_move_node(b | (2 << (14 * 4)), 1, 0.1);
+        begin_search();
+        assert_eq!(score_chance_node(b, 1, 1.0), 0.9 * two + 0.1 * four);
+
+        // A chance selector that always defers to the built-in choice turns
+        // the fast path off without changing which cells are expanded; half
+        // the boards also go through the root cutoff
+        extern "C" fn defer(_: *const u16, _: *mut u8, _: u32) -> u32 { 0 }
+        let mut rng = Rng(160);
+        for k in 0..300 {
+            let mut tiles = board_tiles(random_board(&mut rng));
+            for (i, t) in tiles.iter_mut().enumerate() {
+                if *t == 0 && i % 16 != k % 16 { *t = 2; }
+            }
+            set_chance_cutoff(k % 2 == 1);
+            let fast = ranked(&tiles, 1);
+            set_chance_selector(Some(defer));
+            let general = ranked(&tiles, 1);
+            set_chance_selector(None);
+            assert_eq!(fast, general, "{tiles:?}");
+        }
+    }
+
+    #[test]
+    fn depth_bands_reuse_shallower_entries() {
+        let _g = fresh();
+        set_max_depth(5);
+        // A chance node searched at depth 2 serves a depth-3 probe only when
+        // both fall in one band
+        let b = board(&MIDGAME) | (1 << (9 * 4));
+        for (band, hit) in [(1, false), (2, true)] {
+            set_tt_depth_band(band);
+            begin_search();
+            score_chance_node(b, 2, 1.0);
+            reset_search_counters();
+            score_chance_node(b, 3, 1.0);
+            let st = STATS.with(|s| *s.borrow());
+            assert_eq!(st.tt_hits > 0 && st.chance_nodes == 0, hit, "band {band}: {} hits, {} nodes", st.tt_hits, st.chance_nodes);
+        }
+
+        // Over whole deep searches the banded table expands fewer chance
+        // nodes, and the chosen move stays put
+        for (tiles, depth) in [(OPENING, 4), (CROWDED, 3)] {
+            set_tt_depth_band(1);
+            let exact = (ranked(&tiles, depth), STATS.with(|s| s.borrow().chance_nodes));
+            set_tt_depth_band(2);
+            let banded = (ranked(&tiles, depth), STATS.with(|s| s.borrow().chance_nodes));
+            assert_eq!(banded.0[0].1, exact.0[0].1);
+            assert!(banded.1 < exact.1, "{} vs {} chance nodes", banded.1, exact.1);
+        }
+    }
+
+    #[test]
+    fn snakes_are_orderly_and_scattered_boards_are_not() {
+        let _g = fresh();
+        let snake = [
+            1024, 512, 256, 128,
+            8, 16, 32, 64,
+            4, 2, 0, 0,
+            0, 0, 0, 0,
+        ];
+        // Every row and column climbs and falls by the same amount
+        let scattered = [
+            2, 256, 256, 2,
+            256, 2, 2, 256,
+            256, 2, 2, 256,
+            2, 256, 256, 2,
+        ];
+        assert_eq!(board_entropy(snake.as_ptr()), 0.0);
+        assert_eq!(board_entropy(scattered.as_ptr()), 1.0);
+        assert!((0.0..=1.0).contains(&board_entropy(MIDGAME.as_ptr())));
+        assert!(board_entropy(MIDGAME.as_ptr()) < board_entropy(scattered.as_ptr()));
+        assert!(board_entropy([3; 16].as_ptr()).is_nan());
+    }
+
+    #[test]
+    fn locked_boards_return_no_moves_and_leave_the_buffers_alone() {
+        let _g = fresh();
+        set_max_depth(2);
+        let locked = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 2, 4, 4, 2, 4, 2];
+        let p = locked.as_ptr();
+        let (mut scores, mut dirs) = ([7.0; 4], [9u8; 4]);
+        let (s, d) = (scores.as_mut_ptr(), dirs.as_mut_ptr());
+        let mut after = [1u16; 16];
+        let searches: [(&str, u32); 8] = [
+            ("plain", search_ranked_moves(p, 2, s, d)),
+            ("context", search_ranked_moves_with_context(p, 2, 500, s, d)),
+            ("after", search_ranked_moves_after(p, 2, Direction::Left as u8, s, d)),
+            ("adversarial", search_ranked_moves_adversarial(p, 2, s, d)),
+            ("timed", search_ranked_moves_timed(p, 2, 50, s, d)),
+            ("split", search_ranked_moves_split(p, 2, s, d, std::ptr::null_mut())),
+            ("with board", search_ranked_moves_with_board(p, 2, s, d, after.as_mut_ptr())),
+            ("best first", search_ranked_moves_best_first(p, 100, s, d)),
+        ];
+        for (name, n) in searches {
+            assert_eq!(n, 0, "{name}");
+        }
+        assert_eq!(last_error(), 0);
+        assert_eq!((scores, dirs, after), ([7.0; 4], [9; 4], [1; 16]));
+        assert_eq!(get_move_margin(), 0.0);
+    }
+
+    #[test]
+    fn exploration_varies_moves_and_temperature_zero_does_not() {
+        let _g = fresh();
+        set_max_depth(2);
+        let draws = |n: usize| (0..n).map(|_| ranked(&MIDGAME, 1)[0].1).collect::<Vec<_>>();
+        let argmax = draws(10);
+        assert!(argmax.iter().all(|&d| d == argmax[0]));
+
+        set_exploration_temperature(10.0);
+        set_exploration_seed(3);
+        let explored = draws(40);
+        let mut counts = [0; 4];
+        for &d in &explored { counts[d as usize] += 1; }
+        assert!(counts.iter().filter(|&&c| c > 0).count() >= 3, "{counts:?}");
+        set_exploration_seed(3);
+        assert_eq!(draws(40), explored);
+
+        set_exploration_temperature(-1.0);
+        assert_eq!(get_exploration_temperature(), 0.0);
+        assert_eq!(draws(10), argmax);
+    }
+
+    #[test]
+    fn chance_selector_choices_are_respected() {
+        let _g = fresh();
+        set_max_depth(2);
+        static MAX_SEEN: AtomicU32 = AtomicU32::new(0);
+        fn first_empty(board: *const u16) -> u8 {
+            let tiles = unsafe { std::slice::from_raw_parts(board, 16) };
+            tiles.iter().position(|&t| t == 0).unwrap() as u8
+        }
+        extern "C" fn first(board: *const u16, out: *mut u8, max: u32) -> u32 {
+            MAX_SEEN.store(max, Ordering::Relaxed);
+            unsafe { *out = first_empty(board); }
+            1
+        }
+        // The same cell, padded with an occupied cell, a repeat and an index
+        // off the board, all of which are dropped
+        extern "C" fn padded(board: *const u16, out: *mut u8, _: u32) -> u32 {
+            let tiles = unsafe { std::slice::from_raw_parts(board, 16) };
+            let occupied = tiles.iter().position(|&t| t != 0).unwrap() as u8;
+            let cells = [occupied, first_empty(board), 99, first_empty(board)];
+            unsafe { std::ptr::copy_nonoverlapping(cells.as_ptr(), out, 4) };
+            4
+        }
+        extern "C" fn nothing_valid(_: *const u16, out: *mut u8, _: u32) -> u32 {
+            unsafe { *out = 16; }
+            1
+        }
+        let built_in = ranked(&MIDGAME, 1);
+        set_max_chance_cells(5);
+        set_chance_selector(Some(first));
+        let fixed = ranked(&MIDGAME, 1);
+        assert_eq!(MAX_SEEN.load(Ordering::Relaxed), 5);
+        set_max_chance_cells(16);
+        assert_ne!(fixed, built_in);
+        set_chance_selector(Some(padded));
+        assert_eq!(ranked(&MIDGAME, 1), fixed);
+        set_chance_selector(Some(nothing_valid));
+        assert_eq!(ranked(&MIDGAME, 1), built_in);
+    }
+
+    #[test]
+    fn variance_penalty_avoids_a_bad_four_spawn() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [
+            64, 32, 0, 0,
+            128, 0, 0, 128,
+            2, 256, 64, 32,
+            128, 0, 4, 2,
+        ];
+        // Value of the worst cell a 4 can land in after `d`, with the best reply
+        let worst_four = |d: Direction| {
+            let after = board_tiles(do_move(board(&b), d).0);
+            (0..16).filter(|&i| after[i] == 0)
+                .map(|i| {
+                    let mut spawned = after;
+                    spawned[i] = 4;
+                    ranked(&spawned, 0).first().map_or(0.0, |m| m.0)
+                })
+                .fold(f64::INFINITY, f64::min)
+        };
+        let raw = ranked(&b, 1);
+        assert_eq!(raw[0].1, Direction::Left as u8);
+        assert!(worst_four(Direction::Left) < 0.85 * worst_four(Direction::Up));
+        set_variance_penalty(1.0);
+        let steady = ranked(&b, 1);
+        assert_eq!(steady[0].1, Direction::Up as u8);
+        for &(v, d) in &steady {
+            assert!(v < raw.iter().find(|m| m.1 == d).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn depth_one_tree_has_a_chance_node_per_move() {
+        let _g = fresh();
+        set_max_depth(2);
+        let mut json: *mut c_char = std::ptr::null_mut();
+        let nodes = dump_search_tree(OPENING.as_ptr(), 1, &mut json);
+        let text = unsafe { std::ffi::CStr::from_ptr(json) }.to_str().unwrap().to_owned();
+        free_json(json);
+
+        // Root max node → one chance node per move → a max node per spawn
+        // (cell and tile) → one leaf per legal reply
+        let b = board(&OPENING);
+        let (mut spawns, mut leaves, mut best) = (0, 0, 0.0f64);
+        for d in Direction::ALL {
+            let after = do_move(b, d).0;
+            let empty: Vec<u64> = (0..16).filter(|&i| (after >> (i * 4)) & 0xF == 0).collect();
+            let mut mean = 0.0;
+            for &i in &empty {
+                for (rank, p) in [(1u64, 0.9), (2, 0.1)] {
+                    let spawned = after | (rank << (i * 4));
+                    spawns += 1;
+                    leaves += count_legal(spawned);
+                    let reply = Direction::ALL.iter().map(|&r| do_move(spawned, r))
+                        .filter(|m| m.2).map(|m| evaluate(m.0)).fold(0.0, f64::max);
+                    mean += p / empty.len() as f64 * reply;
+                }
+            }
+            best = best.max(mean);
+        }
+        let count = |s: &str| text.matches(s).count();
+        assert!(text.starts_with("{\"type\":\"max\""));
+        assert_eq!(count("\"type\":\"chance\""), 4);
+        assert_eq!(count("\"move\":"), 4 + leaves as usize);
+        assert_eq!((count("\"prob\":"), count("\"type\":\"max\"")), (spawns, 1 + spawns));
+        assert_eq!(count("\"type\":\"leaf\""), leaves as usize);
+        assert_eq!(nodes as usize, 1 + 4 + spawns + leaves as usize);
+        // The root is worth the best move's spawn-weighted best reply
+        let root: f64 = text.rsplit("\"value\":").next().unwrap().trim_end_matches('}').parse().unwrap();
+        assert!((root - best).abs() < 1e-9 * best, "{root} vs {best}");
+
+        assert_eq!(dump_search_tree(OPENING.as_ptr(), DUMP_MAX_DEPTH + 1, &mut json), -1);
+        assert!(json.is_null());
+        assert_eq!(last_error(), ErrorCode::InvalidDepth as u32);
+    }
+
+    #[test]
+    fn near_won_board_expects_more_than_a_fragile_one() {
+        let _g = fresh();
+        let near_won = [1024, 512, 256, 128, 0, 0, 0, 64, 0, 0, 0, 0, 2, 0, 0, 0];
+        let fragile = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 8, 16, 0, 8, 2, 4];
+        let won = expected_final_score(near_won.as_ptr(), 4, 5);
+        let lost = expected_final_score(fragile.as_ptr(), 4, 5);
+        assert!(won > lost, "{won} vs {lost}");
+        assert_eq!(expected_final_score(near_won.as_ptr(), 4, 5), won);
+
+        assert!(expected_final_score(near_won.as_ptr(), 0, 5).is_nan());
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    #[test]
+    fn contexts_keep_independent_transposition_tables() {
+        let _g = fresh();
+        set_max_depth(2);
+        let (a, b) = (search_context_new(), search_context_new());
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        let mut search = |ctx, tiles: &[u16; 16]| {
+            search_with_context(ctx, tiles.as_ptr(), 1, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            let mut stats = SearchStats::default();
+            search_context_stats(ctx, &mut stats);
+            stats.chance_nodes
+        };
+        let cold = search(a, &MIDGAME);
+        let filled = search_context_tt_entries(a);
+        assert!(filled > 0);
+        assert_eq!(search_context_tt_entries(b), 0);
+
+        // b's search leaves a's table alone, and a's repeat is served from it
+        search(b, &OPENING);
+        assert_eq!(search_context_tt_entries(a), filled);
+        assert!(search(a, &MIDGAME) < cold);
+        assert_eq!(search(b, &MIDGAME), cold);
+
+        let kept = search_context_tt_entries(b);
+        search_context_clear(a);
+        assert_eq!((search_context_tt_entries(a), search_context_tt_entries(b)), (0, kept));
+        search_context_free(a);
+        search_context_free(b);
+    }
+
+    #[test]
+    fn anchor_rewards_only_a_full_strictly_descending_row() {
+        let _g = fresh();
+        let perfect = [1024, 512, 256, 128, 2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let gapped = [1024, 512, 0, 128, 2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let level = [1024, 512, 512, 128, 2, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let plain = [perfect, gapped, level].map(|b| evaluate_board(b.as_ptr()));
+        set_eval_weights(&EvalWeights { anchor: 10.0, ..DEFAULT_WEIGHTS });
+        let with = [perfect, gapped, level].map(|b| evaluate_board(b.as_ptr()));
+        // Ranks 10 + 9 + 8 + 7
+        assert_eq!(with[0], plain[0] + 340.0);
+        assert_eq!(with[1..], plain[1..]);
+    }
+
+    #[test]
+    fn nan_weights_are_detected_and_rejected() {
+        let _g = fresh();
+        let nan = EvalWeights { snake: f64::NAN, ..DEFAULT_WEIGHTS };
+        let inf = EvalWeights { empty: f64::INFINITY, ..DEFAULT_WEIGHTS };
+        let negative = EvalWeights { merges: -1.0, ..DEFAULT_WEIGHTS };
+        assert_eq!(validate_eval_weights(&nan), WEIGHTS_NON_FINITE);
+        assert_eq!(validate_eval_weights(&inf), WEIGHTS_NON_FINITE);
+        assert_eq!(validate_eval_weights(&negative), WEIGHTS_NEGATIVE);
+        assert_eq!(validate_eval_weights(&DEFAULT_WEIGHTS), WEIGHTS_OK);
+        assert_eq!(validate_eval_weights(std::ptr::null()), WEIGHTS_OK);
+
+        let before = evaluate_board(MIDGAME.as_ptr());
+        for set in [set_eval_weights as extern "C" fn(*const EvalWeights), set_survival_weights] {
+            set(&nan);
+            assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+        }
+        let mut kept = DEFAULT_WEIGHTS;
+        get_eval_weights(&mut kept);
+        assert_eq!(kept.values(), DEFAULT_WEIGHTS.values());
+        assert_eq!(evaluate_board(MIDGAME.as_ptr()), before);
+    }
+
+    #[test]
+    fn reverse_penalty_breaks_a_left_right_loop() {
+        let _g = fresh();
+        set_max_depth(2);
+        let start = [0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 32];
+        let top = |tiles: &[u16; 16], last: u8| {
+            let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+            search_ranked_moves_after(tiles.as_ptr(), 1, last, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            dirs[0]
+        };
+        let left = Direction::Left as u8;
+        assert_eq!(top(&start, 0xFF), left);
+        let after = board_tiles(do_move(board(&start), Direction::Left).0);
+        // Unpenalised, Right is best again and undoes Left exactly
+        assert_eq!(top(&after, left), Direction::Right as u8);
+        assert_eq!(board_tiles(do_move(board(&after), Direction::Right).0), start);
+
+        set_reverse_penalty(0.001);
+        assert_ne!(top(&after, left), Direction::Right as u8);
+        assert_eq!(top(&after, 0xFF), Direction::Right as u8);
+        assert_eq!(search_ranked_moves_after(after.as_ptr(), 1, 4, std::ptr::null_mut(), std::ptr::null_mut()), 0);
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    #[test]
+    fn split_scores_add_up_to_the_ranked_scores() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [2, 2, 4, 4, 8, 0, 0, 0, 8, 0, 16, 0, 0, 0, 16, 0];
+        let split = || {
+            let (mut scores, mut dirs, mut positional) = ([0.0; 4], [0u8; 4], [0.0; 4]);
+            let n = search_ranked_moves_split(b.as_ptr(), 1, scores.as_mut_ptr(), dirs.as_mut_ptr(), positional.as_mut_ptr());
+            (0..n as usize).map(|i| (scores[i], dirs[i], positional[i])).collect::<Vec<_>>()
+        };
+        // Off: everything is positional
+        assert!(split().iter().all(|&(score, _, positional)| positional == score));
+
+        set_merge_accumulation(true);
+        let moves = split();
+        assert_eq!(moves.len(), 4);
+        for (score, dir, positional) in moves {
+            let points = do_move(board(&b), Direction::ALL[dir as usize]).1;
+            assert!((score - positional - points).abs() < 1e-6, "{dir}: {score} = {positional} + {points}?");
+        }
+        assert!(split().iter().any(|m| m.0 != m.2));
+    }
+
+    #[test]
+    fn survival_guard_avoids_a_move_a_spawn_can_end() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [
+            128, 8, 64, 32,
+            2, 64, 2, 64,
+            32, 4, 2, 16,
+            8, 32, 2, 64,
+        ];
+        let plain = ranked(&b, 1);
+        let safe_opening = ranked(&OPENING, 1);
+        // Up has the better average, but some spawn after it leaves no move
+        assert_eq!(directions(&plain), [Direction::Up as u8, Direction::Down as u8]);
+        assert!(!survives_spawns(do_move(board(&b), Direction::Up).0));
+        assert!(survives_spawns(do_move(board(&b), Direction::Down).0));
+
+        set_survival_guard(true);
+        assert_eq!(ranked(&b, 1)[0], plain[1]);
+        assert_eq!(ranked(&OPENING, 1), safe_opening);
+    }
+
+    #[test]
+    fn packed_boards_round_trip_and_bad_tiles_are_flagged() {
+        let _g = fresh();
+        let mut rng = Rng(175);
+        let mut out = [0u16; 16];
+        for _ in 0..1000 {
+            let packed = (0..16).fold(0u64, |b, i| b | ((rng.below(16) as u64) << (i * 4)));
+            board_unpack(packed, out.as_mut_ptr());
+            assert_eq!(board_pack(out.as_ptr()), packed);
+        }
+        // Every rank up to 32768 survives the trip
+        let all: [u16; 16] = std::array::from_fn(|i| if i == 0 { 0 } else { 1 << i });
+        board_unpack(board_pack(all.as_ptr()), out.as_mut_ptr());
+        assert_eq!(out, all);
+
+        for bad in [1, 3, 96] {
+            let mut tiles = all;
+            tiles[7] = bad;
+            assert_eq!(board_pack(tiles.as_ptr()), 0);
+            assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+        }
+    }
+
+    #[test]
+    fn eviction_keeps_the_principal_variation() {
+        let _g = fresh();
+        set_max_depth(3);
+        let ctx = search_context_new();
+        let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+        search_with_context(ctx, OPENING.as_ptr(), 2, scores.as_mut_ptr(), dirs.as_mut_ptr());
+        let TransTable::Map { map, pv } = &unsafe { &*ctx }.tt else { panic!("expected the HashMap TT") };
+        let (before, pv) = (map.clone(), pv.clone());
+        // The played move's afterstate leads, then a best reply per spawn
+        assert_eq!(pv[0], do_move(board(&OPENING), Direction::ALL[dirs[0] as usize]).0);
+        assert!(pv.len() > 1);
+        let cached: HashSet<BB> = pv.iter().copied().filter(|k| before.contains_key(k)).collect();
+        assert!(cached.len() > 1);
+
+        let left = search_context_evict(ctx);
+        let TransTable::Map { map, .. } = &unsafe { &*ctx }.tt else { unreachable!() };
+        assert_eq!(left as usize, cached.len());
+        assert!(left < before.len() as u64);
+        for k in &cached {
+            assert_eq!(map.get(k), before.get(k));
+        }
+        search_context_free(ctx);
+    }
+
+    #[test]
+    fn empty_delta_counts_the_cells_merges_free() {
+        let _g = fresh();
+        // One column against the right wall: vertical moves merge both pairs,
+        // Left only slides and Right cannot move
+        let b = [0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 4];
+        let mut out = [0i8; 4];
+        empty_delta(b.as_ptr(), out.as_mut_ptr());
+        assert_eq!(out, [2, 2, 0, EMPTY_DELTA_ILLEGAL]);
+
+        let mut untouched = [7i8; 4];
+        empty_delta([3u16; 16].as_ptr(), untouched.as_mut_ptr());
+        assert_eq!((untouched, last_error()), ([7; 4], ErrorCode::MalformedBoard as u32));
+        empty_delta(b.as_ptr(), std::ptr::null_mut());
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+    }
+
+    #[test]
+    fn snake_blend_lands_between_near_tied_orientations() {
+        let _g = fresh();
+        // Nearly symmetric about the diagonal: the 2 only extends the row snake
+        let b = [1024, 512, 2, 0, 512, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut sums = snake_sums(board(&b), 1.0);
+        sums.sort_by(|x, y| y.total_cmp(x));
+        let (best, second) = (sums[0], sums[1]);
+        assert!(second < best && best - second < 0.01 * best);
+
+        let blended = |margin| snake_term(board(&b), 1.0, &Config { snake_blend: margin, ..DEFAULT_CONFIG });
+        assert_eq!(blended(0.0), best);
+        let mid = blended(0.1);
+        assert!(second < mid && mid < best, "{second} < {mid} < {best}");
+        // Outside the band the best stands alone
+        assert_eq!(blended(0.5 * (best - second) / best), best);
+
+        let snake = TERM_SNAKE.trailing_zeros() as usize;
+        let component = || {
+            let mut terms = [0.0; NUM_TERMS as usize];
+            evaluate_components(b.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+            terms[snake]
+        };
+        set_eval_weights(&EvalWeights { snake: 1.0, ..DEFAULT_WEIGHTS });
+        assert_eq!(component(), best);
+        set_snake_blend(0.1);
+        assert_eq!(component(), mid);
+    }
+
+    #[test]
+    fn empties_first_objective_reorders_the_ranking() {
+        extern "C" fn empties_first(value: f64, _merge_score: f64, empty_cells: u32) -> f64 {
+            empty_cells as f64 * 1e9 + value
+        }
+        extern "C" fn value_only(value: f64, _merge_score: f64, _empty_cells: u32) -> f64 {
+            value
+        }
+        let _g = fresh();
+        set_max_depth(2);
+        // Left scores best, but only Up and Down merge
+        let b = [0, 0, 0, 2, 0, 4, 0, 2, 16, 2, 0, 0, 0, 16, 2, 0];
+        let plain = ranked(&b, 1);
+        let mut by_empties = plain.clone();
+        let empties = |d: u8| count_empty(do_move(board(&b), Direction::ALL[d as usize]).0);
+        by_empties.sort_by(|x, y| empties(y.1).cmp(&empties(x.1)).then(y.0.total_cmp(&x.0)));
+        assert_ne!(directions(&by_empties)[0], directions(&plain)[0]);
+
+        set_move_objective(Some(value_only));
+        assert_eq!(ranked(&b, 1), plain);
+        set_move_objective(Some(empties_first));
+        assert_eq!(directions(&ranked(&b, 1)), directions(&by_empties));
+        set_move_objective(None);
+        assert_eq!(ranked(&b, 1), plain);
+    }
+
+    #[test]
+    fn spawn_risk_steers_toward_the_move_that_copes_with_fours() {
+        let _g = fresh();
+        set_max_depth(2);
+        let b = [2, 2, 0, 0, 16, 0, 0, 0, 0, 16, 0, 0, 16, 0, 0, 0];
+        let standard = ranked(&b, 1);
+        assert!(set_spawn_distribution([4u16].as_ptr(), [1.0].as_ptr(), 1));
+        let fours_only = ranked(&b, 1)[0].1;
+        assert!(set_spawn_distribution(std::ptr::null(), std::ptr::null(), 0));
+        assert_ne!(standard[0].1, fours_only);
+
+        set_spawn_risk(SPAWN_RISK_MAX);
+        assert_eq!(ranked(&b, 1)[0].1, fours_only);
+        // Risk 2 weighs the standard odds as 75/25
+        set_spawn_risk(2.0);
+        let risk_two = ranked(&b, 1);
+        set_spawn_risk(1.0);
+        assert_eq!(ranked(&b, 1), standard);
+        assert!(set_spawn_distribution([2u16, 4].as_ptr(), [0.75, 0.25].as_ptr(), 2));
+        for (r, q) in risk_two.iter().zip(ranked(&b, 1)) {
+            assert_eq!(r.1, q.1);
+            assert!((r.0 - q.0).abs() < 1e-9 * q.0);
+        }
+    }
+
+    #[test]
+    fn adversarial_search_plays_against_the_worst_spawn() {
+        let _g = fresh();
+        set_max_depth(2);
+        let adversarial = |tiles: &[u16; 16]| {
+            let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+            let n = search_ranked_moves_adversarial(tiles.as_ptr(), 1, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            (0..n as usize).map(|i| (scores[i], dirs[i])).collect::<Vec<_>>()
+        };
+        // Right has the best average, but its worst spawn costs it most
+        let b = [
+            16, 0, 4, 32,
+            0, 4, 0, 64,
+            32, 0, 128, 128,
+            0, 128, 128, 0,
+        ];
+        let average = ranked(&b, 1);
+        let worst_case = adversarial(&b);
+        assert_eq!((average[0].1, worst_case[0].1), (Direction::Right as u8, Direction::Down as u8));
+        for (score, dir) in &worst_case {
+            assert!(*score < average.iter().find(|m| m.1 == *dir).unwrap().0);
+        }
+
+        // Each move is worth its worst spawn, whatever the odds (one ply deep,
+        // so no adaptive deepening)
+        set_max_depth(1);
+        for (score, dir) in adversarial(&OPENING) {
+            let after = do_move(board(&OPENING), Direction::ALL[dir as usize]).0;
+            let worst = (0..16).filter(|&i| (after >> (i * 4)) & 0xF == 0)
+                .flat_map(|i| [1u64, 2].map(|rank| after | (rank << (i * 4))))
+                .map(|s| Direction::ALL.iter().map(|&r| do_move(s, r)).filter(|m| m.2).map(|m| evaluate(m.0)).fold(0.0, f64::max))
+                .fold(f64::INFINITY, f64::min);
+            assert!((score - worst).abs() < 1e-9 * worst, "{dir}: {score} vs {worst}");
+        }
+    }
+
+    #[test]
+    fn merge_readiness_counts_equal_neighbours() {
+        let _g = fresh();
+        // A plus of 8s around cell 5, a 4 across a gap from another 4
+        let b = [
+            0, 8, 0, 4,
+            8, 8, 8, 0,
+            0, 8, 0, 4,
+            2, 0, 0, 0,
+        ];
+        let mut out = [0xFFu8; 16];
+        assert_eq!(merge_readiness(b.as_ptr(), out.as_mut_ptr(), 16), 16);
+        assert_eq!(out, [
+            0, 1, 0, 0,
+            1, 4, 1, 0,
+            0, 1, 0, 0,
+            0, 0, 0, 0,
+        ]);
+        let mut short = [0xFFu8; 8];
+        assert_eq!(merge_readiness(b.as_ptr(), short.as_mut_ptr(), 6), 6);
+        assert_eq!(short, [0, 1, 0, 0, 1, 4, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn resumed_checkpoint_replays_the_rest_of_the_game() {
+        let _g = fresh();
+        struct Trace { moves: Vec<([u16; 16], u8, f64)>, save_at: usize, blob: [u8; GAME_STATE_LEN as usize] }
+        extern "C" fn on_move(user: *mut c_void, board: *const u16, dir: u8, score: f64) -> i32 {
+            let t = unsafe { &mut *(user as *mut Trace) };
+            t.moves.push((unsafe { *(board as *const [u16; 16]) }, dir, score));
+            if t.moves.len() == t.save_at {
+                assert_eq!(game_state_save(t.blob.as_mut_ptr(), GAME_STATE_LEN), GAME_STATE_LEN);
+            }
+            0
+        }
+        let new_trace = |save_at| Trace { moves: Vec::new(), save_at, blob: [0; GAME_STATE_LEN as usize] };
+        let mut full = new_trace(150);
+        let played = play_game_stream(11, 0, Some(on_move), &mut full as *mut Trace as *mut c_void);
+        assert!(played > 150 && full.moves.len() == played as usize);
+
+        // Load the move-150 checkpoint and play on: the same moves follow
+        assert!(game_state_load(full.blob.as_ptr(), GAME_STATE_LEN));
+        let mut rest = new_trace(0);
+        let resumed = play_game_resume(0, Some(on_move), &mut rest as *mut Trace as *mut c_void);
+        assert_eq!(resumed, played - 150);
+        assert!(rest.moves == full.moves[150..]);
+
+        let mut bad = full.blob;
+        bad[4] = GAME_STATE_VERSION + 1;
+        assert!(!game_state_load(bad.as_ptr(), GAME_STATE_LEN));
+        assert_eq!(last_error(), ErrorCode::MalformedData as u32);
+        assert!(!game_state_load(full.blob.as_ptr(), GAME_STATE_LEN - 1));
+    }
+
+    #[test]
+    fn snake_power_reshapes_the_snake_term() {
+        let _g = fresh();
+        let snake = TERM_SNAKE.trailing_zeros() as usize;
+        let table = unsafe { &*std::ptr::addr_of!(TBL_SNAKE) };
+        let mut values = Vec::new();
+        for power in [1.0, 0.5, 1.5, 2.0] {
+            set_eval_weights(&EvalWeights { snake: 1.0, snake_power: power, ..DEFAULT_WEIGHTS });
+            let mut terms = [0.0; NUM_TERMS as usize];
+            evaluate_components(MIDGAME.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+            // Best orientation of Σ weight × value^power, empty cells 0
+            let expected = table.iter().map(|w| {
+                MIDGAME.iter().zip(w).map(|(&t, &wt)| if t == 0 { 0.0 } else { (t as f64).powf(power) * wt }).sum::<f64>()
+            }).fold(0.0, f64::max);
+            assert!((terms[snake] - expected).abs() <= 1e-9 * expected, "power {power}: {} vs {expected}", terms[snake]);
+            values.push(terms[snake]);
+        }
+        assert!(values[1] < values[0] && values[0] < values[2] && values[2] < values[3]);
+        assert_eq!(DEFAULT_WEIGHTS.snake_power, 1.0);
+    }
+
+    #[test]
+    fn outcome_estimate_spans_lost_to_won() {
+        let _g = fresh();
+        let estimate = |b: &[u16; 16]| game_outcome_estimate(b.as_ptr(), 2);
+        let won = [2048, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let one_merge_away = [1024, 1024, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let fresh_game = [0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0];
+        let locked = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 2, 4, 4, 2, 4, 2];
+        // One empty cell, no merges: most spawns there end the game
+        let near_dead = [2, 64, 4, 32, 32, 4, 16, 4, 16, 8, 32, 16, 64, 4, 2, 0];
+        assert_eq!(estimate(&won), 1.0);
+        assert!(estimate(&one_merge_away) > 0.95);
+        assert!(estimate(&fresh_game).abs() < 0.05);
+        assert_eq!(estimate(&locked), -1.0);
+        assert!(estimate(&near_dead) < -0.8);
+
+        assert!(game_outcome_estimate(won.as_ptr(), 0).is_nan());
+        assert_eq!(last_error(), ErrorCode::InvalidDepth as u32);
+    }
+
+    #[test]
+    fn target_pattern_steers_toward_the_templated_cells() {
+        let _g = fresh();
+        set_max_depth(2);
+        let plain = ranked(&OPENING, 1);
+        // Template the tiles where the runner-up move leaves them
+        let wanted = Direction::ALL[plain[1].1 as usize];
+        let after = board_tiles(do_move(board(&OPENING), wanted).0);
+        let template = after.map(|t| if t == 0 { PATTERN_ANY } else { t });
+        assert!(set_target_pattern(template.as_ptr()));
+        assert_eq!(ranked(&OPENING, 1), plain);
+        set_eval_weights(&EvalWeights { pattern: 1000.0, ..DEFAULT_WEIGHTS });
+        assert_eq!(ranked(&OPENING, 1)[0].1, wanted as u8);
+
+        // The term charges rank² per broken cell
+        let pattern = TERM_PATTERN.trailing_zeros() as usize;
+        let charge = |b: &[u16; 16]| {
+            let mut terms = [0.0; NUM_TERMS as usize];
+            evaluate_components(b.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+            terms[pattern]
+        };
+        assert_eq!(charge(&after), 0.0);
+        let mut broken = after;
+        let i = (0..16).find(|&i| after[i] != 0).unwrap();
+        broken[i] = 0;
+        let rank = after[i].trailing_zeros() as f64;
+        assert_eq!(charge(&broken), -1000.0 * rank * rank);
+
+        assert!(!set_target_pattern([3u16; 16].as_ptr()));
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn phase_timings_add_up_within_the_search_time() {
+        let _g = fresh();
+        set_max_depth(2);
+        ranked(&MIDGAME, 2);
+        let mut t = SearchTimings::default();
+        get_search_timings(&mut t);
+        let phases = [t.moves_ns, t.eval_ns, t.tt_ns, t.cells_ns];
+        assert!(phases.iter().all(|&ns| ns > 0), "{phases:?}");
+        let sum: u64 = phases.iter().sum();
+        // The phases never overlap; the remainder is the tree walk itself
+        assert!(sum <= t.total_ns, "{sum} of {}", t.total_ns);
+        let stats = STATS.with(|s| *s.borrow());
+        assert_eq!((t.stats.evals, t.stats.depth), (stats.evals, stats.depth));
+    }
+
+    #[test]
+    fn three_by_four_boards_move_and_evaluate_within_the_shape() {
+        let _g = fresh();
+        set_max_depth(2);
+        assert!(set_board_shape(3, 4));
+        for (rows, cols) in [(5, 4), (1, 4), (4, 0)] {
+            assert!(!set_board_shape(rows, cols));
+            assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+        }
+        let (mut rows, mut cols) = (0, 0);
+        get_board_shape(&mut rows, &mut cols);
+        assert_eq!((rows, cols), (3, 4));
+
+        // Down stops at the third row; the fourth is wall
+        let b = [2, 2, 0, 4, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0];
+        let down = board_tiles(do_move(board(&b), Direction::Down).0);
+        assert_eq!(down, [0, 0, 0, 0, 0, 2, 0, 0, 2, 8, 0, 4, 0, 0, 0, 0]);
+        // Spawns stay on the board too
+        let after = do_move(board(&b), Direction::Down).0;
+        let (cells, n) = chance_cells(after, 16);
+        assert_eq!(n, 8);
+        assert!(cells[..n].iter().all(|&i| i < 12));
+
+        // Three full, unmergeable rows are lost here, though 4×4 could slide
+        let locked = [2, 4, 2, 4, 4, 2, 4, 2, 2, 4, 2, 4, 0, 0, 0, 0];
+        assert!(ranked(&locked, 1).is_empty());
+        assert!(evaluate_board(b.as_ptr()) > evaluate_board(locked.as_ptr()));
+        let mut off_board = b;
+        off_board[12] = 2;
+        assert!(evaluate_board(off_board.as_ptr()).is_nan());
+        assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+
+        assert!(set_board_shape(4, 4));
+        assert!(!ranked(&locked, 1).is_empty());
+    }
+
+    #[test]
+    fn alternative_lines_start_with_their_root_moves() {
+        let _g = fresh();
+        set_max_depth(2);
+        let expect = ranked(&OPENING, 1);
+        let (mut dirs, mut scores) = ([0u8; 4 * 3], [0.0; 4]);
+        assert_eq!(alternative_lines(OPENING.as_ptr(), 1, 9, 3, dirs.as_mut_ptr(), scores.as_mut_ptr()), MAX_LINES);
+        for (line, (_, dir)) in dirs.chunks(3).zip(&expect) {
+            assert_eq!(line[0], *dir);
+            // Each later step is legal where the line has got to
+            let mut b = board(&OPENING);
+            for &d in line.iter().take_while(|&&d| d != 0xFF) {
+                let (nb, _, moved) = do_move(b, Direction::ALL[d as usize]);
+                assert!(moved, "{line:?}");
+                b = nb;
+            }
+        }
+        assert_eq!(scores.to_vec(), expect.iter().map(|m| m.0).collect::<Vec<_>>());
+
+        let mut two = [0u8; 2 * 3];
+        assert_eq!(alternative_lines(OPENING.as_ptr(), 1, 2, 3, two.as_mut_ptr(), std::ptr::null_mut()), 2);
+        assert_eq!(two, dirs[..6]);
+        assert_eq!(alternative_lines(OPENING.as_ptr(), 1, 2, 0, two.as_mut_ptr(), std::ptr::null_mut()), 0);
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
+    }
+
+    #[test]
+    fn replays_verify_and_tampering_is_located() {
+        let _g = fresh();
+        set_max_depth(2);
+        // Record 30 turns of greedy play with random spawns
+        let mut rng = Rng(192);
+        let (mut b, mut score) = (board(&OPENING), 0.0);
+        let (mut dirs, mut spawns) = (Vec::new(), Vec::new());
+        for _ in 0..30 {
+            let d = Direction::ALL[greedy_move(board_tiles(b).as_ptr()) as usize];
+            let (nb, ms, _) = do_move(b, d);
+            let empty: Vec<u64> = (0..16).filter(|&i| (nb >> (i * 4)) & 0xF == 0).collect();
+            let cell = empty[rng.below(empty.len() as u32) as usize];
+            let rank = if rng.below(10) == 0 { 2 } else { 1 };
+            b = nb | (rank << (cell * 4));
+            score += ms;
+            dirs.push(d as u8);
+            spawns.extend([cell as u16, 1 << rank]);
+        }
+        let verify = |dirs: &[u8], spawns: &[u16]| verify_replay(OPENING.as_ptr(), dirs.as_ptr(), spawns.as_ptr(), dirs.len() as u32);
+        assert!(score > 0.0);
+        assert_eq!(verify(&dirs, &spawns), score as i32);
+        assert_eq!(verify(&dirs[..0], &spawns[..0]), 0);
+
+        // Turn 12 spawns on an occupied cell, an 8, or plays a move that does not slide
+        let before = (0..12).fold(board(&OPENING), |b, i| {
+            do_move(b, Direction::ALL[dirs[i] as usize]).0 | (spawns[2 * i + 1].trailing_zeros() as u64) << (spawns[2 * i] * 4)
+        });
+        let moved = do_move(before, Direction::ALL[dirs[12] as usize]).0;
+        let (mut occupied, mut eight, mut stuck) = (spawns.clone(), spawns.clone(), dirs.clone());
+        occupied[2 * 12] = (0..16).find(|&i| (moved >> (i * 4)) & 0xF != 0).unwrap();
+        eight[2 * 12 + 1] = 8;
+        stuck[12] = Direction::ALL.iter().find(|&&d| !do_move(before, d).2).map_or(9, |&d| d as u8);
+        for (d, s) in [(&dirs, &occupied), (&dirs, &eight), (&stuck, &spawns)] {
+            assert_eq!(verify(d, s), -13);
+        }
+        assert_eq!(verify_replay(OPENING.as_ptr(), std::ptr::null(), spawns.as_ptr(), 1), REPLAY_ERR_ARGS);
+        assert_eq!(last_error(), ErrorCode::NullPointer as u32);
+    }
+
+    #[test]
+    fn scatter_falloff_charges_near_misses_less_than_opposite_corners() {
+        let _g = fresh();
+        let place = |cells: [usize; 2]| {
+            let mut b = [0u16; 16];
+            for i in cells { b[i] = 256; }
+            b
+        };
+        let (touching, two_apart, corners) = (place([0, 1]), place([0, 2]), place([0, 15]));
+        let scatter = TERM_SCATTER.trailing_zeros() as usize;
+        let charge = |reach, falloff| {
+            set_eval_weights(&EvalWeights { scatter: 1.0, scatter_reach: reach, scatter_falloff: falloff, ..DEFAULT_WEIGHTS });
+            [touching, two_apart, corners].map(|b| {
+                let mut terms = [0.0; NUM_TERMS as usize];
+                evaluate_components(b.as_ptr(), terms.as_mut_ptr(), NUM_TERMS);
+                -terms[scatter]
+            })
+        };
+        // Both 256s (rank 8) count, at rank² each
+        assert_eq!(charge(1.0, 0.0), [0.0, 128.0, 128.0]);
+        // Distance 2 of at most 6
+        let near = charge(1.0, 1.0);
+        assert_eq!((near[0], near[2]), (0.0, 128.0));
+        assert!((near[1] - 128.0 / 3.0).abs() < 1e-9);
+        assert_eq!(charge(2.0, 0.0), [0.0, 0.0, 128.0]);
+    }
+
+    #[test]
+    fn tiny_tt_arrays_collide_and_large_ones_hardly_do() {
+        let _g = fresh();
+        set_max_depth(3);
+        let rate = |bits| {
+            set_tt_array(bits);
+            ranked(&MIDGAME, 3);
+            let s = STATS.with(|s| *s.borrow());
+            (get_tt_collision_rate(), s.tt_collisions, s.tt_probes)
+        };
+        let (tiny, large, map) = (rate(10), rate(20), rate(0));
+        // 1024 slots are too few for this tree and evicted positions get
+        // searched again; 2^20 slots hold it as the HashMap does
+        assert!(tiny.0 > 0.1 && tiny.2 > large.2, "{tiny:?}");
+        assert_eq!((large.0, large.2), (0.0, map.2));
+        assert_eq!(map.0, 0.0);
+        assert!(map.2 > 0);
+    }
+
+    #[test]
+    fn mirror_averaging_ties_the_moves_of_a_symmetric_opening() {
+        let _g = fresh();
+        set_max_depth(2);
+        // Invariant under all 8 symmetries, so no move is really better.
+        // (The empty board has no legal move at all.)
+        let b = [0, 0, 0, 0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 0, 0, 0];
+        let plain = ranked(&b, 1);
+        // Rounding alone splits them by a few ulps
+        assert!(plain.iter().any(|m| m.0 != plain[0].0));
+        set_mirror_averaging(8);
+        let mirrored = ranked(&b, 1);
+        assert_eq!(mirrored.len(), 4);
+        assert!(mirrored.iter().all(|m| m.0 == mirrored[0].0), "{mirrored:?}");
+        // Too few empty cells: searched as usual
+        set_mirror_averaging(12);
+        assert_eq!(ranked(&b, 1), plain);
+    }
+
+    #[test]
+    fn timed_search_returns_the_deepest_completed_ranking() {
+        let _g = fresh();
+        set_max_depth(7);
+        let timed = |depth, ms| {
+            let (mut scores, mut dirs) = ([0.0; 4], [0u8; 4]);
+            let start = Instant::now();
+            let n = search_ranked_moves_timed(OPENING.as_ptr(), depth, ms, scores.as_mut_ptr(), dirs.as_mut_ptr());
+            let elapsed = start.elapsed();
+            let reached = STATS.with(|s| s.borrow().depth);
+            let moves: Vec<(f64, u8)> = (0..n as usize).map(|i| (scores[i], dirs[i])).collect();
+            (moves, reached, elapsed)
+        };
+        // A deadline halfway through the depth-7 iteration, pinned down:
+        // depth 6 is returned exactly as a search of that depth alone ranks it
+        let expect = ranked(&OPENING, 6);
+        let nodes = STATS.with(|s| s.borrow().move_nodes);
+        ABORT_AT.with(|a| a.set(Some((7, nodes / 2))));
+        let (moves, reached, _) = timed(7, 60_000);
+        ABORT_AT.with(|a| a.set(None));
+        assert_eq!((moves, reached), (expect, 6));
+
+        // The watchdog cuts a search short on the clock, at whatever depth
+        let (moves, reached, elapsed) = timed(7, 1);
+        assert!(elapsed < Duration::from_millis(2000), "{elapsed:?}");
+        assert_eq!(moves, ranked(&OPENING, reached));
+
+        let (moves, reached, _) = timed(2, 2000);
+        assert_eq!((moves, reached), (ranked(&OPENING, 2), 2));
+        let (moves, reached, _) = timed(7, 0);
+        assert_eq!((moves, reached), (ranked(&OPENING, 0), 0));
+    }
+
+    #[test]
+    fn max_achievable_tile_rounds_the_sum_down() {
+        let _g = fresh();
+        let three_thousand = [2048, 512, 256, 128, 32, 16, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(max_achievable_tile(three_thousand.as_ptr()), 2048);
+        let exact = [2048, 1024, 512, 256, 128, 64, 32, 16, 8, 4, 2, 2, 2, 2, 0, 0];
+        assert_eq!(max_achievable_tile(exact.as_ptr()), 4096);
+        assert_eq!(max_achievable_tile([32768u16; 16].as_ptr()), 32768);
+        assert_eq!(max_achievable_tile([0u16; 16].as_ptr()), 0);
+        assert_eq!(max_achievable_tile([3u16; 16].as_ptr()), 0);
+        assert_eq!(last_error(), ErrorCode::MalformedBoard as u32);
+    }
+
+    #[test]
+    fn corner_move_hint_mentions_the_corner() {
+        let _g = fresh();
+        set_max_depth(2);
+        // Right takes 2048 into a corner, Down (the only other move) does not
+        let b = [4, 2048, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        set_eval_weights(&EvalWeights { corner: 100.0, ..DEFAULT_WEIGHTS });
+        let mut buf = [0 as c_char; HINT_MAX_LEN as usize];
+        let n = move_hint(b.as_ptr(), 1, buf.as_mut_ptr(), HINT_MAX_LEN);
+        let text = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap().to_owned();
+        assert_eq!(n as usize, text.len());
+        assert!(text.starts_with("right: ") && text.contains("keeps 2048 in corner"), "{text}");
+
+        // A short buffer gets a NUL-terminated prefix and the full length
+        let mut short = [0x7F as c_char; 8];
+        assert_eq!(move_hint(b.as_ptr(), 1, short.as_mut_ptr(), 6), n);
+        let prefix = unsafe { std::ffi::CStr::from_ptr(short.as_ptr()) }.to_str().unwrap();
+        assert_eq!((prefix, short[6]), (&text[..5], 0x7F));
+    }
+
+    #[test]
+    fn corner_growth_scales_the_corner_term_late_in_the_game() {
+        let _g = fresh();
+        let corner = TERM_CORNER.trailing_zeros() as usize;
+        let high = [2048, 512, 8, 2, 4, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let low = [8, 4, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let terms = |b: &[u16; 16], tile, fill| {
+            set_eval_weights(&EvalWeights { corner: 1.0, corner_tile_growth: tile, corner_fill_growth: fill, ..DEFAULT_WEIGHTS });
+            let mut t = [0.0; NUM_TERMS as usize];
+            evaluate_components(b.as_ptr(), t.as_mut_ptr(), NUM_TERMS);
+            t
+        };
+        let (plain_high, plain_low) = (terms(&high, 0.0, 0.0), terms(&low, 0.0, 0.0));
+        // Full growth at 2048 (rank 11), 3/11 of it at 8
+        assert_eq!(terms(&high, 1.0, 0.0)[corner], 2.0 * plain_high[corner]);
+        assert!((terms(&low, 1.0, 0.0)[corner] - plain_low[corner] * 14.0 / 11.0).abs() < 1e-9);
+        // 6 of 16 cells filled
+        assert_eq!(terms(&high, 0.0, 1.0)[corner], plain_high[corner] * (1.0 + 6.0 / 16.0));
+
+        let share = |t: [f64; NUM_TERMS as usize]| t[corner] / t.iter().map(|v| v.abs()).sum::<f64>();
+        assert!(share(terms(&high, 1.0, 0.0)) > share(plain_high));
+    }
+
+    #[test]
+    fn breathing_counts_empty_cells_at_the_snake_tail() {
+        let _g = fresh();
+        let breathing = TERM_BREATHING.trailing_zeros() as usize;
+        let term = |b: &[u16; 16]| {
+            let mut t = [0.0; NUM_TERMS as usize];
+            evaluate_components(b.as_ptr(), t.as_mut_ptr(), NUM_TERMS);
+            t[breathing]
+        };
+        let tail_open = [1024, 512, 256, 128, 8, 16, 32, 64, 4, 2, 4, 2, 0, 0, 0, 0];
+        let head_open = [0, 0, 0, 0, 8, 16, 32, 64, 4, 2, 4, 2, 1024, 512, 256, 128];
+        set_eval_weights(&EvalWeights { breathing: 50.0, ..DEFAULT_WEIGHTS });
+        // Pinned to the snake from the top-left corner, only the tail counts
+        assert!(pin_orientation(0));
+        assert_eq!((term(&tail_open), term(&head_open)), (200.0, 0.0));
+        // Unpinned, the snake turns to start at the big tiles
+        unpin_orientation();
+        assert_eq!((term(&tail_open), term(&head_open)), (200.0, 200.0));
+    }
+
+    #[test]
+    fn csv_export_has_a_header_and_a_row_per_game() {
+        let _g = fresh();
+        let cap = play_games_csv_capacity(3);
+        let mut buf = vec![0 as c_char; cap as usize];
+        let len = play_games_csv(40, 3, 0, buf.as_mut_ptr(), cap);
+        let text = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap().to_owned();
+        assert_eq!(len as usize, text.len());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "seed,score,max_tile,moves");
+        for (row, seed) in lines[1..].iter().zip(40u64..) {
+            let fields: Vec<&str> = row.split(',').collect();
+            let (moves, score, top) = game_outcome(seed, 0);
+            assert_eq!(fields, [seed.to_string(), format!("{score:.0}"), (1u32 << top).to_string(), moves.to_string()]);
+        }
+
+        assert_eq!(play_games_csv(40, 3, 0, buf.as_mut_ptr(), cap - 1), 0);
+        assert_eq!(last_error(), ErrorCode::InvalidArgument as u32);
     }
-    n as u32
 }